@@ -0,0 +1,55 @@
+//! Throughput of the interpreter and the lexer, run with `cargo bench`
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use mylib::{default_buildins, execute, parse, Lexer, Map};
+
+const FIBONACCI: &str = "
+fn fib(n: i32) -> i32 { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }
+fn main() { fib(30) }";
+
+// there is no while loop, for-in over a range is the only loop of the language
+const SUM_LOOP: &str = "
+fn main() {
+    sum = 0;
+    for i in 0..1000 { sum = sum + i; sum };
+    sum
+}";
+
+// strings have no `+`, they are concatenated by joining
+const CONCAT_LOOP: &str = "
+fn main() {
+    s = \"\";
+    for _i in 0..500 { s = join([s, \"x\"], \"\"); s };
+    s
+}";
+
+/// Parse `source` once and measure only its execution
+fn bench_program(c: &mut Criterion, name: &str, source: &str) {
+    let program = parse(source).unwrap();
+    let mut buildins = default_buildins();
+    c.bench_function(name, |b| {
+        b.iter(|| execute(black_box(&program), &mut Map::new(), &mut buildins).unwrap())
+    });
+}
+
+fn eval(c: &mut Criterion) {
+    bench_program(c, "sum_loop_1000", SUM_LOOP);
+    bench_program(c, "concat_loop_500", CONCAT_LOOP);
+    let mut slow = c.benchmark_group("slow");
+    slow.sample_size(10);
+    let program = parse(FIBONACCI).unwrap();
+    slow.bench_function("fibonacci_30", |b| {
+        b.iter(|| execute(black_box(&program), &mut Map::new(), &mut Map::new()).unwrap())
+    });
+    slow.finish();
+}
+
+fn lexer(c: &mut Criterion) {
+    let program = include_str!("../example_programs/fizzbuzz.srs");
+    let source = program.repeat(10 * 1024 / program.len() + 1);
+    c.bench_function("lex_10kb", |b| {
+        b.iter(|| Lexer::new(black_box(&source)).count())
+    });
+}
+
+criterion_group!(benches, eval, lexer);
+criterion_main!(benches);
@@ -0,0 +1,38 @@
+//! Loading a program from `Program::to_bytes` compared with parsing its source, run with
+//! `cargo bench --features cache`
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use mylib::{parse, Program};
+
+/// Source of about three hundred lines, functions calling each other in a chain
+fn fixture() -> String {
+    let mut source = String::new();
+    for i in 0..50 {
+        source += &format!(
+            "/// Step {i} of the chain
+fn step{i}(n: i32) -> i32 {{
+    doubled = n * 2;
+    if doubled > 1000 {{ doubled % 1000 }} else {{ step{next}(doubled + {i}) }}
+}}
+
+",
+            i = i,
+            next = (i + 1) % 50
+        );
+    }
+    source += "fn main() { step0(1) }\n";
+    source
+}
+
+fn load(c: &mut Criterion) {
+    let source = fixture();
+    let bytes = parse(&source).unwrap().to_bytes();
+    let mut group = c.benchmark_group("load");
+    group.bench_function("parse", |b| b.iter(|| parse(black_box(&source)).unwrap()));
+    group.bench_function("from_bytes", |b| {
+        b.iter(|| Program::from_bytes(black_box(&bytes)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, load);
+criterion_main!(benches);
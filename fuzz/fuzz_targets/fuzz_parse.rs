@@ -0,0 +1,7 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// the lexer and the parser must report an error instead of panicking on any input
+fuzz_target!(|data: &[u8]| {
+    let _ = mylib::parse(std::str::from_utf8(data).unwrap_or(""));
+});
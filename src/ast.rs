@@ -1,51 +1,352 @@
-use serde::Serialize;
-use std::collections::HashMap;
-use std::fmt;
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use crate::Map;
+use core::fmt;
+use core::ops::Index;
+#[cfg(feature = "serde")]
+use serde::de::{self, MapAccess};
+#[cfg(feature = "serde")]
+use serde::ser::SerializeMap;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Debug, Eq, PartialEq, Serialize)]
+#[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct ArgList {
     pub args: Vec<VarVal>,
 }
 
-#[derive(Debug, Eq, PartialEq, Serialize)]
+/// Parsed program, it is immutable during execution so one `Program` can be run
+/// by several threads at once, each with its own globals and builtins
+///
+/// Deserializing rejects unknown fields of the nodes, so JSON written by a different
+/// version of the AST fails to load instead of losing parts of the program
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Program {
-    pub functions: HashMap<String, Function>,
+    pub functions: Functions,
+    pub structs: Map<String, StructDef>,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+/// Functions of a program in the order they were added, serialized as an object
+/// from names to functions in that order
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+pub struct Functions {
+    functions: Vec<Function>,
+    /// Position of each function in `functions` by name
+    index: Map<String, usize>,
+}
+
+impl Functions {
+    pub fn new() -> Self {
+        Functions::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Function> {
+        self.index.get(name).map(|&i| &self.functions[i])
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.index.contains_key(name)
+    }
+
+    /// Add the function, one of the same name is replaced in its place and returned
+    pub fn insert(&mut self, function: Function) -> Option<Function> {
+        match self.index.get(&function.name) {
+            Some(&i) => Some(core::mem::replace(&mut self.functions[i], function)),
+            None => {
+                self.index
+                    .insert(function.name.clone(), self.functions.len());
+                self.functions.push(function);
+                None
+            }
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.functions.iter().map(|f| &f.name)
+    }
+
+    pub fn values(&self) -> core::slice::Iter<'_, Function> {
+        self.functions.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.functions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+}
+
+impl Drop for Functions {
+    fn drop(&mut self) {
+        for function in &mut self.functions {
+            drop_block(&mut function.block);
+        }
+    }
+}
+
+impl Index<&str> for Functions {
+    type Output = Function;
+
+    fn index(&self, name: &str) -> &Function {
+        self.get(name).expect("no function of the name")
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Functions {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for function in self.values() {
+            map.serialize_entry(&function.name, function)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Functions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FunctionsVisitor;
+
+        impl<'de> de::Visitor<'de> for FunctionsVisitor {
+            type Value = Functions;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a map from names to functions")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Functions, A::Error> {
+                let mut functions = Functions::new();
+                // the key is a copy of the name, which the function keeps too
+                while let Some((key, function)) = access.next_entry::<String, Function>()? {
+                    if key != function.name {
+                        return Err(de::Error::custom(format_args!(
+                            "function {} is stored under the key {}",
+                            function.name, key
+                        )));
+                    }
+                    if functions.insert(function).is_some() {
+                        return Err(de::Error::custom(format_args!(
+                            "function {} is defined twice",
+                            key
+                        )));
+                    }
+                }
+                Ok(functions)
+            }
+        }
+
+        deserializer.deserialize_map(FunctionsVisitor)
+    }
+}
+
+/// Declaration of a record type `struct Point { x: i32, y: i32 }`
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct StructDef {
+    pub position: usize,
+    /// Byte offset just after the closing `}`, like `end` of every node with a span
+    pub end: usize,
+    pub name: String,
+    pub fields: Vec<Variable>,
+    /// Span of each of the `fields` in the source
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub field_spans: Vec<(usize, usize)>,
+}
+
+/// Top level declaration, the parser produces a list of them which `parse` collects
+/// into a `Program`
+#[cfg(feature = "std")]
+pub enum Item {
+    Function(Function),
+    Struct(StructDef),
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Function {
     pub position: usize,
+    pub end: usize,
     pub arguments: Vec<Variable>,
+    /// Span of each of the `arguments` in the source
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub argument_spans: Vec<(usize, usize)>,
     pub name: String,
+    /// Type declared by `-> T`, checked by `check_types`
+    pub return_type: Option<DataType>,
     pub block: Block,
+    /// Text of the line comments right above the function without the comment markers
+    pub doc: Option<String>,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Variable {
     pub ident: String,
     pub value: VarVal,
+    /// False for names bound by `const`, which can't be assigned again
+    pub is_mutable: bool,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum DataType {
     I32,
     BOOL,
     STRING,
+    CHAR,
+    STRUCT(String),
+    /// Array `[T]` of elements of type `T`
+    ARRAY(Box<DataType>),
+    /// Map `{String: T}` from strings to values of type `T`
+    MAP(Box<DataType>),
     UNIT,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+impl DataType {
+    /// Test if `()` is in the type, it stands for the element type of an empty literal
+    pub(crate) fn has_unknown(&self) -> bool {
+        match self {
+            DataType::UNIT => true,
+            DataType::ARRAY(element) | DataType::MAP(element) => element.has_unknown(),
+            _ => false,
+        }
+    }
+
+    /// Null value of the type, the value of a declared variable before assignment
+    pub fn null_value(&self) -> VarVal {
+        match self {
+            DataType::I32 => VarVal::I32(None),
+            DataType::BOOL => VarVal::BOOL(None),
+            DataType::STRING => VarVal::STRING(None),
+            DataType::CHAR => VarVal::CHAR(None),
+            DataType::STRUCT(name) => VarVal::STRUCT(name.clone(), None),
+            DataType::ARRAY(element) => VarVal::ARRAY((**element).clone(), None),
+            DataType::MAP(value) => VarVal::MAP((**value).clone(), None),
+            DataType::UNIT => VarVal::UNIT,
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DataType::I32 => write!(f, "i32"),
+            DataType::BOOL => write!(f, "bool"),
+            DataType::STRING => write!(f, "String"),
+            DataType::CHAR => write!(f, "char"),
+            DataType::STRUCT(name) => write!(f, "{}", name),
+            DataType::ARRAY(element) => write!(f, "[{}]", element),
+            DataType::MAP(value) => write!(f, "{{String: {}}}", value),
+            DataType::UNIT => write!(f, "()"),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum VarVal {
     I32(Option<i32>),
     BOOL(Option<bool>),
     STRING(Option<String>),
+    CHAR(Option<char>),
+    /// Record of the named struct type with its field values
+    STRUCT(String, Option<Map<String, VarVal>>),
+    /// Array with the type of its elements
+    ARRAY(DataType, Option<Vec<VarVal>>),
+    /// Map from strings with the type of its values
+    MAP(DataType, Option<Map<String, VarVal>>),
     UNIT,
 }
 
+impl VarVal {
+    /// Return the type of the value, null values keep their type
+    pub fn data_type(&self) -> DataType {
+        match self {
+            VarVal::I32(_) => DataType::I32,
+            VarVal::BOOL(_) => DataType::BOOL,
+            VarVal::STRING(_) => DataType::STRING,
+            VarVal::CHAR(_) => DataType::CHAR,
+            VarVal::STRUCT(name, _) => DataType::STRUCT(name.clone()),
+            VarVal::ARRAY(element, _) => DataType::ARRAY(Box::new(element.clone())),
+            VarVal::MAP(value, _) => DataType::MAP(Box::new(value.clone())),
+            VarVal::UNIT => DataType::UNIT,
+        }
+    }
+
+    /// Test if the value can be used as a value of type `ty`, the element type of an
+    /// empty array or map literal is unknown and `()` stands for it, so it fits any type
+    pub(crate) fn fits(&self, ty: &DataType) -> bool {
+        match (self, ty) {
+            (VarVal::ARRAY(element, Some(elements)), DataType::ARRAY(ty))
+                if element.has_unknown() =>
+            {
+                elements.iter().all(|e| e.fits(ty))
+            }
+            (VarVal::MAP(value, Some(entries)), DataType::MAP(ty)) if value.has_unknown() => {
+                entries.values().all(|v| v.fits(ty))
+            }
+            _ => self.data_type() == *ty,
+        }
+    }
+
+    /// Convert the value which `fits` type `ty` to the type, filling in unknown element types
+    pub(crate) fn into_type(self, ty: &DataType) -> VarVal {
+        match (self, ty) {
+            (VarVal::ARRAY(_, Some(elements)), DataType::ARRAY(ty)) => {
+                let elements = elements.into_iter().map(|e| e.into_type(ty)).collect();
+                VarVal::ARRAY((**ty).clone(), Some(elements))
+            }
+            (VarVal::MAP(_, Some(entries)), DataType::MAP(ty)) => {
+                let entries = entries
+                    .into_iter()
+                    .map(|(k, v)| (k, v.into_type(ty)))
+                    .collect();
+                VarVal::MAP((**ty).clone(), Some(entries))
+            }
+            (value, _) => value,
+        }
+    }
+}
+
 impl fmt::Display for VarVal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if let VarVal::UNIT = self {
             write!(f, "()")
+        } else if let VarVal::STRUCT(name, Some(fields)) = self {
+            let mut fields: Vec<_> = fields.iter().collect();
+            fields.sort_by_key(|(field, _)| *field);
+            write!(f, "{} {{", name)?;
+            for (i, (field, value)) in fields.into_iter().enumerate() {
+                let separator = if i == 0 { " " } else { ", " };
+                write!(f, "{}{}: {}", separator, field, value)?;
+            }
+            write!(f, " }}")
+        } else if let VarVal::ARRAY(_, Some(elements)) = self {
+            write!(f, "[")?;
+            for (i, element) in elements.iter().enumerate() {
+                let separator = if i == 0 { "" } else { ", " };
+                write!(f, "{}{}", separator, element)?;
+            }
+            write!(f, "]")
+        } else if let VarVal::MAP(_, Some(entries)) = self {
+            let mut entries: Vec<_> = entries.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            write!(f, "{{")?;
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                let separator = if i == 0 { "" } else { ", " };
+                write!(f, "{}{}: {}", separator, key, value)?;
+            }
+            write!(f, "}}")
         } else {
             write!(
                 f,
@@ -54,6 +355,7 @@ impl fmt::Display for VarVal {
                     VarVal::I32(Some(v)) => v.to_string(),
                     VarVal::BOOL(Some(v)) => v.to_string(),
                     VarVal::STRING(Some(v)) => v.clone(),
+                    VarVal::CHAR(Some(v)) => v.to_string(),
                     _ => "null".to_string(),
                 }
             )
@@ -61,52 +363,258 @@ impl fmt::Display for VarVal {
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Block {
-    pub statements: Vec<Stmt>,
+    /// Byte offset of the opening `{`
+    pub position: usize,
+    pub end: usize,
+    pub statements: Vec<Statement>,
     pub expr: Box<Expr>,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+/// Statement of a block with its span, which includes the closing `;`
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct Statement {
+    pub position: usize,
+    pub end: usize,
+    pub stmt: Stmt,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Stmt {
     Expr(Box<Expr>),
     Asgn(String, Box<Expr>),
+    /// `const x = expr;` binding a variable that can't be assigned again, constants exist
+    /// only in blocks as values shared by all functions come from the globals of the host
+    Const(String, Box<Expr>),
+    /// `let x: T;` declaring a variable holding null of type `T`
+    Decl(Variable),
+    /// `let x = expr;` binding a new variable in the block, unlike `Asgn` which changes
+    /// a variable of an enclosing block, the hidden variable comes back when the block ends
+    Let(String, Box<Expr>),
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct If {
+    /// Span from `if` to the end of the last block of the `else if` chain
+    pub position: usize,
+    pub end: usize,
     pub condition: Box<Expr>,
     pub if_block: Block,
     pub else_part: Else,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Else {
     Else(Block),
     ElseIf(Box<If>),
     None,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
 pub struct Expr {
     pub position: usize,
+    pub end: usize,
     pub expression_type: ExprType,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+/// Drop the expressions of the block with a work list, dropping them recursively would
+/// overflow the stack on long chains like `1 + 1 + ... + 1` in generated code
+fn drop_block(block: &mut Block) {
+    let mut work = Vec::new();
+    push_block(block, &mut work);
+    while let Some(expression_type) = work.pop() {
+        match expression_type {
+            ExprType::Op(lhs, _, rhs) => {
+                work.push(lhs.expression_type);
+                work.push(rhs.expression_type);
+            }
+            ExprType::Function(_, values) | ExprType::Array(values) => {
+                work.extend(values.into_iter().map(|value| value.expression_type))
+            }
+            ExprType::If(if_expr) => {
+                let mut next = Some(if_expr);
+                while let Some(mut if_expr) = next.take() {
+                    work.push(if_expr.condition.expression_type);
+                    push_block(&mut if_expr.if_block, &mut work);
+                    match if_expr.else_part {
+                        Else::Else(mut block) => push_block(&mut block, &mut work),
+                        Else::ElseIf(next_if) => next = Some(*next_if),
+                        Else::None => (),
+                    }
+                }
+            }
+            ExprType::ForIn(_, iter, mut block) => {
+                work.push(iter.expression_type);
+                push_block(&mut block, &mut work);
+            }
+            ExprType::Struct(_, fields) => {
+                work.extend(fields.into_iter().map(|(_, value)| value.expression_type))
+            }
+            ExprType::Field(value, _) => work.push(value.expression_type),
+            ExprType::Index(value, index) => {
+                work.push(value.expression_type);
+                work.push(index.expression_type);
+            }
+            ExprType::Map(entries) => {
+                for (key, value) in entries {
+                    work.push(key.expression_type);
+                    work.push(value.expression_type);
+                }
+            }
+            ExprType::Var(_) | ExprType::Value(_) => (),
+        }
+    }
+}
+
+/// Move the expressions of the block to `work`, leaving the block empty
+fn push_block(block: &mut Block, work: &mut Vec<ExprType>) {
+    for statement in block.statements.drain(..) {
+        match statement.stmt {
+            Stmt::Expr(expr) | Stmt::Asgn(_, expr) | Stmt::Const(_, expr) | Stmt::Let(_, expr) => {
+                work.push(expr.expression_type)
+            }
+            Stmt::Decl(_) => (),
+        }
+    }
+    let unit = ExprType::Value(VarVal::UNIT);
+    work.push(core::mem::replace(&mut block.expr.expression_type, unit));
+}
+
+/// First expression of the block in the source nested deeper than `max_depth`
+///
+/// The depth counts the nested calls of the evaluator, so each operand, argument, element,
+/// index and block is a level, except the left operands of a chain like `a + b + c` which
+/// are evaluated in a loop. The walk uses a work list as the tree may be arbitrarily deep
+#[cfg(feature = "std")]
+pub(crate) fn too_deep(block: &Block, max_depth: usize) -> Option<&Expr> {
+    let mut work = Vec::new();
+    push_exprs(block, 1, &mut work);
+    let mut found: Option<&Expr> = None;
+    while let Some((expr, depth)) = work.pop() {
+        if depth > max_depth {
+            if found.is_none_or(|f| expr.position < f.position) {
+                found = Some(expr);
+            }
+            continue;
+        }
+        let next = depth + 1;
+        match &expr.expression_type {
+            ExprType::Op(lhs, _, rhs) => {
+                let lhs_depth = match lhs.expression_type {
+                    ExprType::Op(..) => depth,
+                    _ => next,
+                };
+                work.push((lhs, lhs_depth));
+                work.push((rhs, next));
+            }
+            ExprType::Function(_, values) | ExprType::Array(values) => {
+                work.extend(values.iter().map(|value| (&**value, next)))
+            }
+            ExprType::If(if_expr) => {
+                // each `else if` is evaluated inside the previous one
+                let (mut if_expr, mut next) = (if_expr, next);
+                loop {
+                    work.push((&if_expr.condition, next));
+                    push_exprs(&if_expr.if_block, next + 1, &mut work);
+                    match &if_expr.else_part {
+                        Else::Else(block) => break push_exprs(block, next + 1, &mut work),
+                        Else::ElseIf(next_if) => if_expr = next_if,
+                        Else::None => break,
+                    }
+                    next += 1;
+                }
+            }
+            ExprType::ForIn(_, iter, block) => {
+                work.push((iter, next));
+                push_exprs(block, next + 1, &mut work);
+            }
+            ExprType::Struct(_, fields) => {
+                work.extend(fields.iter().map(|(_, value)| (&**value, next)))
+            }
+            ExprType::Field(value, _) => work.push((value, next)),
+            ExprType::Index(value, index) => {
+                work.push((value, next));
+                work.push((index, next));
+            }
+            ExprType::Map(entries) => {
+                for (key, value) in entries {
+                    work.push((key, next));
+                    work.push((value, next));
+                }
+            }
+            ExprType::Var(_) | ExprType::Value(_) => (),
+        }
+    }
+    found
+}
+
+/// Add the expressions of the block to `work` at `depth`
+#[cfg(feature = "std")]
+fn push_exprs<'a>(block: &'a Block, depth: usize, work: &mut Vec<(&'a Expr, usize)>) {
+    for statement in &block.statements {
+        match &statement.stmt {
+            Stmt::Expr(expr) | Stmt::Asgn(_, expr) | Stmt::Const(_, expr) | Stmt::Let(_, expr) => {
+                work.push((expr, depth))
+            }
+            Stmt::Decl(_) => (),
+        }
+    }
+    work.push((&block.expr, depth));
+}
+
+/// Leftmost operand of a chain like `a + b + c` and the operations applied to it in order
+/// with their right operands, passes walk chains in a loop as in `Interpreter::eval`
+pub(crate) fn operator_chain(expr: &Expr) -> (&Expr, Vec<(&Expr, &Opcode, &Expr)>) {
+    let mut chain = Vec::new();
+    let mut first = expr;
+    while let ExprType::Op(lhs, opc, rhs) = &first.expression_type {
+        chain.push((first, opc, &**rhs));
+        first = lhs;
+    }
+    chain.reverse();
+    (first, chain)
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ExprType {
     Var(String),
     Value(VarVal),
     Op(Box<Expr>, Opcode, Box<Expr>),
     Function(String, Vec<Box<Expr>>),
     If(If),
+    /// Loop `for x in arr { ... }` running the block with each element bound to the name
+    ForIn(String, Box<Expr>, Block),
+    /// Struct literal `Point { x: 1, y: 2 }`
+    Struct(String, Vec<(String, Box<Expr>)>),
+    /// Field access `p.x`
+    Field(Box<Expr>, String),
+    /// Element of an array or character of a string at an index `a[i]`
+    Index(Box<Expr>, Box<Expr>),
+    /// Array literal `[1, 2, 3]`
+    Array(Vec<Box<Expr>>),
+    /// Map literal `{ "a": 1, "b": 2 }` with expressions giving the keys and the values
+    Map(Vec<(Box<Expr>, Box<Expr>)>),
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+#[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Opcode {
     Mul,
     Div,
     Mod,
+    Pow,
     Add,
     Sub,
     Eq,
@@ -117,4 +625,194 @@ pub enum Opcode {
     Ge,
     And,
     Or,
+    /// Integers from the left operand up to, but not including, the right one `a..b`
+    Range,
+    /// Integers from the left operand up to and including the right one `a..=b`
+    RangeInclusive,
+}
+
+/// Read-only traversal of the syntax tree. Each method walks into the children of its
+/// node by default, an implementation overrides the methods of the nodes it's interested
+/// in and calls the matching `walk_` function to continue into the children
+///
+/// Nodes are visited in pre-order and in source order, the statements of a block before
+/// its tail expression
+pub trait Visitor {
+    fn visit_function(&mut self, function: &Function) {
+        walk_function(self, function)
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block)
+    }
+
+    fn visit_stmt(&mut self, statement: &Statement) {
+        walk_stmt(self, statement)
+    }
+
+    fn visit_if(&mut self, if_expr: &If) {
+        walk_if(self, if_expr)
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr)
+    }
+}
+
+/// Visit the functions in the order of `Program::functions`
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    for function in program.functions.values() {
+        visitor.visit_function(function);
+    }
+}
+
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, function: &Function) {
+    visitor.visit_block(&function.block);
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &Block) {
+    for statement in &block.statements {
+        visitor.visit_stmt(statement);
+    }
+    visitor.visit_expr(&block.expr);
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, statement: &Statement) {
+    match &statement.stmt {
+        Stmt::Expr(expr) | Stmt::Asgn(_, expr) | Stmt::Const(_, expr) | Stmt::Let(_, expr) => {
+            visitor.visit_expr(expr)
+        }
+        Stmt::Decl(..) => (),
+    }
+}
+
+/// Visit the condition, the block and then the `else` part
+pub fn walk_if<V: Visitor + ?Sized>(visitor: &mut V, if_expr: &If) {
+    visitor.visit_expr(&if_expr.condition);
+    visitor.visit_block(&if_expr.if_block);
+    match &if_expr.else_part {
+        Else::Else(block) => visitor.visit_block(block),
+        Else::ElseIf(next_if) => visitor.visit_if(next_if),
+        Else::None => (),
+    }
+}
+
+/// Visit the operands of the expression from left to right
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match &expr.expression_type {
+        ExprType::Op(lhs, _, rhs) => {
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        }
+        ExprType::Function(_, args) => {
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        ExprType::If(if_expr) => visitor.visit_if(if_expr),
+        ExprType::ForIn(_, iter, block) => {
+            visitor.visit_expr(iter);
+            visitor.visit_block(block);
+        }
+        ExprType::Struct(_, fields) => {
+            for (_, value) in fields {
+                visitor.visit_expr(value);
+            }
+        }
+        ExprType::Field(value, _) => visitor.visit_expr(value),
+        ExprType::Index(value, index) => {
+            visitor.visit_expr(value);
+            visitor.visit_expr(index);
+        }
+        ExprType::Array(elements) => {
+            for value in elements {
+                visitor.visit_expr(value);
+            }
+        }
+        ExprType::Map(entries) => {
+            for (key, value) in entries {
+                visitor.visit_expr(key);
+                visitor.visit_expr(value);
+            }
+        }
+        ExprType::Var(_) | ExprType::Value(_) => (),
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    /// Kinds of the visited nodes, expressions by their variable name or value
+    #[derive(Default)]
+    struct Trace(Vec<String>);
+
+    impl Visitor for Trace {
+        fn visit_function(&mut self, function: &Function) {
+            self.0.push(format!("fn {}", function.name));
+            walk_function(self, function);
+        }
+
+        fn visit_block(&mut self, block: &Block) {
+            self.0.push("block".to_string());
+            walk_block(self, block);
+        }
+
+        fn visit_stmt(&mut self, statement: &Statement) {
+            self.0.push("stmt".to_string());
+            walk_stmt(self, statement);
+        }
+
+        fn visit_if(&mut self, if_expr: &If) {
+            self.0.push("if".to_string());
+            walk_if(self, if_expr);
+        }
+
+        fn visit_expr(&mut self, expr: &Expr) {
+            match &expr.expression_type {
+                ExprType::Var(id) => self.0.push(id.clone()),
+                ExprType::Value(value) => self.0.push(value.to_string()),
+                ExprType::Op(_, opc, _) => self.0.push(format!("{:?}", opc)),
+                _ => (),
+            }
+            walk_expr(self, expr);
+        }
+    }
+
+    #[test]
+    fn visit_order() {
+        let program = parse(
+            "fn f(a: i32) { a }
+            fn main() { let x: i32; x = 1 + f(2); if x { 3 } else if y { 4 } else { 5 }; [x][0] }",
+        )
+        .unwrap();
+        let mut trace = Trace::default();
+        walk_program(&mut trace, &program);
+        let expected = [
+            "fn f", "block", "a", //
+            "fn main", "block", "stmt", "stmt", "Add", "1", "2", //
+            "stmt", "if", "x", "block", "3", "if", "y", "block", "4", "block", "5", //
+            "x", "0",
+        ];
+        assert_eq!(trace.0, expected);
+    }
+
+    /// Overriding one method leaves the traversal of the rest to the defaults
+    #[test]
+    fn count_calls() {
+        struct Calls(usize);
+        impl Visitor for Calls {
+            fn visit_expr(&mut self, expr: &Expr) {
+                if let ExprType::Function(..) = expr.expression_type {
+                    self.0 += 1;
+                }
+                walk_expr(self, expr);
+            }
+        }
+        let program = parse("fn main() { for x in range(0, len([1])) { print(x) } }").unwrap();
+        let mut calls = Calls(0);
+        walk_program(&mut calls, &program);
+        assert_eq!(calls.0, 3);
+    }
 }
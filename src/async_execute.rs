@@ -0,0 +1,124 @@
+use crate::ast::{ArgList, Program, VarVal, Variable};
+use crate::{execute, Buildins, Map, RuntimeError, RuntimeErrorType};
+use std::future::Future;
+use std::panic;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::runtime::Handle;
+use tokio::task;
+
+/// Builtin function producing its value asynchronously
+pub type AsyncBuildin =
+    Box<dyn Fn(ArgList) -> Pin<Box<dyn Future<Output = VarVal> + Send>> + Send + Sync>;
+
+/// Execute function `main` of `program` on the blocking thread pool of tokio
+pub async fn execute_async(
+    program: Arc<Program>,
+    globals: Arc<Mutex<Map<String, Variable>>>,
+    buildins: Arc<Mutex<Buildins<'static>>>,
+) -> Result<VarVal, RuntimeError> {
+    let res = task::spawn_blocking(move || {
+        let mut globals = globals.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut buildins = buildins.lock().unwrap_or_else(PoisonError::into_inner);
+        execute(&program, &mut globals, &mut buildins)
+    })
+    .await;
+    match res {
+        Ok(res) => res,
+        Err(e) => panic::resume_unwind(e.into_panic()),
+    }
+}
+
+/// Call an asynchronous builtin and wait for its value
+pub async fn call_builtin_async(buildin: &AsyncBuildin, args: ArgList) -> VarVal {
+    buildin(args).await
+}
+
+/// Wrap an asynchronous builtin to be callable by programs run with `execute_async`,
+/// the call blocks the interpreter thread until the future running on `handle` finishes
+pub fn blocking_buildin(
+    buildin: AsyncBuildin,
+    handle: Handle,
+) -> Box<dyn FnMut(ArgList) -> Result<VarVal, RuntimeErrorType> + Send> {
+    Box::new(move |args| Ok(handle.block_on(call_builtin_async(&buildin, args))))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+    use tokio::sync::mpsc::{self, Receiver, Sender};
+
+    /// Builtin sending its integer argument into `tx`
+    fn send(tx: Sender<i32>) -> AsyncBuildin {
+        Box::new(move |args: ArgList| {
+            let tx = tx.clone();
+            Box::pin(async move {
+                if let [VarVal::I32(Some(v))] = args.args.as_slice() {
+                    tx.send(*v).await.unwrap();
+                }
+                VarVal::UNIT
+            })
+        })
+    }
+
+    /// Builtin waiting for the next integer from `rx`
+    fn recv(rx: Receiver<i32>) -> AsyncBuildin {
+        let rx = Arc::new(tokio::sync::Mutex::new(rx));
+        Box::new(move |_| {
+            let rx = Arc::clone(&rx);
+            Box::pin(async move { VarVal::I32(rx.lock().await.recv().await) })
+        })
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn runs_alongside_other_tasks() {
+        let program = parse("fn main() { send(20); a = recv(); send(a + 1); recv() }").unwrap();
+        let (to_task, mut from_program) = mpsc::channel(1);
+        let (to_program, from_task) = mpsc::channel(1);
+        let mut buildins: Buildins<'static> = Map::new();
+        let handle = Handle::current();
+        buildins.insert(
+            "send".to_owned(),
+            blocking_buildin(send(to_task), handle.clone()),
+        );
+        buildins.insert("recv".to_owned(), blocking_buildin(recv(from_task), handle));
+
+        // the program only finishes when this task answers each of its messages
+        let doubler = tokio::spawn(async move {
+            let mut answered = 0;
+            while let Some(v) = from_program.recv().await {
+                to_program.send(v * 2).await.unwrap();
+                answered += 1;
+            }
+            answered
+        });
+        let res = execute_async(
+            Arc::new(program),
+            Arc::new(Mutex::new(Map::new())),
+            Arc::new(Mutex::new(buildins)),
+        )
+        .await;
+        assert_eq!(res.unwrap(), VarVal::I32(Some(82)));
+        assert_eq!(doubler.await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn async_builtin_value() {
+        let double: AsyncBuildin = Box::new(|args: ArgList| {
+            Box::pin(async move {
+                match args.args.as_slice() {
+                    [VarVal::I32(Some(v))] => VarVal::I32(Some(v * 2)),
+                    _ => VarVal::I32(None),
+                }
+            })
+        });
+        let args = ArgList {
+            args: vec![VarVal::I32(Some(21))],
+        };
+        assert_eq!(
+            call_builtin_async(&double, args).await,
+            VarVal::I32(Some(42))
+        );
+    }
+}
@@ -1,33 +1,14 @@
-use mylib::ast::{ArgList, VarVal};
-use mylib::{execute, parse, Buildins};
+use mylib::{
+    check, check_types, default_buildins, parse_all_named, render_diagnostic, Buildins, Diagnostic,
+    Interpreter, Program, Severity, VarVal,
+};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 
-fn buildins() -> Buildins<'static> {
-    let mut f: Buildins = HashMap::new();
-    f.insert(
-        "print".to_owned(),
-        Box::from(|args: ArgList| {
-            for arg in args.args {
-                match arg {
-                    VarVal::I32(Some(v)) => print!("{}", v),
-                    VarVal::BOOL(Some(v)) => print!("{}", v),
-                    VarVal::STRING(Some(v)) => print!("{}", v),
-                    VarVal::UNIT => print!("()"),
-                    _ => (),
-                }
-            }
-            println!();
-            VarVal::UNIT
-        }),
-    );
-    f
-}
-
 fn usage() {
-    eprintln!("program <file>");
+    eprintln!("program [--check | --ast] <file> [arguments...]");
 }
 
 fn load_program(file_path: &Path) -> Result<String, std::io::Error> {
@@ -37,31 +18,104 @@ fn load_program(file_path: &Path) -> Result<String, std::io::Error> {
     Ok(input)
 }
 
+/// Print the diagnostic with the name of the file it is about
+fn report(input: &str, file: &str, diagnostic: Diagnostic) {
+    let diagnostic = Diagnostic {
+        source_name: Some(file.to_string()),
+        ..diagnostic
+    };
+    eprint!("{}", render_diagnostic(input, &diagnostic));
+}
+
+/// Print problems found by the static checks, return true if any of them is an error
+fn report_diagnostics(input: &str, file: &str, program: &Program, buildins: &Buildins) -> bool {
+    let names: Vec<&str> = buildins.keys().map(String::as_str).collect();
+    let mut diagnostics = check(program, &names);
+    if let Err(e) = check_types(program) {
+        diagnostics.push(Diagnostic::from(&e));
+    }
+    let failed = diagnostics.iter().any(|d| d.severity == Severity::Error);
+    for diagnostic in diagnostics {
+        report(input, file, diagnostic);
+    }
+    failed
+}
+
+/// What to do with the program given on the command line
+#[derive(PartialEq)]
+enum Mode {
+    Run,
+    /// Only parse and check the program
+    Check,
+    /// Print the parsed program as JSON
+    Ast,
+}
+
 fn main() {
-    let mut args = std::env::args();
-    let file = args.nth(1).unwrap_or_else(|| {
+    let mut args = std::env::args().skip(1).peekable();
+    // in the --check and --ast modes failures give exit code 1
+    let mode = match args.next_if(|arg| arg == "--check" || arg == "--ast") {
+        Some(flag) if flag == "--check" => Mode::Check,
+        Some(_) => Mode::Ast,
+        None => Mode::Run,
+    };
+    let file = args.next().unwrap_or_else(|| {
         usage();
         std::process::exit(1)
     });
+    // arguments after the file are passed to main as strings
+    let argv: Vec<VarVal> = args.map(|arg| VarVal::STRING(Some(arg))).collect();
     let file_path = Path::new(&file);
     //let res = load_program(&file_path)
     //    .map(|program| parse(&program).map(|ast| execute(&ast, &mut HashMap::new())));
     //if let Err(e) = res {
     //    eprintln!("{:#?}", e);
     //}
-    match load_program(file_path) {
+    let ok = match load_program(file_path) {
         Ok(input) => {
-            match parse(&input) {
+            match parse_all_named(&input, &file) {
+                Ok(program) if mode == Mode::Ast => {
+                    println!("{}", serde_json::to_string_pretty(&program).unwrap());
+                    true
+                }
+                Err(errors) if mode == Mode::Ast => {
+                    println!("{}", serde_json::to_string_pretty(&errors).unwrap());
+                    false
+                }
                 Ok(program) => {
-                    //println!("{:#?}", program);
-                    match execute(&program, &mut HashMap::new(), &mut buildins()) {
-                        Ok(_) => (),
-                        Err(e) => eprintln!("Runtime error: {:#?}", e),
+                    let mut buildins = default_buildins();
+                    // warnings alone don't stop the program
+                    if report_diagnostics(&input, &file, &program, &buildins) {
+                        false
+                    } else if mode == Mode::Check {
+                        true
+                    } else {
+                        let mut globals = HashMap::new();
+                        let mut interpreter =
+                            Interpreter::new(&program, &mut globals, &mut buildins).with_args(argv);
+                        match interpreter.run() {
+                            Ok(_) => true,
+                            Err(e) => {
+                                report(&input, &file, Diagnostic::from(&e));
+                                false
+                            }
+                        }
+                    }
+                }
+                Err(errors) => {
+                    for e in &errors {
+                        eprint!("{}", render_diagnostic(&input, e));
                     }
+                    false
                 }
-                Err(e) => eprintln!("Runtime error: {:#?}", e),
             }
         }
-        Err(e) => eprintln!("OS error: {:#?}", e),
+        Err(e) => {
+            eprintln!("OS error: {:#?}", e);
+            false
+        }
+    };
+    if mode != Mode::Run && !ok {
+        std::process::exit(1);
     }
 }
@@ -1,23 +1,796 @@
-use crate::ast::{ArgList, VarVal};
-use std::collections::HashMap;
+use crate::ast::{ArgList, DataType, VarVal};
+use crate::{Buildins, RuntimeErrorType};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, BufReader, Write};
+use std::sync::{Arc, Mutex};
 
-pub fn buildins() -> HashMap<String, Box<dyn Fn(ArgList) -> VarVal>> {
-    let mut f: HashMap<String, Box<dyn Fn(ArgList) -> VarVal>> = HashMap::new();
+/// Build the standard builtin functions reading stdin and writing to stdout,
+/// callers may insert their own functions, replacing the standard ones of the same name
+pub fn default_buildins() -> Buildins<'static> {
+    buildins_with_output(io::stdout())
+}
+
+/// Build the builtin functions reading stdin and with `print` writing into `output`
+pub fn buildins_with_output<'a>(output: impl Write + Send + 'a) -> Buildins<'a> {
+    buildins_with_io(BufReader::new(io::stdin()), output)
+}
+
+/// Build the builtin functions reading lines from `input` and writing into `output`
+pub fn buildins_with_io<'a>(
+    input: impl BufRead + Send + 'a,
+    mut output: impl Write + Send + 'a,
+) -> Buildins<'a> {
+    let mut f: Buildins = HashMap::new();
     f.insert(
         "print".to_owned(),
-        Box::from(|args: ArgList| {
+        Box::from(move |args: ArgList| {
+            let mut line = String::new();
             for arg in args.args {
                 match arg {
-                    VarVal::I32(Some(v)) => print!("{}", v),
-                    VarVal::BOOL(Some(v)) => print!("{}", v),
-                    VarVal::STRING(Some(v)) => print!("{}", v),
-                    VarVal::UNIT => print!("()"),
+                    VarVal::I32(Some(v)) => line.push_str(&v.to_string()),
+                    VarVal::BOOL(Some(v)) => line.push_str(&v.to_string()),
+                    VarVal::STRING(Some(v)) => line.push_str(&v),
+                    VarVal::CHAR(Some(v)) => line.push(v),
+                    VarVal::UNIT => line.push_str("()"),
+                    v @ VarVal::STRUCT(_, Some(_))
+                    | v @ VarVal::ARRAY(_, Some(_))
+                    | v @ VarVal::MAP(_, Some(_)) => line.push_str(&v.to_string()),
                     _ => (),
                 }
             }
-            println!();
-            VarVal::UNIT
+            // there is no way to report the error to the program
+            let _ = writeln!(output, "{}", line);
+            Ok(VarVal::UNIT)
+        }),
+    );
+    f.insert("is_null".to_owned(), Box::from(is_null));
+    f.insert("to_i32".to_owned(), Box::from(to_i32));
+    f.insert("len".to_owned(), Box::from(len));
+    f.insert("substring".to_owned(), Box::from(substring));
+    f.insert("range".to_owned(), Box::from(range));
+    f.insert(
+        "to_upper".to_owned(),
+        Box::from(|args| string_map("to_upper", args, str::to_uppercase)),
+    );
+    f.insert(
+        "to_lower".to_owned(),
+        Box::from(|args| string_map("to_lower", args, str::to_lowercase)),
+    );
+    f.insert(
+        "trim".to_owned(),
+        Box::from(|args| string_map("trim", args, |s| s.trim().to_owned())),
+    );
+    f.insert("split".to_owned(), Box::from(split));
+    f.insert("join".to_owned(), Box::from(join));
+    f.insert("get".to_owned(), Box::from(get));
+    f.insert("insert".to_owned(), Box::from(insert));
+    f.insert(
+        "contains".to_owned(),
+        Box::from(|args| string_test("contains", args, |s, p| s.contains(p))),
+    );
+    f.insert(
+        "starts_with".to_owned(),
+        Box::from(|args| string_test("starts_with", args, |s, p| s.starts_with(p))),
+    );
+    f.insert(
+        "ends_with".to_owned(),
+        Box::from(|args| string_test("ends_with", args, |s, p| s.ends_with(p))),
+    );
+    f.insert("assert".to_owned(), Box::from(assert));
+    f.insert("assert_eq".to_owned(), Box::from(assert_eq));
+    f.insert("error".to_owned(), Box::from(user_error));
+
+    // both readers consume lines of the same input
+    let input = Arc::new(Mutex::new(input));
+    let line_input = Arc::clone(&input);
+    f.insert(
+        "read_line".to_owned(),
+        Box::from(move |args: ArgList| match args.args.as_slice() {
+            [] => Ok(VarVal::STRING(read_line(&mut *line_input.lock().unwrap()))),
+            _ => Err(RuntimeErrorType::WrongNumberOfArguments(
+                "read_line".to_owned(),
+            )),
+        }),
+    );
+    f.insert(
+        "read_int".to_owned(),
+        Box::from(move |args: ArgList| match args.args.as_slice() {
+            [] => Ok(VarVal::I32(
+                read_line(&mut *input.lock().unwrap()).and_then(|line| line.trim().parse().ok()),
+            )),
+            _ => Err(RuntimeErrorType::WrongNumberOfArguments(
+                "read_int".to_owned(),
+            )),
         }),
     );
     f
 }
+
+/// Builtins reading input or writing output
+const IO_BUILDINS: &[&str] = &["print", "read_line", "read_int"];
+
+/// Restriction of the builtins an untrusted program may call,
+/// by default every builtin except the ones doing input and output is allowed
+#[derive(Debug, Clone, Default)]
+pub struct SandboxPolicy {
+    /// If set, only builtins named here may be called
+    pub allow: Option<HashSet<String>>,
+    /// Builtins which may never be called
+    pub deny: HashSet<String>,
+    /// Allow builtins reading input or writing output
+    pub allow_io: bool,
+}
+
+impl SandboxPolicy {
+    /// Return true if a program may call builtin `name`
+    pub fn allows(&self, name: &str) -> bool {
+        (self.allow_io || !IO_BUILDINS.contains(&name))
+            && self.allow.as_ref().is_none_or(|allow| allow.contains(name))
+            && !self.deny.contains(name)
+    }
+
+    /// Replace builtins which aren't allowed by ones failing with `Forbidden`
+    pub fn apply<'a>(&self, buildins: Buildins<'a>) -> Buildins<'a> {
+        let mut restricted: Buildins = HashMap::new();
+        for (name, f) in buildins {
+            if self.allows(&name) {
+                restricted.insert(name, f);
+            } else {
+                let forbidden = name.clone();
+                restricted.insert(
+                    name,
+                    Box::from(move |_| Err(RuntimeErrorType::Forbidden(forbidden.clone()))),
+                );
+            }
+        }
+        restricted
+    }
+}
+
+/// Build the builtin functions reading stdin and writing to stdout, restricted by `policy`
+pub fn default_buildins_for(policy: &SandboxPolicy) -> Buildins<'static> {
+    policy.apply(default_buildins())
+}
+
+/// Read a line without its line ending, `None` at the end of input or on error
+fn read_line(input: &mut impl BufRead) -> Option<String> {
+    let mut line = String::new();
+    match input.read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => {
+            if line.ends_with('\n') {
+                line.pop();
+                if line.ends_with('\r') {
+                    line.pop();
+                }
+            }
+            Some(line)
+        }
+    }
+}
+
+/// Test if the single argument is a null value (or unit)
+fn is_null(args: ArgList) -> Result<VarVal, RuntimeErrorType> {
+    match args.args.as_slice() {
+        [value] => Ok(VarVal::BOOL(Some(match value {
+            VarVal::I32(v) => v.is_none(),
+            VarVal::BOOL(v) => v.is_none(),
+            VarVal::STRING(v) => v.is_none(),
+            VarVal::CHAR(v) => v.is_none(),
+            VarVal::STRUCT(_, v) => v.is_none(),
+            VarVal::ARRAY(_, v) => v.is_none(),
+            VarVal::MAP(_, v) => v.is_none(),
+            VarVal::UNIT => true,
+        }))),
+        _ => Err(RuntimeErrorType::WrongNumberOfArguments(
+            "is_null".to_owned(),
+        )),
+    }
+}
+
+/// Convert the single numeric argument to i32, the only numeric type so far
+fn to_i32(args: ArgList) -> Result<VarVal, RuntimeErrorType> {
+    match args.args.as_slice() {
+        [VarVal::I32(v)] => Ok(VarVal::I32(*v)),
+        [_] => Err(RuntimeErrorType::InvalidOperands),
+        _ => Err(RuntimeErrorType::WrongNumberOfArguments(
+            "to_i32".to_owned(),
+        )),
+    }
+}
+
+/// Return number of characters of the single string argument, elements of an array
+/// or entries of a map
+fn len(args: ArgList) -> Result<VarVal, RuntimeErrorType> {
+    match args.args.as_slice() {
+        [VarVal::STRING(v)] => Ok(VarVal::I32(v.as_ref().map(|s| s.chars().count() as i32))),
+        [VarVal::ARRAY(_, v)] => Ok(VarVal::I32(v.as_ref().map(|a| a.len() as i32))),
+        [VarVal::MAP(_, v)] => Ok(VarVal::I32(v.as_ref().map(|m| m.len() as i32))),
+        [_] => Err(RuntimeErrorType::InvalidOperands),
+        _ => Err(RuntimeErrorType::WrongNumberOfArguments("len".to_owned())),
+    }
+}
+
+/// Return characters `start..end` of the string argument, indices count characters
+fn substring(args: ArgList) -> Result<VarVal, RuntimeErrorType> {
+    match args.args.as_slice() {
+        [VarVal::STRING(Some(s)), VarVal::I32(Some(start)), VarVal::I32(Some(end))] => {
+            let len = s.chars().count() as i32;
+            if *start < 0 || start > end || *end > len {
+                return Err(RuntimeErrorType::IndexOutOfBounds);
+            }
+            let slice = s.chars().skip(*start as usize).take((end - start) as usize);
+            Ok(VarVal::STRING(Some(slice.collect())))
+        }
+        [VarVal::STRING(_), VarVal::I32(_), VarVal::I32(_)] => Ok(VarVal::STRING(None)),
+        [_, _, _] => Err(RuntimeErrorType::InvalidOperands),
+        _ => Err(RuntimeErrorType::WrongNumberOfArguments(
+            "substring".to_owned(),
+        )),
+    }
+}
+
+/// Integers from `start` up to `end` excluded, like the range `start..end`
+fn range(args: ArgList) -> Result<VarVal, RuntimeErrorType> {
+    match args.args.as_slice() {
+        [VarVal::I32(Some(start)), VarVal::I32(Some(end))] => crate::ops::range(*start, *end),
+        [VarVal::I32(_), VarVal::I32(_)] => Err(RuntimeErrorType::NullDereference),
+        [_, _] => Err(RuntimeErrorType::InvalidOperands),
+        _ => Err(RuntimeErrorType::WrongNumberOfArguments("range".to_owned())),
+    }
+}
+
+/// Apply `map` to the single string argument of builtin `name`
+fn string_map(
+    name: &str,
+    args: ArgList,
+    map: fn(&str) -> String,
+) -> Result<VarVal, RuntimeErrorType> {
+    match args.args.as_slice() {
+        [VarVal::STRING(s)] => Ok(VarVal::STRING(s.as_deref().map(map))),
+        [_] => Err(RuntimeErrorType::InvalidOperands),
+        _ => Err(RuntimeErrorType::WrongNumberOfArguments(name.to_owned())),
+    }
+}
+
+/// Split the string at each occurrence of the separator into an array of strings,
+/// an empty string gives one empty part and an empty separator splits into characters
+fn split(args: ArgList) -> Result<VarVal, RuntimeErrorType> {
+    match args.args.as_slice() {
+        [VarVal::STRING(Some(s)), VarVal::STRING(Some(separator))] => {
+            let parts: Vec<_> = if separator.is_empty() {
+                s.chars().map(|ch| ch.to_string()).collect()
+            } else {
+                s.split(separator.as_str()).map(str::to_owned).collect()
+            };
+            let parts = parts.into_iter().map(|part| VarVal::STRING(Some(part)));
+            Ok(VarVal::ARRAY(DataType::STRING, Some(parts.collect())))
+        }
+        [VarVal::STRING(_), VarVal::STRING(_)] => Ok(VarVal::ARRAY(DataType::STRING, None)),
+        [_, _] => Err(RuntimeErrorType::InvalidOperands),
+        _ => Err(RuntimeErrorType::WrongNumberOfArguments("split".to_owned())),
+    }
+}
+
+/// Concatenate an array of strings putting the separator between them, the reverse of `split`
+fn join(args: ArgList) -> Result<VarVal, RuntimeErrorType> {
+    match args.args.as_slice() {
+        [VarVal::ARRAY(_, Some(parts)), VarVal::STRING(Some(separator))] => {
+            let mut joined = String::new();
+            for (i, part) in parts.iter().enumerate() {
+                match part {
+                    VarVal::STRING(Some(part)) => {
+                        if i > 0 {
+                            joined.push_str(separator);
+                        }
+                        joined.push_str(part);
+                    }
+                    _ => return Err(RuntimeErrorType::InvalidOperands),
+                }
+            }
+            Ok(VarVal::STRING(Some(joined)))
+        }
+        [VarVal::ARRAY(_, _), VarVal::STRING(_)] => Ok(VarVal::STRING(None)),
+        [_, _] => Err(RuntimeErrorType::InvalidOperands),
+        _ => Err(RuntimeErrorType::WrongNumberOfArguments("join".to_owned())),
+    }
+}
+
+/// Return the value of the key in the map like `map[key]`, null when the key is missing
+fn get(args: ArgList) -> Result<VarVal, RuntimeErrorType> {
+    match args.args.as_slice() {
+        [VarVal::MAP(value, Some(entries)), VarVal::STRING(Some(key))] => Ok(entries
+            .get(key)
+            .cloned()
+            .unwrap_or_else(|| value.null_value())),
+        [VarVal::MAP(value, _), VarVal::STRING(_)] => Ok(value.null_value()),
+        [_, _] => Err(RuntimeErrorType::InvalidOperands),
+        _ => Err(RuntimeErrorType::WrongNumberOfArguments("get".to_owned())),
+    }
+}
+
+/// Return a copy of the map with the key set to the value, which must have the type
+/// of the other values, an empty map takes the type of the value
+fn insert(args: ArgList) -> Result<VarVal, RuntimeErrorType> {
+    match args.args.as_slice() {
+        [VarVal::MAP(value_type, Some(entries)), VarVal::STRING(Some(key)), value]
+            if entries.is_empty() || value.data_type() == *value_type =>
+        {
+            let mut entries = entries.clone();
+            entries.insert(key.clone(), value.clone());
+            Ok(VarVal::MAP(value.data_type(), Some(entries)))
+        }
+        [VarVal::MAP(_, Some(_)), VarVal::STRING(Some(_)), _] => {
+            Err(RuntimeErrorType::InvalidOperands)
+        }
+        [VarVal::MAP(value_type, _), VarVal::STRING(_), _] => {
+            Ok(VarVal::MAP(value_type.clone(), None))
+        }
+        [_, _, _] => Err(RuntimeErrorType::InvalidOperands),
+        _ => Err(RuntimeErrorType::WrongNumberOfArguments(
+            "insert".to_owned(),
+        )),
+    }
+}
+
+/// Apply `test` to the two string arguments of builtin `name`
+fn string_test(
+    name: &str,
+    args: ArgList,
+    test: fn(&str, &str) -> bool,
+) -> Result<VarVal, RuntimeErrorType> {
+    match args.args.as_slice() {
+        [VarVal::STRING(Some(s)), VarVal::STRING(Some(pattern))] => {
+            Ok(VarVal::BOOL(Some(test(s, pattern))))
+        }
+        [VarVal::STRING(_), VarVal::STRING(_)] => Ok(VarVal::BOOL(None)),
+        [_, _] => Err(RuntimeErrorType::InvalidOperands),
+        _ => Err(RuntimeErrorType::WrongNumberOfArguments(name.to_owned())),
+    }
+}
+
+/// Fail with `AssertionFailed` unless the single argument is true
+fn assert(args: ArgList) -> Result<VarVal, RuntimeErrorType> {
+    match args.args.as_slice() {
+        [VarVal::BOOL(Some(true))] => Ok(VarVal::UNIT),
+        [VarVal::BOOL(Some(false))] => Err(RuntimeErrorType::AssertionFailed(
+            "condition is false".to_owned(),
+        )),
+        [_] => Err(RuntimeErrorType::BooleanExpected),
+        _ => Err(RuntimeErrorType::WrongNumberOfArguments(
+            "assert".to_owned(),
+        )),
+    }
+}
+
+/// Fail with `AssertionFailed` showing both values unless the two arguments are equal
+fn assert_eq(args: ArgList) -> Result<VarVal, RuntimeErrorType> {
+    match args.args.as_slice() {
+        [left, right] if left == right => Ok(VarVal::UNIT),
+        [left, right] => Err(RuntimeErrorType::AssertionFailed(format!(
+            "{} != {}",
+            quoted(left),
+            quoted(right)
+        ))),
+        _ => Err(RuntimeErrorType::WrongNumberOfArguments(
+            "assert_eq".to_owned(),
+        )),
+    }
+}
+
+/// Fail with `UserError` carrying the single string argument as its message
+fn user_error(args: ArgList) -> Result<VarVal, RuntimeErrorType> {
+    match args.args.as_slice() {
+        [VarVal::STRING(Some(message))] => Err(RuntimeErrorType::UserError(message.clone())),
+        [_] => Err(RuntimeErrorType::InvalidOperands),
+        _ => Err(RuntimeErrorType::WrongNumberOfArguments("error".to_owned())),
+    }
+}
+
+/// Display a value the way it is written in source, so `"1"` differs from `1`
+fn quoted(value: &VarVal) -> String {
+    match value {
+        VarVal::STRING(Some(s)) => format!("{:?}", s),
+        VarVal::CHAR(Some(ch)) => format!("{:?}", ch),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{execute, parse, RuntimeError, MAX_RANGE_LEN};
+
+    fn null_check(value: VarVal) -> VarVal {
+        is_null(ArgList { args: vec![value] }).unwrap()
+    }
+
+    #[test]
+    fn is_null_values() {
+        assert_eq!(null_check(VarVal::I32(None)), VarVal::BOOL(Some(true)));
+        assert_eq!(null_check(VarVal::I32(Some(0))), VarVal::BOOL(Some(false)));
+        assert_eq!(null_check(VarVal::BOOL(None)), VarVal::BOOL(Some(true)));
+        assert_eq!(
+            null_check(VarVal::BOOL(Some(false))),
+            VarVal::BOOL(Some(false))
+        );
+        assert_eq!(null_check(VarVal::STRING(None)), VarVal::BOOL(Some(true)));
+        assert_eq!(
+            null_check(VarVal::STRING(Some(String::new()))),
+            VarVal::BOOL(Some(false))
+        );
+        assert_eq!(null_check(VarVal::UNIT), VarVal::BOOL(Some(true)));
+    }
+
+    #[test]
+    fn is_null_arity() {
+        let err = is_null(ArgList { args: vec![] }).unwrap_err();
+        assert!(matches!(err, RuntimeErrorType::WrongNumberOfArguments(name) if name == "is_null"));
+        let err = run("fn main() { is_null(1, 2) }").unwrap_err();
+        assert!(matches!(
+            err.error_type,
+            RuntimeErrorType::WrongNumberOfArguments(_)
+        ));
+    }
+
+    #[test]
+    fn is_null_program() {
+        let res = run("fn main() { is_null(1) || is_null(if false { 1 }) }");
+        assert_eq!(res.unwrap(), VarVal::BOOL(Some(true)));
+    }
+
+    #[test]
+    fn print_output() {
+        let program = parse("fn main() { print(\"a\", 1, true); print(print()) }").unwrap();
+        let mut output = Vec::new();
+        execute(
+            &program,
+            &mut HashMap::new(),
+            &mut buildins_with_output(&mut output),
+        )
+        .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "a1true\n\n()\n");
+    }
+
+    fn run(input: &str) -> Result<VarVal, RuntimeError> {
+        let program = parse(input).unwrap();
+        execute(&program, &mut HashMap::new(), &mut default_buildins())
+    }
+
+    fn run_with_input(input: &str, program: &str) -> (VarVal, String) {
+        let program = parse(program).unwrap();
+        let mut output = Vec::new();
+        let res = execute(
+            &program,
+            &mut HashMap::new(),
+            &mut buildins_with_io(input.as_bytes(), &mut output),
+        )
+        .unwrap();
+        (res, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn read_int_sum() {
+        let program = "
+            fn sum() {
+                n = read_int();
+                if is_null(n) { 0 } else { n + sum() }
+            }
+            fn main() {
+                print(\"sum: \", sum())
+            }";
+        let (_, output) = run_with_input("1\n2\n 39 \n", program);
+        assert_eq!(output, "sum: 42\n");
+    }
+
+    #[test]
+    fn read_line_values() {
+        let program = "fn main() { a = read_line(); b = read_line(); c = read_int(); print(a, b, c); read_line() }";
+        let (res, output) = run_with_input("first\r\nsecond\nthird", program);
+        assert_eq!(output, "firstsecond\n");
+        assert_eq!(res, VarVal::STRING(None));
+    }
+
+    #[test]
+    fn read_arity() {
+        for program in &[
+            "fn main() { read_line(1) }",
+            "fn main() { read_int(\"1\") }",
+        ] {
+            let program = parse(program).unwrap();
+            let err = execute(
+                &program,
+                &mut HashMap::new(),
+                &mut buildins_with_io("1\n".as_bytes(), Vec::new()),
+            )
+            .unwrap_err();
+            assert!(matches!(
+                err.error_type,
+                RuntimeErrorType::WrongNumberOfArguments(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn sandbox_without_print() {
+        let policy = SandboxPolicy {
+            deny: ["print".to_owned()].iter().cloned().collect(),
+            allow_io: true,
+            ..SandboxPolicy::default()
+        };
+        let mut buildins = default_buildins_for(&policy);
+
+        let program = parse("fn main() { is_null(1) || 6 * 7 == 42 }").unwrap();
+        let res = execute(&program, &mut HashMap::new(), &mut buildins);
+        assert_eq!(res.unwrap(), VarVal::BOOL(Some(true)));
+
+        let program = parse("fn main() { print(1) }").unwrap();
+        let err = execute(&program, &mut HashMap::new(), &mut buildins).unwrap_err();
+        match err.error_type {
+            RuntimeErrorType::Forbidden(name) => assert_eq!(name, "print"),
+            e => panic!("unexpected error {:?}", e),
+        }
+        assert_eq!(err.position, 12);
+    }
+
+    #[test]
+    fn sandbox_policy() {
+        let policy = SandboxPolicy::default();
+        assert!(policy.allows("is_null"));
+        assert!(!policy.allows("read_line"));
+        let policy = SandboxPolicy {
+            allow: Some(["read_int".to_owned()].iter().cloned().collect()),
+            allow_io: true,
+            ..SandboxPolicy::default()
+        };
+        assert!(policy.allows("read_int"));
+        assert!(!policy.allows("read_line"));
+        assert!(!policy.allows("is_null"));
+    }
+
+    #[test]
+    fn override_default() {
+        let mut buildins = default_buildins();
+        buildins.insert(
+            "is_null".to_owned(),
+            Box::from(|_| Ok(VarVal::STRING(Some("custom".to_owned())))),
+        );
+        buildins.insert(
+            "answer".to_owned(),
+            Box::from(|_| Ok(VarVal::I32(Some(42)))),
+        );
+        let program = parse("fn main() { is_null(answer()) }").unwrap();
+        let res = execute(&program, &mut HashMap::new(), &mut buildins);
+        assert_eq!(res.unwrap(), VarVal::STRING(Some("custom".to_owned())));
+    }
+
+    #[test]
+    fn to_i32_values() {
+        let res = run("fn main() { to_i32(5) + 1 }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(6)));
+        let err = run("fn main() { to_i32(\"5\") }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOperands));
+    }
+
+    #[test]
+    fn len_method() {
+        let res =
+            run("fn main() { a = \"abc\".len(); b = len(\"abc\"); if a == b { \"čaj\".len() } }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(3)));
+        let err = run("fn main() { 1.len() }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOperands));
+    }
+
+    #[test]
+    fn string_slicing() {
+        let res = run("fn main() { s = \"hello\"; substring(s, 1, 4) }");
+        assert_eq!(res.unwrap(), VarVal::STRING(Some("ell".to_string())));
+        let res = run("fn main() { \"žluťoučký kůň\".substring(2, 9) }");
+        assert_eq!(res.unwrap(), VarVal::STRING(Some("uťoučký".to_string())));
+        let res = run("fn main() { \"čaj\".substring(3, 3) }");
+        assert_eq!(res.unwrap(), VarVal::STRING(Some(String::new())));
+        for input in &[
+            "fn main() { \"čaj\".substring(1, 4) }",
+            "fn main() { \"čaj\".substring(2, 1) }",
+            "fn main() { \"čaj\".substring(-1, 1) }",
+        ] {
+            let err = run(input).unwrap_err();
+            assert!(matches!(err.error_type, RuntimeErrorType::IndexOutOfBounds));
+        }
+
+        let res = run("fn main() { s = \"hello\"; s[1] }");
+        assert_eq!(res.unwrap(), VarVal::CHAR(Some('e')));
+        let res = run("fn main() { s = \"kůň\"; if s[1] == 'ů' { s[s.len() - 1] } }");
+        assert_eq!(res.unwrap(), VarVal::CHAR(Some('ň')));
+        let input = "fn main() { \"kůň\"[3] }";
+        let err = run(input).unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::IndexOutOfBounds));
+        assert_eq!(err.position, input.find('3').unwrap());
+        let err = run("fn main() { 1[0] }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOperands));
+    }
+
+    #[test]
+    fn string_tests() {
+        let cases = [
+            ("contains(\"kůň\", \"ů\")", true),
+            ("contains(\"abc\", \"\")", true),
+            ("\"abc\".contains(\"ac\")", false),
+            ("starts_with(\"hello\", \"he\")", true),
+            ("\"hello\".starts_with(\"lo\")", false),
+            ("ends_with(\"hello\", \"lo\")", true),
+            ("\"hello\".ends_with(\"hello!\")", false),
+        ];
+        for (expr, expected) in &cases {
+            let res = run(&format!("fn main() {{ {} }}", expr));
+            assert_eq!(res.unwrap(), VarVal::BOOL(Some(*expected)), "{}", expr);
+        }
+        let err = run("fn main() { contains(\"1\", 1) }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOperands));
+        let err = run("fn main() { \"a\".ends_with() }").unwrap_err();
+        assert!(
+            matches!(err.error_type, RuntimeErrorType::WrongNumberOfArguments(name) if name == "ends_with")
+        );
+    }
+
+    #[test]
+    fn maps() {
+        let res = run("fn main() { m = { \"a\": 1, \"b\": 2 }; m[\"a\"] * 10 + get(m, \"b\") }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(12)));
+        let res = run("fn main() { m = { \"a\": 1 }; is_null(m[\"x\"]) && is_null(m.get(\"x\")) }");
+        assert_eq!(res.unwrap(), VarVal::BOOL(Some(true)));
+        let res = run("fn main() {
+                m = {};
+                n = m.insert(\"a\", \"x\").insert(\"b\", \"y\").insert(\"a\", \"z\");
+                print(m.len(), n);
+                n
+            }");
+        let entries: HashMap<_, _> = [("a", "z"), ("b", "y")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), VarVal::STRING(Some(v.to_string()))))
+            .collect();
+        assert_eq!(res.unwrap(), VarVal::MAP(DataType::STRING, Some(entries)));
+
+        let err = run("fn main() { insert({ \"a\": 1 }, \"b\", true) }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOperands));
+        let err = run("fn main() { { \"a\": 1, \"b\": 'c' } }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOperands));
+        let err = run("fn main() { { 1: 1 } }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOperands));
+        let res = run("fn main() { let m: {String: i32}; is_null(m.insert(\"a\", 1)) }");
+        assert_eq!(res.unwrap(), VarVal::BOOL(Some(true)));
+    }
+
+    #[test]
+    fn ranges() {
+        let res = run("fn main() { range(0, 3) }").unwrap();
+        assert_eq!(res.to_string(), "[0, 1, 2]");
+        let res = run("fn main() { n = 4; range(n, n) == [] && (3..1) == [] }");
+        assert_eq!(res.unwrap(), VarVal::BOOL(Some(true)));
+        let res = run("fn main() { n = 3; a = 1..n + 1; a }").unwrap();
+        assert_eq!(res.to_string(), "[1, 2, 3]");
+        let err = run("fn main() { range(0, \"3\") }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOperands));
+
+        let res = run("fn main() { len(range(0 - 1048576, 0)) }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(MAX_RANGE_LEN as i32)));
+        let err = run("fn main() { range(0 - 1048576, 1) }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::RangeTooLong));
+        let input = "fn main() { for i in 0 - 2147483647..2147483647 { i }; 0 }";
+        let err = run(input).unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::RangeTooLong));
+        assert_eq!(err.position, input.find("0 -").unwrap());
+    }
+
+    #[test]
+    fn split_join() {
+        let strings = |parts: &[&str]| {
+            let parts = parts.iter().map(|p| VarVal::STRING(Some(p.to_string())));
+            VarVal::ARRAY(DataType::STRING, Some(parts.collect()))
+        };
+        let res = run("fn main() { split(\"a,b,c\", \",\") }");
+        assert_eq!(res.unwrap(), strings(&["a", "b", "c"]));
+        let res = run("fn main() { parts = \"a,b,c\".split(\",\"); parts.len() }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(3)));
+        let res = run("fn main() { \"a,b,,c\".split(\",\").join(\";\") }");
+        assert_eq!(res.unwrap(), VarVal::STRING(Some("a;b;;c".to_string())));
+        let res = run("fn main() { \"\".split(\",\") }");
+        assert_eq!(res.unwrap(), strings(&[""]));
+        let res = run("fn main() { \"čaj\".split(\"\") }");
+        assert_eq!(res.unwrap(), strings(&["č", "a", "j"]));
+        let res = run("fn main() { join([], \",\") }");
+        assert_eq!(res.unwrap(), VarVal::STRING(Some(String::new())));
+
+        let err = run("fn main() { join([1, 2], \",\") }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOperands));
+        let err = run("fn main() { split(\"a\") }").unwrap_err();
+        assert!(matches!(
+            err.error_type,
+            RuntimeErrorType::WrongNumberOfArguments(_)
+        ));
+    }
+
+    #[test]
+    fn case_and_trim() {
+        let cases = [
+            ("to_upper(\"Hello 1\")", "HELLO 1"),
+            ("\"ß\".to_upper()", "SS"),
+            ("\"ŽLUŤOUČKÝ\".to_lower()", "žluťoučký"),
+            ("to_lower(\"ΣΑ\")", "σα"),
+            ("trim(\" \\t a b \\n\")", "a b"),
+            ("\"\u{3000}x\".trim()", "x"),
+        ];
+        for (expr, expected) in &cases {
+            let res = run(&format!("fn main() {{ {} }}", expr));
+            assert_eq!(
+                res.unwrap(),
+                VarVal::STRING(Some(expected.to_string())),
+                "{}",
+                expr
+            );
+        }
+        let res = run("fn main() { let s: String; s.trim() }");
+        assert_eq!(res.unwrap(), VarVal::STRING(None));
+        let err = run("fn main() { to_upper(1) }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOperands));
+    }
+
+    #[test]
+    fn assertions() {
+        let res =
+            run("fn main() { assert(1 < 2); assert_eq(split(\"a b\", \" \"), [\"a\", \"b\"]) }");
+        assert_eq!(res.unwrap(), VarVal::UNIT);
+
+        let err = run("fn main() {\n  a = 2;\n  assert(a == 3)\n}").unwrap_err();
+        assert_eq!(err.position, 23);
+        assert_eq!(
+            err.error_type.to_string(),
+            "Assertion failed: condition is false"
+        );
+        let err = run("fn main() { assert_eq(\"1\", 1) }").unwrap_err();
+        assert!(
+            matches!(&err.error_type, RuntimeErrorType::AssertionFailed(message) if message == "\"1\" != 1")
+        );
+        let err = run("fn main() { let a: i32; assert_eq(a, 'a') }").unwrap_err();
+        assert_eq!(err.error_type.to_string(), "Assertion failed: null != 'a'");
+        let err = run("fn main() { assert(1) }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::BooleanExpected));
+        let err = run("fn main() { assert_eq(1) }").unwrap_err();
+        assert!(matches!(
+            err.error_type,
+            RuntimeErrorType::WrongNumberOfArguments(_)
+        ));
+    }
+
+    #[test]
+    fn user_error() {
+        let input = "fn check(n: i32) { if n < 0 { error(\"boom\") } else { n } }
+            fn main() { check(1) + check(-1) }";
+        let err = run(input).unwrap_err();
+        assert!(
+            matches!(&err.error_type, RuntimeErrorType::UserError(message) if message == "boom")
+        );
+        assert_eq!(err.position, input.find("error").unwrap());
+        assert_eq!(
+            err.to_string(),
+            format!("error[E0018]: boom at byte {}", err.position)
+        );
+        let err = run("fn main() { error(1) }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOperands));
+    }
+
+    #[test]
+    fn arities() {
+        let buildins = default_buildins();
+        for name in buildins.keys() {
+            assert_eq!(
+                crate::buildin_arity(name).is_none(),
+                name == "print",
+                "{}",
+                name
+            );
+        }
+        assert_eq!(crate::buildin_arity("substring"), Some(3));
+        assert_eq!(crate::buildin_arity("print"), None);
+    }
+}
@@ -0,0 +1,539 @@
+use crate::ast::{
+    operator_chain, Block, Else, Expr, ExprType, Function, If, Program, Statement, Stmt,
+};
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use crate::{Diagnostic, Map, Set, Severity};
+use alloc::{format, vec};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// Problem found in a program without running it
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct CheckError {
+    pub position: usize,
+    pub description: String,
+}
+
+/// Number of arguments of the standard builtins, `print` takes any number of them
+const BUILDIN_ARITIES: &[(&str, usize)] = &[
+    ("is_null", 1),
+    ("to_i32", 1),
+    ("len", 1),
+    ("substring", 3),
+    ("range", 2),
+    ("to_upper", 1),
+    ("to_lower", 1),
+    ("trim", 1),
+    ("split", 2),
+    ("join", 2),
+    ("get", 2),
+    ("insert", 3),
+    ("contains", 2),
+    ("starts_with", 2),
+    ("ends_with", 2),
+    ("assert", 1),
+    ("assert_eq", 2),
+    ("error", 1),
+    ("read_line", 0),
+    ("read_int", 0),
+];
+
+/// Number of arguments builtin `name` takes if it is a standard builtin with a fixed arity
+pub fn buildin_arity(name: &str) -> Option<usize> {
+    BUILDIN_ARITIES
+        .iter()
+        .find(|(buildin, _)| *buildin == name)
+        .map(|(_, arity)| *arity)
+}
+
+/// Check that called functions and used structs exist and that functions of the program
+/// and standard builtins are called with the right number of arguments,
+/// `buildins` names the available builtins
+pub fn check_program(program: &Program, buildins: &[&str]) -> Result<(), CheckError> {
+    let mut checker = Checker::new(program, buildins, false);
+    checker.functions();
+    match checker.diagnostics.into_iter().next() {
+        Some(diagnostic) => Err(CheckError {
+            position: diagnostic.span.0,
+            description: diagnostic.message,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Find all problems `check_program` does, together with variables read before they are
+/// assigned, in the order of the functions by name. A variable assigned only inside an
+/// `if` or a `for` body is undefined after it, the checker doesn't know globals passed to
+/// `execute`. Values never read, unused parameters and functions never called are warnings too,
+/// unless their name starts with `_`
+pub fn check(program: &Program, known_builtins: &[&str]) -> Vec<Diagnostic> {
+    let mut checker = Checker::new(program, known_builtins, true);
+    checker.functions();
+    checker.diagnostics
+}
+
+struct Checker<'a> {
+    program: &'a Program,
+    buildins: &'a [&'a str],
+    /// Whether to track variables, names bound in each enclosing block, innermost last
+    variables: Option<Vec<Bound>>,
+    diagnostics: Vec<Diagnostic>,
+    /// Function being checked
+    current: &'a str,
+    usage: Usage,
+    /// Functions called from other functions
+    called: Set<String>,
+}
+
+/// Reads and writes of variables in the function being checked
+#[derive(Default)]
+struct Usage {
+    read: Set<String>,
+    /// Assignments whose value wasn't read yet, by variable
+    unread: Map<String, Vec<Assignment>>,
+    /// Spans of assignments overwritten before their value was read
+    overwritten: Vec<((usize, usize), String)>,
+    /// Blocks being checked from the outermost, each gets a new number
+    blocks: Vec<usize>,
+    block_count: usize,
+}
+
+struct Assignment {
+    span: (usize, usize),
+    /// Blocks the assignment is in, as `Usage::blocks` at the time
+    blocks: Vec<usize>,
+}
+
+/// Names bound in a block
+#[derive(Default)]
+struct Bound {
+    /// Names bound by every path through the block so far
+    always: Set<String>,
+    /// Names bound only inside a branch of an `if` in the block
+    maybe: Set<String>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(program: &'a Program, buildins: &'a [&'a str], variables: bool) -> Self {
+        Checker {
+            program,
+            buildins,
+            variables: if variables { Some(Vec::new()) } else { None },
+            diagnostics: Vec::new(),
+            current: "",
+            usage: Usage::default(),
+            called: Set::new(),
+        }
+    }
+
+    fn functions(&mut self) {
+        let program = self.program;
+        let mut functions: Vec<_> = program.functions.values().collect();
+        functions.sort_by_key(|f| &f.name);
+        for function in &functions {
+            self.current = &function.name;
+            let arguments = function.arguments.iter().map(|arg| arg.ident.clone());
+            if let Some(scopes) = &mut self.variables {
+                scopes.push(Bound {
+                    always: arguments.collect(),
+                    maybe: Set::new(),
+                });
+            }
+            self.block(&function.block);
+            if let Some(scopes) = &mut self.variables {
+                scopes.clear();
+                self.unused_variables(function);
+            }
+        }
+        if self.variables.is_some() {
+            for function in functions {
+                let name = &function.name;
+                if name != "main" && !name.starts_with('_') && !self.called.contains(name) {
+                    let description = format!("Function {} is never called", name);
+                    self.warn((function.position, function.end), description);
+                }
+            }
+        }
+    }
+
+    /// Report parameters and assigned values of `function` which are never read
+    fn unused_variables(&mut self, function: &Function) {
+        let usage = core::mem::take(&mut self.usage);
+        for (i, arg) in function.arguments.iter().enumerate() {
+            if !arg.ident.starts_with('_') && !usage.read.contains(&arg.ident) {
+                // programs loaded from JSON may come without the spans of parameters
+                let span = function.argument_spans.get(i).copied();
+                let description = format!("Parameter {} is never used", arg.ident);
+                self.warn(
+                    span.unwrap_or((function.position, function.end)),
+                    description,
+                );
+            }
+        }
+        let mut unread: Vec<_> = usage
+            .unread
+            .into_iter()
+            .flat_map(|(name, spans)| spans.into_iter().map(move |a| (a.span, name.clone())))
+            .chain(usage.overwritten)
+            .filter(|(_, name)| !name.starts_with('_'))
+            .collect();
+        unread.sort();
+        for (span, name) in unread {
+            let description = format!("Value assigned to {} is never read", name);
+            self.warn(span, description);
+        }
+    }
+
+    fn warn(&mut self, span: (usize, usize), description: String) {
+        self.diagnostics
+            .push(Diagnostic::new(Severity::Warning, span, description));
+    }
+
+    fn report(&mut self, severity: Severity, expr: &Expr, description: String) {
+        let span = (expr.position, expr.end);
+        self.diagnostics
+            .push(Diagnostic::new(severity, span, description));
+    }
+
+    /// Bind `name` in the innermost block
+    fn declare(&mut self, name: &str) {
+        if let Some(bound) = self.variables.as_mut().and_then(|scopes| scopes.last_mut()) {
+            bound.always.insert(name.to_string());
+        }
+    }
+
+    /// Bind `name` to a value assigned at `span`
+    fn bind(&mut self, name: &str, span: (usize, usize)) {
+        self.declare(name);
+        if self.variables.is_some() {
+            let usage = &mut self.usage;
+            let unread = usage.unread.entry(name.to_string()).or_default();
+            // a value assigned in this block or a block nested in it is always overwritten,
+            // one assigned in an enclosing block may still be read when this block is skipped
+            let blocks = &usage.blocks;
+            let (overwritten, kept) = unread
+                .drain(..)
+                .partition(|a: &Assignment| a.blocks.starts_with(blocks));
+            *unread = kept;
+            let overwritten = overwritten.into_iter().map(|a| (a.span, name.to_string()));
+            usage.overwritten.extend(overwritten);
+            unread.push(Assignment {
+                span,
+                blocks: blocks.clone(),
+            });
+        }
+    }
+
+    fn block(&mut self, block: &Block) -> Bound {
+        if let Some(scopes) = &mut self.variables {
+            scopes.push(Bound::default());
+        }
+        self.usage.block_count += 1;
+        self.usage.blocks.push(self.usage.block_count);
+        for Statement { stmt, .. } in &block.statements {
+            match stmt {
+                Stmt::Expr(expr) => self.expr(expr),
+                Stmt::Asgn(name, expr) | Stmt::Const(name, expr) | Stmt::Let(name, expr) => {
+                    self.expr(expr);
+                    self.bind(name, (expr.position, expr.end));
+                }
+                // a declared variable is null, there is no value which could be unread
+                Stmt::Decl(var) => self.declare(&var.ident),
+            }
+        }
+        self.expr(&block.expr);
+        self.usage.blocks.pop();
+        match &mut self.variables {
+            Some(scopes) => scopes.pop().unwrap_or_default(),
+            None => Bound::default(),
+        }
+    }
+
+    fn if_expr(&mut self, if_expr: &If) {
+        self.expr(&if_expr.condition);
+        let mut branches = vec![self.block(&if_expr.if_block)];
+        match &if_expr.else_part {
+            Else::Else(block) => branches.push(self.block(block)),
+            Else::ElseIf(next_if) => self.if_expr(next_if),
+            Else::None => (),
+        }
+        // the runtime drops variables of a block when it ends, so names bound in a branch
+        // are only available if they were bound before the `if`
+        if let Some(outer) = self.variables.as_mut().and_then(|scopes| scopes.last_mut()) {
+            for bound in branches {
+                outer.maybe.extend(bound.always);
+                outer.maybe.extend(bound.maybe);
+            }
+        }
+    }
+
+    fn for_in(&mut self, name: &str, iter: &Expr, block: &Block) {
+        self.expr(iter);
+        if let Some(scopes) = &mut self.variables {
+            let mut element = Bound::default();
+            element.always.insert(name.to_string());
+            scopes.push(element);
+        }
+        let body = self.block(block);
+        // the body doesn't run for an empty array, so like in a branch of an `if` the names
+        // bound there are only available if they were bound before the loop
+        if let Some(scopes) = &mut self.variables {
+            scopes.pop();
+            if let Some(outer) = scopes.last_mut() {
+                outer.maybe.extend(body.always);
+                outer.maybe.extend(body.maybe);
+            }
+        }
+    }
+
+    fn var(&mut self, expr: &Expr, name: &str) {
+        let scopes = match &self.variables {
+            Some(scopes) => scopes,
+            None => return,
+        };
+        self.usage.read.insert(name.to_string());
+        self.usage.unread.remove(name);
+        if scopes.iter().any(|bound| bound.always.contains(name)) {
+            return;
+        }
+        // the runtime drops names bound in a branch or loop body, reading them fails too
+        let description = if scopes.iter().any(|bound| bound.maybe.contains(name)) {
+            format!(
+                "Undefined variable {}, it is only assigned inside an if or a for loop",
+                name
+            )
+        } else {
+            format!("Undefined variable {}", name)
+        };
+        self.report(Severity::Error, expr, description);
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.expression_type {
+            ExprType::Op(..) => {
+                let (first, chain) = operator_chain(expr);
+                self.expr(first);
+                for (_, _, rhs) in chain {
+                    self.expr(rhs);
+                }
+            }
+            ExprType::Function(name, args) => {
+                if name != self.current {
+                    self.called.insert(name.clone());
+                }
+                // builtins take precedence over functions of the program
+                let (kind, arity) = if self.buildins.contains(&name.as_str()) {
+                    ("Builtin", buildin_arity(name))
+                } else {
+                    match self.program.functions.get(name) {
+                        Some(f) => ("Function", Some(f.arguments.len())),
+                        None => {
+                            let description = format!("Undefined function {}", name);
+                            self.report(Severity::Error, expr, description);
+                            ("Function", None)
+                        }
+                    }
+                };
+                match arity {
+                    Some(arity) if arity != args.len() => {
+                        let description = format!(
+                            "{} {} takes {} arguments but {} were given",
+                            kind,
+                            name,
+                            arity,
+                            args.len()
+                        );
+                        self.report(Severity::Error, expr, description);
+                    }
+                    _ => (),
+                }
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            ExprType::If(if_expr) => self.if_expr(if_expr),
+            ExprType::ForIn(name, iter, block) => self.for_in(name, iter, block),
+            ExprType::Struct(name, fields) => {
+                if !self.program.structs.contains_key(name) {
+                    let description = format!("Undefined struct {}", name);
+                    self.report(Severity::Error, expr, description);
+                }
+                fields.iter().for_each(|(_, value)| self.expr(value));
+            }
+            ExprType::Field(value, _) => self.expr(value),
+            ExprType::Index(value, index) => {
+                self.expr(value);
+                self.expr(index);
+            }
+            ExprType::Array(elements) => elements.iter().for_each(|value| self.expr(value)),
+            ExprType::Map(entries) => {
+                for (key, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            }
+            ExprType::Var(name) => self.var(expr, name),
+            ExprType::Value(_) => (),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn undefined_function() {
+        let input = "fn main() { print(foo(1)) }";
+        let err = check_program(&parse(input).unwrap(), &["print"]).unwrap_err();
+        assert_eq!(err.position, input.find("foo").unwrap());
+        assert_eq!(err.description, "Undefined function foo");
+    }
+
+    #[test]
+    fn wrong_arity() {
+        let input = "fn f(a: i32) { a } fn main() { if true { f(1, 2) } }";
+        let err = check_program(&parse(input).unwrap(), &[]).unwrap_err();
+        assert_eq!(err.position, input.find("f(1").unwrap());
+        assert_eq!(
+            err.description,
+            "Function f takes 1 arguments but 2 were given"
+        );
+        let input = "fn f(a: i32) { a } fn main() { f(1, 2) }";
+        assert!(check_program(&parse(input).unwrap(), &["f"]).is_ok());
+    }
+
+    fn diagnostics(input: &str) -> Vec<(Severity, &str, String)> {
+        check(&parse(input).unwrap(), &["print"])
+            .into_iter()
+            .map(|d| (d.severity, &input[d.span.0..d.span.1], d.message))
+            .collect()
+    }
+
+    #[test]
+    fn undefined_variables() {
+        let input = "fn f(n: i32) { m = n + k; g(m) }
+            fn main() { a = b; let c: i32; print(a, c, f(1)); d = d + 1; d }";
+        assert_eq!(
+            diagnostics(input),
+            [
+                (Severity::Error, "k", "Undefined variable k".to_string()),
+                (Severity::Error, "g(m)", "Undefined function g".to_string()),
+                (Severity::Error, "b", "Undefined variable b".to_string()),
+                (Severity::Error, "d", "Undefined variable d".to_string()),
+            ]
+        );
+        let input = "fn main() { a = 1; if a > 0 { b = a; b } else { a }; a }";
+        assert!(diagnostics(input).is_empty());
+    }
+
+    #[test]
+    fn conditionally_bound() {
+        let input = "fn main() {
+                if true { x = 1; y = 2; y } else if false { x = 2; x } else { z = 3; z };
+                if true { if false { w = 1; w } else { 0 } } else { 1 };
+                print(x, y, z, w);
+                x = 1;
+                x
+            }";
+        let found = diagnostics(input);
+        let names: Vec<_> = found.iter().map(|(_, name, _)| *name).collect();
+        assert_eq!(names, ["x", "y", "z", "w"]);
+        assert!(found
+            .iter()
+            .all(|(severity, _, _)| *severity == Severity::Error));
+        assert_eq!(
+            found[0].2,
+            "Undefined variable x, it is only assigned inside an if or a for loop"
+        );
+        let found = diagnostics("fn main() { for i in [1] { s = i; s }; s }");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, Severity::Error);
+    }
+
+    #[test]
+    fn check_program_ignores_variables() {
+        let input = "fn main() { a }";
+        assert!(check_program(&parse(input).unwrap(), &[]).is_ok());
+        assert_eq!(diagnostics(input).len(), 1);
+    }
+
+    #[test]
+    fn arity_in_dead_branch() {
+        let input = "fn f(a: i32, b: i32) { a + b }
+            fn main() { if false { f(1) } else { substring(\"abc\", 1) }; print() }";
+        let found: Vec<_> = check(&parse(input).unwrap(), &["substring", "print"])
+            .into_iter()
+            .map(|d| (&input[d.span.0..d.span.1], d.message))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    "f(1)",
+                    "Function f takes 2 arguments but 1 were given".to_string()
+                ),
+                (
+                    "substring(\"abc\", 1)",
+                    "Builtin substring takes 3 arguments but 2 were given".to_string()
+                ),
+            ]
+        );
+        // unknown names are only reported as undefined
+        let found = check(&parse("fn main() { g(1) }").unwrap(), &[]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].message, "Undefined function g");
+    }
+
+    #[test]
+    fn unused_values() {
+        let input = "fn main() { a = 1; b = 2; a = b; c = 3; let d: i32; _e = 5; print(a) }";
+        assert_eq!(
+            diagnostics(input),
+            [
+                (
+                    Severity::Warning,
+                    "1",
+                    "Value assigned to a is never read".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    "3",
+                    "Value assigned to c is never read".to_string()
+                ),
+            ]
+        );
+        // a value overwritten only in a branch may still be read
+        let input = "fn main() { f = 1; if true { f = 2; 0 } else { 0 }; print(f) }";
+        assert!(diagnostics(input).is_empty());
+        let input = "fn main() { if true { f = 1; 0 } else { 0 }; f = 2; print(f) }";
+        let found = diagnostics(input);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1, "1");
+    }
+
+    #[test]
+    fn unused_parameters_and_functions() {
+        let input = "fn add(a: i32, b: i32, _c: i32) { a }
+            fn helper(n: i32) { if n > 0 { helper(n - 1) } else { 0 } }
+            fn _spare() { 1 }
+            fn main() { add(1, 2, 3) }";
+        assert_eq!(
+            diagnostics(input),
+            [
+                (
+                    Severity::Warning,
+                    "b: i32",
+                    "Parameter b is never used".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    "fn helper(n: i32) { if n > 0 { helper(n - 1) } else { 0 } }",
+                    "Function helper is never called".to_string()
+                ),
+            ]
+        );
+        assert!(check_program(&parse(input).unwrap(), &[]).is_ok());
+    }
+}
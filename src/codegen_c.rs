@@ -0,0 +1,950 @@
+use crate::ast::{
+    operator_chain, walk_expr, Block, Else, Expr, ExprType, Function, If, Opcode, Program,
+    Statement, Stmt, VarVal, Visitor,
+};
+use std::collections::{HashMap, HashSet};
+
+/// C representation of a pra-lang value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum CType {
+    Int,
+    Bool,
+    Str,
+    /// Unicode code point
+    Char,
+    Void,
+}
+
+impl CType {
+    fn of(value: &VarVal) -> CType {
+        match value {
+            VarVal::I32(_) => CType::Int,
+            VarVal::BOOL(_) => CType::Bool,
+            VarVal::STRING(_) => CType::Str,
+            VarVal::CHAR(_) => CType::Char,
+            // reported by `generate_c`
+            VarVal::STRUCT(..) | VarVal::ARRAY(..) | VarVal::MAP(..) => CType::Void,
+            VarVal::UNIT => CType::Void,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            CType::Int | CType::Bool | CType::Char => "int",
+            CType::Str => "const char*",
+            CType::Void => "void",
+        }
+    }
+
+    fn zero(self) -> &'static str {
+        match self {
+            CType::Str => "\"\"",
+            _ => "0",
+        }
+    }
+}
+
+/// Where the value of a block ends up
+#[derive(Clone)]
+enum Dest {
+    Discard,
+    Return(CType),
+    Assign(String),
+}
+
+/// Generate a C99 translation unit equivalent to `program`.
+///
+/// Types of local variables and return types of functions are inferred from
+/// their uses. Constructs which can't be expressed in C are reported with
+/// `#error` directives, so the output fails to compile instead of misbehaving.
+pub fn generate_c(program: &Program) -> String {
+    let mut functions: Vec<&Function> = program.functions.values().collect();
+    functions.sort_by_key(|f| f.position);
+
+    let mut gen = Generator {
+        program,
+        return_types: HashMap::new(),
+        errors: Vec::new(),
+        helpers: Vec::new(),
+    };
+    let mut prototypes = String::new();
+    let mut definitions = String::new();
+    for function in &functions {
+        prototypes.push_str(&gen.signature(function));
+        prototypes.push_str(";\n");
+        definitions.push('\n');
+        definitions.push_str(&gen.function(function));
+    }
+    if !program.structs.is_empty() {
+        gen.error("structs are not supported".to_string());
+    }
+    if program.functions.contains_key("main") {
+        definitions.push_str("\nint main(void) {\n    f_main();\n    return 0;\n}\n");
+    } else {
+        gen.error("function main wasn't found".to_string());
+    }
+
+    let mut out = String::from("#include <stdio.h>\n#include <stdlib.h>\n#include <string.h>\n\n");
+    for error in &gen.errors {
+        out.push_str(&format!("#error {}\n", c_string(error)));
+    }
+    if !gen.errors.is_empty() {
+        out.push('\n');
+    }
+    for helper in &gen.helpers {
+        out.push_str(helper);
+    }
+    out.push_str(&prototypes);
+    out.push_str(&definitions);
+    out
+}
+
+struct Generator<'a> {
+    program: &'a Program,
+    /// Inferred return types, `None` while the function is being inferred
+    return_types: HashMap<String, Option<CType>>,
+    errors: Vec<String>,
+    /// Helper functions used by the generated code
+    helpers: Vec<&'static str>,
+}
+
+/// Integer power for `**`, a negative exponent stops the program with the error
+/// of the interpreter
+const POW_HELPER: &str = "static int pra_pow(int base, int exp) {
+    int res = 1;
+    if (exp < 0) {
+        fprintf(stderr, \"error[E0009]: Negative exponent\\n\");
+        exit(1);
+    }
+    while (exp-- > 0) {
+        res *= base;
+    }
+    return res;
+}
+
+";
+
+/// Check of a variable declared by `let x: T;` before its value is used, a null
+/// variable stops the program with the error of the interpreter
+const NULL_HELPER: &str = "static void pra_null(int is_null) {
+    if (is_null) {
+        fprintf(stderr, \"error[E0019]: Null value used as an operand\\n\");
+        exit(1);
+    }
+}
+
+";
+
+/// UTF-8 encoding of a character into `buf` of at least 5 bytes, for printing
+const UTF8_HELPER: &str = "static const char* pra_utf8(int c, char *buf) {
+    if (c < 0x80) {
+        buf[0] = c;
+        buf[1] = 0;
+    } else if (c < 0x800) {
+        buf[0] = 0xC0 | (c >> 6);
+        buf[1] = 0x80 | (c & 0x3F);
+        buf[2] = 0;
+    } else if (c < 0x10000) {
+        buf[0] = 0xE0 | (c >> 12);
+        buf[1] = 0x80 | ((c >> 6) & 0x3F);
+        buf[2] = 0x80 | (c & 0x3F);
+        buf[3] = 0;
+    } else {
+        buf[0] = 0xF0 | (c >> 18);
+        buf[1] = 0x80 | ((c >> 12) & 0x3F);
+        buf[2] = 0x80 | ((c >> 6) & 0x3F);
+        buf[3] = 0x80 | (c & 0x3F);
+        buf[4] = 0;
+    }
+    return buf;
+}
+
+";
+
+impl<'a> Generator<'a> {
+    fn error(&mut self, message: String) {
+        if !self.errors.contains(&message) {
+            self.errors.push(message);
+        }
+    }
+
+    fn helper(&mut self, helper: &'static str) {
+        if !self.helpers.contains(&helper) {
+            self.helpers.push(helper);
+        }
+    }
+
+    fn return_type(&mut self, function: &Function) -> Option<CType> {
+        if let Some(ty) = self.return_types.get(&function.name) {
+            return *ty;
+        }
+        self.return_types.insert(function.name.clone(), None);
+        let locals = self.locals(function);
+        let ty = self.infer(&function.block.expr, &locals);
+        self.return_types.insert(function.name.clone(), ty);
+        ty
+    }
+
+    /// Types of arguments and of all variables assigned in the function body
+    fn locals(&mut self, function: &Function) -> HashMap<String, CType> {
+        let mut locals = HashMap::new();
+        for arg in &function.arguments {
+            locals.insert(arg.ident.clone(), CType::of(&arg.value));
+        }
+        self.collect_block(&function.block, &mut locals);
+        locals
+    }
+
+    fn collect_block(&mut self, block: &Block, locals: &mut HashMap<String, CType>) {
+        for Statement { stmt, .. } in &block.statements {
+            match stmt {
+                Stmt::Expr(expr) => self.collect_expr(expr, locals),
+                Stmt::Asgn(id, expr) | Stmt::Const(id, expr) | Stmt::Let(id, expr) => {
+                    self.collect_expr(expr, locals);
+                    match (self.infer(expr, locals), locals.get(id)) {
+                        (Some(CType::Void), _) | (None, _) => (),
+                        (Some(ty), None) => {
+                            locals.insert(id.clone(), ty);
+                        }
+                        (Some(ty), Some(known)) if ty != *known => {
+                            self.error(format!("variable {} changes its type", id))
+                        }
+                        _ => (),
+                    }
+                }
+                Stmt::Decl(var) => match (CType::of(&var.value), locals.get(&var.ident)) {
+                    (CType::Void, _) => (),
+                    (ty, None) => {
+                        locals.insert(var.ident.clone(), ty);
+                    }
+                    (ty, Some(known)) if ty != *known => {
+                        self.error(format!("variable {} changes its type", var.ident))
+                    }
+                    _ => (),
+                },
+            }
+        }
+        self.collect_expr(&block.expr, locals);
+    }
+
+    fn collect_expr(&mut self, expr: &Expr, locals: &mut HashMap<String, CType>) {
+        match &expr.expression_type {
+            ExprType::Op(..) => {
+                let (first, chain) = operator_chain(expr);
+                self.collect_expr(first, locals);
+                for (_, _, rhs) in chain {
+                    self.collect_expr(rhs, locals);
+                }
+            }
+            ExprType::Function(_, args) => {
+                for arg in args {
+                    self.collect_expr(arg, locals);
+                }
+            }
+            ExprType::If(if_expr) => {
+                let mut if_expr = if_expr;
+                loop {
+                    self.collect_expr(&if_expr.condition, locals);
+                    self.collect_block(&if_expr.if_block, locals);
+                    match &if_expr.else_part {
+                        Else::Else(block) => break self.collect_block(block, locals),
+                        Else::ElseIf(next_if) => if_expr = next_if,
+                        Else::None => break,
+                    }
+                }
+            }
+            ExprType::ForIn(_, iter, block) => {
+                self.collect_expr(iter, locals);
+                self.collect_block(block, locals);
+            }
+            ExprType::Struct(_, fields) => {
+                for (_, value) in fields {
+                    self.collect_expr(value, locals);
+                }
+            }
+            ExprType::Field(value, _) => self.collect_expr(value, locals),
+            ExprType::Index(value, index) => {
+                self.collect_expr(value, locals);
+                self.collect_expr(index, locals);
+            }
+            ExprType::Array(elements) => {
+                for value in elements {
+                    self.collect_expr(value, locals);
+                }
+            }
+            ExprType::Map(entries) => {
+                for (key, value) in entries {
+                    self.collect_expr(key, locals);
+                    self.collect_expr(value, locals);
+                }
+            }
+            ExprType::Var(_) | ExprType::Value(_) => (),
+        }
+    }
+
+    fn infer(&mut self, expr: &Expr, locals: &HashMap<String, CType>) -> Option<CType> {
+        match &expr.expression_type {
+            ExprType::Value(value) => Some(CType::of(value)),
+            ExprType::Var(id) => locals.get(id).copied(),
+            ExprType::Op(_, opc, _) => match opc {
+                Opcode::Add
+                | Opcode::Sub
+                | Opcode::Mul
+                | Opcode::Div
+                | Opcode::Mod
+                | Opcode::Pow => Some(CType::Int),
+                _ => Some(CType::Bool),
+            },
+            ExprType::Function(name, _) => {
+                let program = self.program;
+                match program.functions.get(name) {
+                    Some(function) => self.return_type(function),
+                    None => Some(CType::Void),
+                }
+            }
+            ExprType::If(if_expr) => self.infer_if(if_expr, locals),
+            ExprType::ForIn(..)
+            | ExprType::Struct(..)
+            | ExprType::Field(..)
+            | ExprType::Index(..)
+            | ExprType::Array(..)
+            | ExprType::Map(..) => None,
+        }
+    }
+
+    fn infer_if(&mut self, if_expr: &If, locals: &HashMap<String, CType>) -> Option<CType> {
+        let else_type = match &if_expr.else_part {
+            Else::Else(block) => self.infer(&block.expr, locals),
+            Else::ElseIf(next_if) => self.infer_if(next_if, locals),
+            Else::None => return Some(CType::Void),
+        };
+        // a recursive call in one branch takes its type from the other one
+        self.infer(&if_expr.if_block.expr, locals).or(else_type)
+    }
+
+    fn signature(&mut self, function: &Function) -> String {
+        let ty = self.return_type(function).unwrap_or_else(|| {
+            self.error(format!("can't infer return type of {}", function.name));
+            CType::Void
+        });
+        let arguments = if function.arguments.is_empty() {
+            "void".to_string()
+        } else {
+            function
+                .arguments
+                .iter()
+                .map(|arg| {
+                    format!(
+                        "{} {}",
+                        CType::of(&arg.value).name(),
+                        c_name("v", &arg.ident)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        format!(
+            "{} {}({})",
+            ty.name(),
+            c_name("f", &function.name),
+            arguments
+        )
+    }
+
+    fn function(&mut self, function: &Function) -> String {
+        let signature = self.signature(function);
+        let ty = self.return_types[&function.name].unwrap_or(CType::Void);
+        let locals = self.locals(function);
+        let mut body = Body {
+            gen: self,
+            locals,
+            read: HashSet::new(),
+            assigned: HashSet::new(),
+            bindings: Vec::new(),
+            temps: Vec::new(),
+            out: String::new(),
+            indent: 1,
+        };
+        body.block(&function.block, &Dest::Return(ty));
+
+        let mut out = format!("{} {{\n", signature);
+        let mut declared: Vec<(&String, &CType)> = body
+            .assigned
+            .iter()
+            .filter(|id| !function.arguments.iter().any(|arg| &arg.ident == *id))
+            .filter_map(|id| Some((id, body.locals.get(id)?)))
+            .collect();
+        declared.sort();
+        for (id, ty) in &declared {
+            out.push_str(&format!(
+                "    {} {} = {};\n",
+                ty.name(),
+                c_name("v", id),
+                ty.zero()
+            ));
+        }
+        for (id, _) in &declared {
+            if !body.read.contains(*id) {
+                out.push_str(&format!("    (void){};\n", c_name("v", id)));
+            }
+        }
+        for (i, ty) in body.temps.iter().enumerate() {
+            out.push_str(&format!("    {} t_{} = {};\n", ty.name(), i, ty.zero()));
+        }
+        out.push_str(&body.out);
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// Statements of a single function being generated
+struct Body<'g, 'a> {
+    gen: &'g mut Generator<'a>,
+    locals: HashMap<String, CType>,
+    /// Variables read outside of the `let` and `const` declaring them
+    read: HashSet<String>,
+    /// Variables assigned outside of the `let` and `const` declaring them, these are
+    /// declared at the start of the function
+    assigned: HashSet<String>,
+    /// Variables declared by `let` or `const` in the enclosing blocks, innermost last,
+    /// true for `let x: T;` whose flag `n_x` is set while the variable is null
+    bindings: Vec<(String, bool)>,
+    temps: Vec<CType>,
+    out: String,
+    indent: usize,
+}
+
+impl<'g, 'a> Body<'g, 'a> {
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn ty(&mut self, expr: &Expr) -> Option<CType> {
+        self.gen.infer(expr, &self.locals)
+    }
+
+    /// Nullability of the innermost variable `id` declared in the enclosing blocks
+    fn binding(&self, id: &str) -> Option<bool> {
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(name, _)| name == id)
+            .map(|(_, nullable)| *nullable)
+    }
+
+    /// Declare a C variable hiding the one of the same name until the block ends,
+    /// each declaration opens a C block so that a name can be declared again
+    fn declare(&mut self, id: &str, ty: CType, value: &str, nullable: bool) {
+        self.line("{");
+        self.indent += 1;
+        let name = c_name("v", id);
+        self.line(&format!("{} {} = {};", ty.name(), name, value));
+        self.line(&format!("(void){};", name));
+        if nullable {
+            let flag = c_name("n", id);
+            self.line(&format!("int {} = 1;", flag));
+            self.line(&format!("(void){};", flag));
+        }
+        self.bindings.push((id.to_string(), nullable));
+    }
+
+    fn block(&mut self, block: &Block, dest: &Dest) {
+        let outer = self.bindings.len();
+        for Statement { stmt, .. } in &block.statements {
+            match stmt {
+                Stmt::Expr(expr) => self.discard(expr),
+                Stmt::Asgn(id, expr) => {
+                    if let Some(CType::Void) = self.ty(expr) {
+                        self.discard(expr);
+                    } else {
+                        let value = self.value(expr);
+                        self.line(&format!("{} = {};", c_name("v", id), value));
+                        match self.binding(id) {
+                            Some(true) => self.line(&format!("{} = 0;", c_name("n", id))),
+                            Some(false) => (),
+                            None => {
+                                self.assigned.insert(id.clone());
+                            }
+                        }
+                    }
+                }
+                Stmt::Const(id, expr) | Stmt::Let(id, expr) => match self.ty(expr) {
+                    Some(CType::Void) => self.discard(expr),
+                    ty => {
+                        let ty = ty.unwrap_or_else(|| {
+                            self.gen.error(format!("can't infer type of {}", id));
+                            CType::Int
+                        });
+                        let mut value = self.value(expr);
+                        // the C variable is in scope already in its initializer
+                        if reads(expr, id) {
+                            let temp = format!("t_{}", self.temps.len());
+                            self.temps.push(ty);
+                            self.line(&format!("{} = {};", temp, value));
+                            value = temp;
+                        }
+                        self.declare(id, ty, &value, false);
+                    }
+                },
+                // null has no C representation, a flag tells if the variable is null
+                Stmt::Decl(var) => match CType::of(&var.value) {
+                    CType::Void => (),
+                    ty => self.declare(&var.ident, ty, ty.zero(), true),
+                },
+            }
+        }
+        self.tail(&block.expr, dest);
+        for _ in outer..self.bindings.len() {
+            self.indent -= 1;
+            self.line("}");
+        }
+        self.bindings.truncate(outer);
+    }
+
+    fn tail(&mut self, expr: &Expr, dest: &Dest) {
+        if let ExprType::If(if_expr) = &expr.expression_type {
+            return self.if_stmt(if_expr, dest);
+        }
+        match dest {
+            Dest::Discard | Dest::Return(CType::Void) => self.discard(expr),
+            Dest::Return(_) => {
+                let value = self.value(expr);
+                self.line(&format!("return {};", value));
+            }
+            Dest::Assign(target) => {
+                let value = self.value(expr);
+                self.line(&format!("{} = {};", target, value));
+            }
+        }
+    }
+
+    fn if_stmt(&mut self, if_expr: &If, dest: &Dest) {
+        let condition = self.value(&if_expr.condition);
+        self.line(&format!("if ({}) {{", condition));
+        self.indent += 1;
+        self.block(&if_expr.if_block, dest);
+        self.indent -= 1;
+        match &if_expr.else_part {
+            Else::Else(block) => {
+                self.line("} else {");
+                self.indent += 1;
+                self.block(block, dest);
+                self.indent -= 1;
+            }
+            Else::ElseIf(next_if) => {
+                self.line("} else {");
+                self.indent += 1;
+                self.if_stmt(next_if, dest);
+                self.indent -= 1;
+            }
+            Else::None => (),
+        }
+        self.line("}");
+    }
+
+    /// Emit an expression whose value isn't used
+    fn discard(&mut self, expr: &Expr) {
+        match &expr.expression_type {
+            ExprType::If(if_expr) => self.if_stmt(if_expr, &Dest::Discard),
+            ExprType::Function(name, args) if name == "print" => self.print(args),
+            ExprType::Function(..) => {
+                let call = self.value(expr);
+                self.line(&format!("{};", call));
+            }
+            _ => {
+                let value = self.value(expr);
+                self.line(&format!("(void)({});", value));
+            }
+        }
+    }
+
+    fn print(&mut self, args: &[Box<Expr>]) {
+        let mut format = String::new();
+        let mut values = Vec::new();
+        for arg in args {
+            match self.ty(arg) {
+                Some(CType::Int) => {
+                    format.push_str("%d");
+                    values.push(self.value(arg));
+                }
+                Some(CType::Bool) => {
+                    format.push_str("%s");
+                    let value = self.value(arg);
+                    values.push(format!("({} ? \"true\" : \"false\")", value));
+                }
+                Some(CType::Str) => {
+                    format.push_str("%s");
+                    values.push(self.value(arg));
+                }
+                Some(CType::Char) => {
+                    self.gen.helper(UTF8_HELPER);
+                    format.push_str("%s");
+                    let value = self.value(arg);
+                    values.push(format!("pra_utf8({}, (char[5]){{0}})", value));
+                }
+                Some(CType::Void) | None => {
+                    self.discard(arg);
+                    format.push_str("()");
+                }
+            }
+        }
+        let mut call = format!("printf(\"{}\\n\"", format);
+        for value in values {
+            call.push_str(", ");
+            call.push_str(&value);
+        }
+        call.push_str(");");
+        self.line(&call);
+    }
+
+    /// Return C expression computing the value of `expr`
+    fn value(&mut self, expr: &Expr) -> String {
+        match &expr.expression_type {
+            ExprType::Value(value) => match value {
+                VarVal::I32(Some(v)) => v.to_string(),
+                VarVal::BOOL(Some(v)) => (*v as i32).to_string(),
+                VarVal::STRING(Some(v)) => c_string(v),
+                VarVal::CHAR(Some(v)) => (*v as u32).to_string(),
+                _ => {
+                    self.gen.error("value without C representation".to_string());
+                    "0".to_string()
+                }
+            },
+            ExprType::Var(id) => match self.binding(id) {
+                Some(true) => {
+                    self.gen.helper(NULL_HELPER);
+                    format!("(pra_null({}), {})", c_name("n", id), c_name("v", id))
+                }
+                Some(false) => c_name("v", id),
+                None => {
+                    self.read.insert(id.clone());
+                    c_name("v", id)
+                }
+            },
+            ExprType::Op(..) => {
+                let (mut lhs, chain) = operator_chain(expr);
+                let mut value = self.value(lhs);
+                for (op_expr, opc, rhs) in chain {
+                    let strings = self.ty(lhs) == Some(CType::Str);
+                    value = self.operation(opc, strings, value, rhs);
+                    lhs = op_expr;
+                }
+                value
+            }
+            ExprType::Function(name, args) => {
+                if !self.gen.program.functions.contains_key(name) {
+                    self.gen
+                        .error(format!("function {} can't be used as a value", name));
+                }
+                let args: Vec<String> = args.iter().map(|arg| self.value(arg)).collect();
+                format!("{}({})", c_name("f", name), args.join(", "))
+            }
+            ExprType::Struct(..) | ExprType::Field(..) => {
+                self.gen.error("structs are not supported".to_string());
+                "0".to_string()
+            }
+            ExprType::Index(..) => {
+                self.gen.error("indexing is not supported".to_string());
+                "0".to_string()
+            }
+            ExprType::Array(..) => {
+                self.gen.error("arrays are not supported".to_string());
+                "0".to_string()
+            }
+            ExprType::Map(..) => {
+                self.gen.error("maps are not supported".to_string());
+                "0".to_string()
+            }
+            ExprType::ForIn(..) => {
+                self.gen.error("loops are not supported".to_string());
+                "0".to_string()
+            }
+            ExprType::If(if_expr) => match self.ty(expr) {
+                Some(CType::Void) | None => {
+                    self.gen
+                        .error("if expression without value used as a value".to_string());
+                    "0".to_string()
+                }
+                Some(ty) => {
+                    let temp = format!("t_{}", self.temps.len());
+                    self.temps.push(ty);
+                    self.if_stmt(if_expr, &Dest::Assign(temp.clone()));
+                    temp
+                }
+            },
+        }
+    }
+
+    /// C code applying `opc` to the code `l` of the left operand and to `rhs`, `strings`
+    /// when the left operand is a string
+    fn operation(&mut self, opc: &Opcode, strings: bool, l: String, rhs: &Expr) -> String {
+        let r = self.value(rhs);
+        match (opc, strings) {
+            (Opcode::Eq, true) => format!("(strcmp({}, {}) == 0)", l, r),
+            (Opcode::Ne, true) => format!("(strcmp({}, {}) != 0)", l, r),
+            (_, true) => {
+                self.gen
+                    .error(format!("invalid operator {:?} for strings", opc));
+                "0".to_string()
+            }
+            (Opcode::Range, false) | (Opcode::RangeInclusive, false) => {
+                self.gen.error("ranges are not supported".to_string());
+                "0".to_string()
+            }
+            (Opcode::Pow, false) => {
+                self.gen.helper(POW_HELPER);
+                format!("pra_pow({}, {})", l, r)
+            }
+            (_, false) => format!("({} {} {})", l, c_operator(opc), r),
+        }
+    }
+}
+
+/// Test if `expr` reads the variable `id`
+fn reads(expr: &Expr, id: &str) -> bool {
+    struct Reads<'a> {
+        id: &'a str,
+        found: bool,
+    }
+
+    impl Visitor for Reads<'_> {
+        fn visit_expr(&mut self, expr: &Expr) {
+            let (first, chain) = operator_chain(expr);
+            if let ExprType::Var(id) = &first.expression_type {
+                self.found |= id == self.id;
+            }
+            walk_expr(self, first);
+            for (_, _, rhs) in chain {
+                self.visit_expr(rhs);
+            }
+        }
+    }
+
+    let mut visitor = Reads { id, found: false };
+    visitor.visit_expr(expr);
+    visitor.found
+}
+
+fn c_operator(opc: &Opcode) -> &'static str {
+    match opc {
+        Opcode::Mul => "*",
+        Opcode::Div => "/",
+        Opcode::Mod => "%",
+        Opcode::Pow => unreachable!("power is generated as a call of pra_pow"),
+        Opcode::Add => "+",
+        Opcode::Sub => "-",
+        Opcode::Eq => "==",
+        Opcode::Ne => "!=",
+        Opcode::Lt => "<",
+        Opcode::Le => "<=",
+        Opcode::Gt => ">",
+        Opcode::Ge => ">=",
+        Opcode::And => "&&",
+        Opcode::Or => "||",
+        Opcode::Range | Opcode::RangeInclusive => {
+            unreachable!("ranges are reported as unsupported")
+        }
+    }
+}
+
+/// C identifier of the variable or function `ident`, non-ASCII identifiers get a
+/// `u` after the prefix and their underscores and non-ASCII characters encoded as
+/// `__` and `_<hex code>_`, so they can't clash with the ASCII ones
+fn c_name(prefix: &str, ident: &str) -> String {
+    if ident.is_ascii() {
+        return format!("{}_{}", prefix, ident);
+    }
+    let mut out = format!("{}u_", prefix);
+    for ch in ident.chars() {
+        match ch {
+            '_' => out.push_str("__"),
+            ch if ch.is_ascii() => out.push(ch),
+            ch => out.push_str(&format!("_{:x}_", ch as u32)),
+        }
+    }
+    out
+}
+
+/// Quote `s` as a C string literal
+fn c_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            // avoid forming trigraphs
+            '?' => out.push_str("\\?"),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => out.push_str(&format!("\\{:03o}", ch as u32)),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse;
+    use std::process::Command;
+
+    /// Compile `input` to a native executable and return what it prints
+    fn compile_and_run(name: &str, input: &str) -> String {
+        let source = generate_c(&parse(input).unwrap());
+        let dir = std::env::temp_dir().join(format!("pra-codegen-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let c_file = dir.join("main.c");
+        let exe = dir.join("main");
+        std::fs::write(&c_file, &source).unwrap();
+        let cc = Command::new("cc")
+            .args(["-std=c99", "-Wall", "-Werror", "-o"])
+            .arg(&exe)
+            .arg(&c_file)
+            .output()
+            .expect("C compiler cc is required");
+        assert!(
+            cc.status.success(),
+            "{}\n{}",
+            source,
+            String::from_utf8_lossy(&cc.stderr)
+        );
+        let run = Command::new(&exe).output().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        String::from_utf8(run.stdout).unwrap()
+    }
+
+    #[test]
+    fn fibonacci() {
+        let input = "
+            fn fib(n: i32) {
+                res = 1;
+                s = 0;
+                if ( n > 2 ) {
+                    res = fib( n - 1 );
+                    s = fib( n - 2 );
+                    0
+                };
+                res + s
+            }
+            fn main() {
+                print(\"fib(10) = \", fib(10))
+            }";
+        assert_eq!(compile_and_run("fibonacci", input), "fib(10) = 55\n");
+    }
+
+    #[test]
+    fn if_else_values() {
+        let input = "
+            fn classify(n: i32) {
+                if n % 15 == 0 { \"FizzBuzz\" } else if n % 3 == 0 { \"Fizz\" } else { \"other\" }
+            }
+            fn main() {
+                big = if 3 > 2 { true } else { false };
+                print(classify(30), \" \", classify(9), \" \", classify(7), \" \", big);
+                print(\"trigraph ??= \", classify(7) == \"other\")
+            }";
+        assert_eq!(
+            compile_and_run("if_else_values", input),
+            "FizzBuzz Fizz other true\ntrigraph ??= true\n"
+        );
+    }
+
+    #[test]
+    fn recursion_without_else() {
+        let input = "
+            fn count(current: i32, stop: i32) {
+                if current <= stop {
+                    print(current);
+                    count(current + 1, stop)
+                }
+            }
+            fn main() {
+                unused = 5;
+                count(1, 3)
+            }";
+        assert_eq!(
+            compile_and_run("recursion_without_else", input),
+            "1\n2\n3\n"
+        );
+    }
+
+    #[test]
+    fn declarations() {
+        let input = "
+            fn main() {
+                let count: i32;
+                if true { count = 2; 0 } else { 0 };
+                print(count + 1);
+                let name: String;
+                print(name)
+            }";
+        let source = generate_c(&parse(input).unwrap());
+        assert!(source.contains("error[E0019]: Null value used as an operand"));
+        assert_eq!(compile_and_run("declarations", input), "3\n");
+        let input = "fn main() { let count: i32; count = count + 2; print(count) }";
+        assert_eq!(compile_and_run("null_read", input), "");
+    }
+
+    #[test]
+    fn shadowing() {
+        let input = "
+            fn twice(a: i32) { let a = a * 2; a }
+            fn main() {
+                x = 1;
+                if true { let x = 5; print(x) } else { 0 };
+                print(x);
+                let x = 10;
+                let x = x + twice(x);
+                const c = 3;
+                y = if x > 1 { let c = 4; x = x + c; c } else { c };
+                print(x, \" \", y, \" \", c)
+            }";
+        assert_eq!(compile_and_run("shadowing", input), "5\n1\n34 4 3\n");
+    }
+
+    #[test]
+    fn power() {
+        let input = "fn main() { print(2 ** 10, \" \", 2 ** 3 ** 2) }";
+        assert_eq!(compile_and_run("power", input), "1024 512\n");
+    }
+
+    #[test]
+    fn negative_exponent() {
+        let input = "fn main() { print(2 ** 2); print(2 ** -1) }";
+        let source = generate_c(&parse(input).unwrap());
+        assert!(source.contains("error[E0009]: Negative exponent"));
+        assert_eq!(compile_and_run("negative_exponent", input), "4\n");
+    }
+
+    #[test]
+    fn non_ascii_names() {
+        let input = "
+            fn délka(ú: i32, u_fa_: i32) { ú + u_fa_ }
+            fn main() { π = 3; print(délka(π, 1)) }";
+        let source = generate_c(&parse(input).unwrap());
+        assert!(source.contains("int fu_d_e9_lka(int vu__fa_, int v_u_fa_)"));
+        assert_eq!(compile_and_run("non_ascii_names", input), "4\n");
+    }
+
+    #[test]
+    fn chars() {
+        let input = "fn main() { c = 'é'; print(c, 'z' - 'a', \" \", c > 'e', '\\u{1F600}') }";
+        assert_eq!(compile_and_run("chars", input), "é25 true😀\n");
+    }
+
+    #[test]
+    fn unsupported_reported() {
+        let source = generate_c(&parse("fn f() { \"a\" + \"b\" }").unwrap());
+        assert!(source.contains("#error \"function main wasn't found\""));
+        assert!(source.contains("#error \"invalid operator Add for strings\""));
+        let source = generate_c(&parse("struct P { x: i32 } fn main() { P { x: 1 }.x }").unwrap());
+        assert!(source.contains("#error \"structs are not supported\""));
+    }
+}
@@ -0,0 +1,117 @@
+use crate::ast::{operator_chain, walk_expr, walk_program, Expr, Program, Visitor};
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use crate::Set;
+use alloc::collections::BTreeSet;
+use core::cmp::Reverse;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// Source span `(start, end)` of an expression in bytes
+pub type Span = (usize, usize);
+
+/// Record of which expressions of a program were evaluated during a run
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Coverage {
+    spans: BTreeSet<Span>,
+    executed: Set<Span>,
+}
+
+impl Coverage {
+    /// Create an empty coverage record for every expression in `program`
+    pub fn new(program: &Program) -> Self {
+        let mut spans = Spans(BTreeSet::new());
+        walk_program(&mut spans, program);
+        Coverage {
+            spans: spans.0,
+            executed: Set::new(),
+        }
+    }
+
+    pub(crate) fn record(&mut self, expr: &Expr) {
+        self.executed.insert((expr.position, expr.end));
+    }
+
+    /// Return true if the innermost expression containing `offset` was evaluated
+    pub fn is_covered(&self, offset: usize) -> bool {
+        self.spans
+            .iter()
+            .filter(|(start, end)| *start <= offset && offset < *end)
+            .min_by_key(|(start, end)| end - start)
+            .is_some_and(|span| self.executed.contains(span))
+    }
+
+    /// Return the outermost spans that were never evaluated, ordered by position
+    pub fn uncovered_spans(&self) -> Vec<Span> {
+        let mut candidates: Vec<&Span> = self
+            .spans
+            .iter()
+            .filter(|s| !self.executed.contains(s))
+            .collect();
+        // enclosing spans go first so that the spans nested in them can be skipped
+        candidates.sort_by_key(|(start, end)| (*start, Reverse(*end)));
+        let mut uncovered: Vec<Span> = Vec::new();
+        for span in candidates {
+            match uncovered.last() {
+                Some((_, end)) if span.1 <= *end => (),
+                _ => uncovered.push(*span),
+            }
+        }
+        uncovered
+    }
+}
+
+/// Collects the spans of all expressions
+struct Spans(BTreeSet<Span>);
+
+impl Visitor for Spans {
+    fn visit_expr(&mut self, expr: &Expr) {
+        let (first, chain) = operator_chain(expr);
+        for (op_expr, _, _) in &chain {
+            self.0.insert((op_expr.position, op_expr.end));
+        }
+        self.0.insert((first.position, first.end));
+        walk_expr(self, first);
+        for (_, _, rhs) in chain {
+            self.visit_expr(rhs);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use crate::{parse, Interpreter};
+    use std::collections::HashMap;
+
+    #[test]
+    fn if_else_coverage() {
+        let input = "fn main() { if 1 < 2 { 10 } else { 20 } }";
+        let program = parse(input).unwrap();
+        let (mut globals, mut buildins) = (HashMap::new(), HashMap::new());
+        let mut interpreter =
+            Interpreter::new(&program, &mut globals, &mut buildins).with_coverage();
+        interpreter.run().unwrap();
+        let coverage = interpreter.coverage().unwrap();
+
+        let taken = input.find("10").unwrap();
+        let skipped = input.find("20").unwrap();
+        assert!(coverage.is_covered(taken));
+        assert!(!coverage.is_covered(skipped));
+        assert_eq!(coverage.uncovered_spans(), vec![(skipped, skipped + 2)]);
+    }
+
+    #[test]
+    fn uncovered_function() {
+        let input = "fn unused(a: i32) { a + 1 } fn main() { 0 }";
+        let program = parse(input).unwrap();
+        let (mut globals, mut buildins) = (HashMap::new(), HashMap::new());
+        let mut interpreter =
+            Interpreter::new(&program, &mut globals, &mut buildins).with_coverage();
+        interpreter.run().unwrap();
+        let coverage = interpreter.coverage().unwrap();
+
+        let body = input.find("a + 1").unwrap();
+        assert_eq!(coverage.uncovered_spans(), vec![(body, body + 5)]);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::check::CheckError;
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use crate::typecheck::TypeError;
+use crate::{ParsingError, RuntimeError};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// How serious a `Diagnostic` is, only errors make the program fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum Severity {
+    Error,
+    Warning,
+    /// Additional information, like where a name was defined first
+    Note,
+}
+
+/// Problem found by the parser, the checkers or the interpreter, in a common shape
+/// for reporting them together
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable code of the error, see `explain`, `None` for problems of the checkers
+    pub code: Option<&'static str>,
+    pub message: String,
+    /// Byte offsets of the start and the end of the source the problem concerns
+    pub span: (usize, usize),
+    /// Other places of the source the problem refers to, with a note about each
+    pub related: Vec<((usize, usize), String)>,
+    /// Name of the source, like the path of the file, shown with the location
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub source_name: Option<String>,
+}
+
+impl Diagnostic {
+    /// Diagnostic without a code, related places and source name
+    pub fn new(severity: Severity, span: (usize, usize), message: String) -> Self {
+        Diagnostic {
+            severity,
+            code: None,
+            message,
+            span,
+            related: Vec::new(),
+            source_name: None,
+        }
+    }
+}
+
+impl From<&ParsingError> for Diagnostic {
+    fn from(error: &ParsingError) -> Self {
+        Diagnostic {
+            code: Some(error.code()),
+            related: error.related.clone(),
+            source_name: error.source_name.clone(),
+            ..Diagnostic::new(
+                Severity::Error,
+                (error.from, error.to),
+                error.description.clone(),
+            )
+        }
+    }
+}
+
+impl From<&RuntimeError> for Diagnostic {
+    fn from(error: &RuntimeError) -> Self {
+        Diagnostic {
+            code: Some(error.code()),
+            ..Diagnostic::new(
+                Severity::Error,
+                (error.position, error.end),
+                error.error_type.to_string(),
+            )
+        }
+    }
+}
+
+impl From<&CheckError> for Diagnostic {
+    fn from(error: &CheckError) -> Self {
+        let span = (error.position, error.position + 1);
+        Diagnostic::new(Severity::Error, span, error.description.clone())
+    }
+}
+
+impl From<&TypeError> for Diagnostic {
+    fn from(error: &TypeError) -> Self {
+        let span = (error.position, error.position + 1);
+        Diagnostic::new(Severity::Error, span, error.description.clone())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::{check_program, check_types, execute, parse, parse_all, Map};
+
+    #[test]
+    fn from_errors() {
+        let err = parse("fn main() { a = 1 b }").unwrap_err();
+        let diagnostic = Diagnostic::from(&err);
+        assert_eq!(diagnostic.code, Some("E0103"));
+        assert_eq!(diagnostic.message, "missing ';' before identifier");
+        assert_eq!(diagnostic.span, (18, 19));
+
+        let program = parse("fn main() { a }").unwrap();
+        let err = execute(&program, &mut Map::new(), &mut Map::new()).unwrap_err();
+        assert_eq!(
+            Diagnostic::from(&err),
+            Diagnostic {
+                code: Some("E0001"),
+                ..Diagnostic::new(
+                    Severity::Error,
+                    (12, 13),
+                    "Undefined variable a".to_string()
+                )
+            }
+        );
+
+        let program = parse("fn main() { f() }").unwrap();
+        let err = check_program(&program, &[]).unwrap_err();
+        let expected = "Undefined function f".to_string();
+        assert_eq!(
+            Diagnostic::from(&err),
+            Diagnostic::new(Severity::Error, (12, 13), expected)
+        );
+
+        let program = parse("fn main() -> i32 { true }").unwrap();
+        let diagnostic = Diagnostic::from(&check_types(&program).unwrap_err());
+        assert_eq!(diagnostic.code, None);
+        assert_eq!(diagnostic.span, (19, 20));
+    }
+
+    #[test]
+    fn duplicate_definition() {
+        let errors = parse_all("fn f() { 1 }\nfn f() { 2 }\nfn main() { f() }").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, Some("E0114"));
+        assert_eq!(errors[0].span, (13, 17));
+        assert_eq!(
+            errors[0].related,
+            [((0, 4), "function f is first defined here".to_string())]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_mixed() {
+        let program = parse("fn main() { a = 1; b }").unwrap();
+        let mut diagnostics = crate::check(&program, &[]);
+        let err = execute(&program, &mut Map::new(), &mut Map::new()).unwrap_err();
+        diagnostics.push(Diagnostic::from(&err));
+        let json = serde_json::to_string(&diagnostics).unwrap();
+        assert_eq!(
+            json,
+            "[{\"severity\":\"Error\",\"code\":null,\
+             \"message\":\"Undefined variable b\",\"span\":[19,20],\"related\":[]},\
+             {\"severity\":\"Warning\",\"code\":null,\
+             \"message\":\"Value assigned to a is never read\",\"span\":[16,17],\"related\":[]},\
+             {\"severity\":\"Error\",\"code\":\"E0001\",\
+             \"message\":\"Undefined variable b\",\"span\":[19,20],\"related\":[]}]"
+        );
+    }
+}
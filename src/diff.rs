@@ -0,0 +1,167 @@
+use crate::ast::{Block, Else, Expr, ExprType, Function, If, Program, Stmt};
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+
+/// Structural difference between two programs, source positions are ignored
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Difference {
+    AddedFunction(String),
+    RemovedFunction(String),
+    /// Function arguments or return type differ
+    ChangedSignature(String),
+    ChangedBody(String),
+    AddedStruct(String),
+    RemovedStruct(String),
+    ChangedStruct(String),
+}
+
+/// Return differences turning program `a` into program `b`, ordered by name
+pub fn ast_diff(a: &Program, b: &Program) -> Vec<Difference> {
+    let mut differences = Vec::new();
+
+    let mut names: Vec<&String> = a.functions.keys().chain(b.functions.keys()).collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        match (a.functions.get(name), b.functions.get(name)) {
+            (Some(_), None) => differences.push(Difference::RemovedFunction(name.clone())),
+            (None, Some(_)) => differences.push(Difference::AddedFunction(name.clone())),
+            (Some(old), Some(new)) => {
+                if old.arguments != new.arguments || old.return_type != new.return_type {
+                    differences.push(Difference::ChangedSignature(name.clone()));
+                }
+                if body(old) != body(new) {
+                    differences.push(Difference::ChangedBody(name.clone()));
+                }
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+
+    let mut names: Vec<&String> = a.structs.keys().chain(b.structs.keys()).collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        match (a.structs.get(name), b.structs.get(name)) {
+            (Some(_), None) => differences.push(Difference::RemovedStruct(name.clone())),
+            (None, Some(_)) => differences.push(Difference::AddedStruct(name.clone())),
+            (Some(old), Some(new)) => {
+                if old.fields != new.fields {
+                    differences.push(Difference::ChangedStruct(name.clone()));
+                }
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+    differences
+}
+
+/// Copy of the function body with every position zeroed
+fn body(function: &Function) -> Block {
+    let mut block = function.block.clone();
+    strip_block(&mut block);
+    block
+}
+
+fn strip_block(block: &mut Block) {
+    block.position = 0;
+    block.end = 0;
+    for statement in &mut block.statements {
+        statement.position = 0;
+        statement.end = 0;
+        match &mut statement.stmt {
+            Stmt::Expr(expr) | Stmt::Asgn(_, expr) | Stmt::Const(_, expr) | Stmt::Let(_, expr) => {
+                strip_expr(expr)
+            }
+            Stmt::Decl(_) => (),
+        }
+    }
+    strip_expr(&mut block.expr);
+}
+
+fn strip_if(if_expr: &mut If) {
+    if_expr.position = 0;
+    if_expr.end = 0;
+    strip_expr(&mut if_expr.condition);
+    strip_block(&mut if_expr.if_block);
+    match &mut if_expr.else_part {
+        Else::Else(block) => strip_block(block),
+        Else::ElseIf(next_if) => strip_if(next_if),
+        Else::None => (),
+    }
+}
+
+fn strip_expr(expr: &mut Expr) {
+    expr.position = 0;
+    expr.end = 0;
+    match &mut expr.expression_type {
+        ExprType::Op(lhs, _, rhs) => {
+            strip_expr(lhs);
+            strip_expr(rhs);
+        }
+        ExprType::Function(_, args) => {
+            for arg in args {
+                strip_expr(arg);
+            }
+        }
+        ExprType::If(if_expr) => strip_if(if_expr),
+        ExprType::ForIn(_, iter, block) => {
+            strip_expr(iter);
+            strip_block(block);
+        }
+        ExprType::Struct(_, fields) => {
+            for (_, value) in fields {
+                strip_expr(value);
+            }
+        }
+        ExprType::Field(value, _) => strip_expr(value),
+        ExprType::Index(value, index) => {
+            strip_expr(value);
+            strip_expr(index);
+        }
+        ExprType::Array(elements) => {
+            for value in elements {
+                strip_expr(value);
+            }
+        }
+        ExprType::Map(entries) => {
+            for (key, value) in entries {
+                strip_expr(key);
+                strip_expr(value);
+            }
+        }
+        ExprType::Var(_) | ExprType::Value(_) => (),
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn changed_body() {
+        let a = parse("fn inc(a: i32) { a + 1 } fn main() { inc(1) }").unwrap();
+        let b = parse("fn inc(a: i32) { a + 2 }\n\nfn main() { inc(1) }").unwrap();
+        assert_eq!(
+            ast_diff(&a, &b),
+            vec![Difference::ChangedBody("inc".to_string())]
+        );
+        assert_eq!(ast_diff(&a, &a), vec![]);
+    }
+
+    #[test]
+    fn added_and_removed() {
+        let a = parse("struct P { x: i32 } fn f() { 1 } fn main() { 0 }").unwrap();
+        let b = parse("struct P { y: i32 } fn main(a: i32) { 0 } fn g() -> i32 { 1 }").unwrap();
+        assert_eq!(
+            ast_diff(&a, &b),
+            vec![
+                Difference::RemovedFunction("f".to_string()),
+                Difference::AddedFunction("g".to_string()),
+                Difference::ChangedSignature("main".to_string()),
+                Difference::ChangedStruct("P".to_string()),
+            ]
+        );
+    }
+}
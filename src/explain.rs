@@ -0,0 +1,263 @@
+/// Codes of all errors with their longer descriptions, `E00xx` are runtime errors
+/// and `E01xx` errors found while parsing
+const EXPLANATIONS: &[(&str, &str)] = &[
+    (
+        "E0001",
+        "A variable was read before any value was assigned to it. Variables come into \
+         existence by assignment `a = 1;` or declaration `let a: i32;`, either in the \
+         function or as globals passed to `execute`.",
+    ),
+    (
+        "E0002",
+        "A function was called which is neither defined in the program nor provided \
+         as a builtin.",
+    ),
+    (
+        "E0003",
+        "An operator was applied to values it isn't defined for, e.g. `<` on booleans.",
+    ),
+    (
+        "E0004",
+        "Operands of an operator or arguments of a builtin have types which don't fit \
+         together, or one of them is null.",
+    ),
+    (
+        "E0005",
+        "The condition of an `if` or an operand of `&&` or `||` isn't a boolean.",
+    ),
+    (
+        "E0006",
+        "A function was called with a different number of arguments than it declares.",
+    ),
+    (
+        "E0007",
+        "A name bound by `const` was assigned again, constants can't change their value.",
+    ),
+    (
+        "E0008",
+        "The result of an arithmetic operation doesn't fit into i32.",
+    ),
+    (
+        "E0009",
+        "The exponent of `**` is negative, integer powers need exponents of 0 or more.",
+    ),
+    (
+        "E0010",
+        "A builtin was called which the sandbox policy of the interpreter doesn't allow.",
+    ),
+    (
+        "E0011",
+        "A struct literal names a struct which isn't declared in the program.",
+    ),
+    (
+        "E0012",
+        "A field was accessed or initialized which the struct doesn't declare.",
+    ),
+    (
+        "E0013",
+        "A struct literal leaves out one of the fields declared by the struct.",
+    ),
+    (
+        "E0014",
+        "An index or range lies outside of the array or string it is applied to.",
+    ),
+    (
+        "E0015",
+        "The program has no `main` function, execution starts by calling `main()`.",
+    ),
+    (
+        "E0016",
+        "The debug hook asked to stop the execution before the reported statement.",
+    ),
+    (
+        "E0017",
+        "The condition of `assert` was false, or the two values passed to `assert_eq` \
+         differ. The message shows the values compared.",
+    ),
+    (
+        "E0018",
+        "The program gave up by calling `error(message)`, the message says why.",
+    ),
+    (
+        "E0019",
+        "An operator was applied to a null value. Variables declared by `let a: i32;` \
+         are null until assigned, builtins like `read_int` return null when they have \
+         no result. Test values with `is_null` first.",
+    ),
+    (
+        "E0020",
+        "The right operand of `/` or `%` is zero, test the divisor before dividing.",
+    ),
+    (
+        "E0021",
+        "A range `start..end` or `range(start, end)` has more than 1048576 elements, \
+         iterate over it in smaller parts.",
+    ),
+    (
+        "E0101",
+        "The source contains a character which doesn't start any token, e.g. `?` or `#`.",
+    ),
+    (
+        "E0102",
+        "The parser found a token which can't continue the program at this place, \
+         the error lists the tokens it would accept.",
+    ),
+    (
+        "E0103",
+        "Two statements are not separated by `;`. A line break ends a statement too, \
+         unless it is inside parentheses or brackets.",
+    ),
+    (
+        "E0104",
+        "The source ended in the middle of a declaration, usually because of a missing \
+         closing `}` or `)`.",
+    ),
+    (
+        "E0105",
+        "The parser found a token after the point where the program was already complete.",
+    ),
+    (
+        "E0106",
+        "The lexer produced a token the parser doesn't know.",
+    ),
+    ("E0107", "A block comment `/*` is missing its closing `*/`."),
+    (
+        "E0108",
+        "A backslash in a string is followed by a character without escape meaning, \
+         known escapes are `\\n`, `\\r`, `\\t`, `\\\\`, `\\\"`, `\\'`, `\\0` and `\\u{..}`.",
+    ),
+    (
+        "E0109",
+        "A character literal must contain exactly one character between single quotes.",
+    ),
+    ("E0110", "A string literal is missing its closing quote."),
+    (
+        "E0111",
+        "An integer literal doesn't fit into i32, which ranges from -2147483648 to 2147483647.",
+    ),
+    (
+        "E0112",
+        "An integer literal contains a digit invalid for its base, or starts or ends \
+         with `_`.",
+    ),
+    (
+        "E0113",
+        "Comparisons were chained like `a < b < c`, combine them with `&&` instead.",
+    ),
+    (
+        "E0114",
+        "Two functions or two structs have the same name. The first definition is kept, \
+         rename or remove the other one.",
+    ),
+    (
+        "E0115",
+        "An expression is nested too deeply in operands, arguments, indices or blocks, \
+         evaluating it could overflow the stack. Split it using variables. Long chains \
+         like `a + b + c` aren't nested and are accepted.",
+    ),
+];
+
+/// Longer description of the error with `code`, like `E0001`, `None` for unknown codes
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS
+        .iter()
+        .find(|(known, _)| *known == code)
+        .map(|(_, explanation)| *explanation)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(not(feature = "std"))]
+    use crate::prelude::*;
+    use crate::{LexerErrorKind, RuntimeErrorType};
+
+    fn runtime_codes() -> Vec<&'static str> {
+        let name = || "x".to_string();
+        [
+            RuntimeErrorType::UndefinedVariable(name()),
+            RuntimeErrorType::UndefinedFunction(name()),
+            RuntimeErrorType::InvalidOpcode,
+            RuntimeErrorType::InvalidOperands,
+            RuntimeErrorType::BooleanExpected,
+            RuntimeErrorType::WrongNumberOfArguments(name()),
+            RuntimeErrorType::MutationOfImmutable(name()),
+            RuntimeErrorType::IntegerOverflow,
+            RuntimeErrorType::NegativeExponent,
+            RuntimeErrorType::Forbidden(name()),
+            RuntimeErrorType::UndefinedStruct(name()),
+            RuntimeErrorType::UndefinedField(name()),
+            RuntimeErrorType::MissingField(name()),
+            RuntimeErrorType::IndexOutOfBounds,
+            RuntimeErrorType::NoMain,
+            RuntimeErrorType::Stopped,
+            RuntimeErrorType::AssertionFailed(name()),
+            RuntimeErrorType::UserError(name()),
+            RuntimeErrorType::NullDereference,
+            RuntimeErrorType::DivisionByZero,
+            RuntimeErrorType::RangeTooLong,
+        ]
+        .iter()
+        .map(RuntimeErrorType::code)
+        .collect()
+    }
+
+    fn lexer_codes() -> Vec<&'static str> {
+        [
+            LexerErrorKind::UnexpectedCharacter,
+            LexerErrorKind::UnterminatedBlockComment,
+            LexerErrorKind::UnknownEscape,
+            LexerErrorKind::InvalidCharLiteral,
+            LexerErrorKind::UnterminatedString,
+            LexerErrorKind::IntegerOverflow,
+            LexerErrorKind::InvalidIntegerLiteral,
+        ]
+        .iter()
+        .map(LexerErrorKind::code)
+        .collect()
+    }
+
+    #[test]
+    fn codes_are_unique_and_explained() {
+        let mut codes = runtime_codes();
+        codes.extend(lexer_codes());
+        codes.extend(&[
+            "E0102", "E0103", "E0104", "E0105", "E0106", "E0113", "E0114", "E0115",
+        ]);
+        for code in &codes {
+            assert!(explain(code).is_some(), "{} isn't explained", code);
+        }
+        let count = codes.len();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), count);
+        assert_eq!(codes.len(), EXPLANATIONS.len());
+        assert_eq!(explain("E9999"), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn parse_error_codes() {
+        use crate::parse;
+        let code = |input: &str| parse(input).unwrap_err().code();
+        assert_eq!(code("fn main() { a = 1 b = 2; a }"), "E0103");
+        assert_eq!(code("fn main() { } }"), "E0102");
+        assert_eq!(code("fn main() {"), "E0104");
+        assert_eq!(code("fn main() { 1 } ?"), "E0101");
+        assert_eq!(code("fn main() { a = 1 < 2 < 3; a }"), "E0113");
+    }
+
+    #[cfg(all(feature = "std", feature = "serde"))]
+    #[test]
+    fn serialized_codes() {
+        use crate::{execute, parse, Map};
+        let err = parse("fn main() { \"abc }").unwrap_err();
+        let json = serde_json::to_string(&err).unwrap();
+        assert!(json.contains("\"code\":\"E0110\""), "{}", json);
+
+        let program = parse("fn main() { a }").unwrap();
+        let err = execute(&program, &mut Map::new(), &mut Map::new()).unwrap_err();
+        let json = serde_json::to_string(&err).unwrap();
+        assert!(json.contains("\"code\":\"E0001\""), "{}", json);
+    }
+}
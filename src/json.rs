@@ -0,0 +1,123 @@
+use crate::ast::{DataType, VarVal};
+use core::convert::TryFrom;
+use serde_json::{Number, Value};
+
+impl VarVal {
+    /// Encode the value as JSON, null values and unit become `null`, characters strings
+    /// and structs and maps objects
+    pub fn to_json(&self) -> String {
+        to_value(self).to_string()
+    }
+
+    /// Decode a JSON number fitting into i32, boolean, string, `null` as unit or array
+    /// of elements of one type, `None` for other input like objects
+    pub fn from_json(s: &str) -> Option<VarVal> {
+        from_value(&serde_json::from_str(s).ok()?)
+    }
+}
+
+fn to_value(value: &VarVal) -> Value {
+    match value {
+        VarVal::I32(Some(v)) => Value::Number(Number::from(*v)),
+        VarVal::BOOL(Some(v)) => Value::Bool(*v),
+        VarVal::STRING(Some(v)) => Value::String(v.clone()),
+        VarVal::CHAR(Some(v)) => Value::String(v.to_string()),
+        VarVal::STRUCT(_, Some(fields)) => Value::Object(
+            fields
+                .iter()
+                .map(|(field, value)| (field.clone(), to_value(value)))
+                .collect(),
+        ),
+        VarVal::ARRAY(_, Some(elements)) => Value::Array(elements.iter().map(to_value).collect()),
+        VarVal::MAP(_, Some(entries)) => Value::Object(
+            entries
+                .iter()
+                .map(|(key, value)| (key.clone(), to_value(value)))
+                .collect(),
+        ),
+        _ => Value::Null,
+    }
+}
+
+fn from_value(value: &Value) -> Option<VarVal> {
+    match value {
+        Value::Null => Some(VarVal::UNIT),
+        Value::Bool(v) => Some(VarVal::BOOL(Some(*v))),
+        Value::Number(n) => {
+            let n = n.as_i64()?;
+            i32::try_from(n).ok().map(|n| VarVal::I32(Some(n)))
+        }
+        Value::String(v) => Some(VarVal::STRING(Some(v.clone()))),
+        Value::Array(values) => {
+            // elements must share a type like in array literals, `()` stands for the
+            // unknown type of an empty array
+            let elements = values.iter().map(from_value).collect::<Option<Vec<_>>>()?;
+            let element = elements.first().map_or(DataType::UNIT, VarVal::data_type);
+            if elements.iter().any(|e| e.data_type() != element) {
+                return None;
+            }
+            Some(VarVal::ARRAY(element, Some(elements)))
+        }
+        Value::Object(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Map;
+
+    fn round_trip(value: VarVal, json: &str) {
+        assert_eq!(value.to_json(), json);
+        assert_eq!(VarVal::from_json(json), Some(value));
+    }
+
+    #[test]
+    fn scalars() {
+        round_trip(VarVal::I32(Some(-42)), "-42");
+        round_trip(VarVal::BOOL(Some(true)), "true");
+        round_trip(VarVal::BOOL(Some(false)), "false");
+        round_trip(
+            VarVal::STRING(Some("a \"b\"\n\\ č".to_string())),
+            "\"a \\\"b\\\"\\n\\\\ č\"",
+        );
+        round_trip(VarVal::UNIT, "null");
+        assert_eq!(VarVal::CHAR(Some('x')).to_json(), "\"x\"");
+        assert_eq!(VarVal::from_json("2147483648"), None);
+        assert_eq!(VarVal::from_json("1.5"), None);
+    }
+
+    #[test]
+    fn nulls() {
+        assert_eq!(VarVal::I32(None).to_json(), "null");
+        assert_eq!(VarVal::STRING(None).to_json(), "null");
+        assert_eq!(VarVal::ARRAY(DataType::I32, None).to_json(), "null");
+    }
+
+    #[test]
+    fn arrays() {
+        let int = |v| VarVal::I32(Some(v));
+        let inner = |elements| VarVal::ARRAY(DataType::I32, Some(elements));
+        round_trip(inner(vec![int(1), int(2)]), "[1,2]");
+        round_trip(VarVal::ARRAY(DataType::UNIT, Some(Vec::new())), "[]");
+        round_trip(
+            VarVal::ARRAY(
+                DataType::ARRAY(Box::new(DataType::I32)),
+                Some(vec![inner(vec![int(1)]), inner(vec![int(2), int(3)])]),
+            ),
+            "[[1],[2,3]]",
+        );
+        assert_eq!(VarVal::from_json("[1, true]"), None);
+    }
+
+    #[test]
+    fn objects() {
+        assert_eq!(VarVal::from_json("{}"), None);
+        assert_eq!(VarVal::from_json("[{\"a\": 1}]"), None);
+        assert_eq!(VarVal::from_json("not json"), None);
+        let mut fields = Map::new();
+        fields.insert("x".to_string(), VarVal::I32(Some(1)));
+        let point = VarVal::STRUCT("Point".to_string(), Some(fields));
+        assert_eq!(point.to_json(), "{\"x\":1}");
+    }
+}
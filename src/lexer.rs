@@ -1,5 +1,10 @@
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use alloc::format;
+use core::fmt;
+use core::str::CharIndices;
+#[cfg(feature = "serde")]
 use serde::Serialize;
-use std::str::CharIndices;
 
 fn is_symbol(ch: char) -> bool {
     matches!(
@@ -9,13 +14,13 @@ fn is_symbol(ch: char) -> bool {
 }
 
 fn is_ident_start(ch: char) -> bool {
-    ch.is_ascii_alphabetic()
+    ch == '_' || ch.is_alphabetic()
 }
 
 fn is_ident_continue(ch: char) -> bool {
     match ch {
-        '0'..='9' | '_' => true,
-        ch => is_ident_start(ch),
+        '_' => true,
+        ch => ch.is_alphanumeric(),
     }
 }
 
@@ -23,46 +28,143 @@ fn is_dec_digit(ch: char) -> bool {
     ch.is_ascii_digit()
 }
 
+/// Kind of a lexer error
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum ErrorKind {
+    /// Character which doesn't start any token
+    UnexpectedCharacter,
+    /// Block comment without its closing `*/`
+    UnterminatedBlockComment,
+    /// Backslash in a string followed by a character without escape meaning
+    UnknownEscape,
+    /// Quote not followed by exactly one character and a closing quote
+    InvalidCharLiteral,
+    /// String literal without its closing quote
+    UnterminatedString,
+    /// Integer literal which doesn't fit into i32
+    IntegerOverflow,
+    /// Integer literal with a digit invalid for its base or a leading or trailing `_`
+    InvalidIntegerLiteral,
+}
+
 /// An error that occurred while lexing the source file
-#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct Error {
     /// The location where the lexer error occured
     pub location: usize,
     /// Unexpected character
     pub char: Option<char>,
+    pub kind: ErrorKind,
+    /// Name of the source set by `Lexer::with_source_name`
+    pub source_name: Option<String>,
+}
+
+impl ErrorKind {
+    /// Stable code of the error, see `explain`
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::UnexpectedCharacter => "E0101",
+            ErrorKind::UnterminatedBlockComment => "E0107",
+            ErrorKind::UnknownEscape => "E0108",
+            ErrorKind::InvalidCharLiteral => "E0109",
+            ErrorKind::UnterminatedString => "E0110",
+            ErrorKind::IntegerOverflow => "E0111",
+            ErrorKind::InvalidIntegerLiteral => "E0112",
+        }
+    }
+}
+
+impl Error {
+    /// Error of `kind` at `location`, the lexer sets the name of the source
+    pub fn new(location: usize, char: Option<char>, kind: ErrorKind) -> Self {
+        Error {
+            location,
+            char,
+            kind,
+            source_name: None,
+        }
+    }
+
+    /// Description of the error without its location
+    pub(crate) fn message(&self) -> String {
+        let ch = self.char.unwrap_or(' ');
+        match self.kind {
+            ErrorKind::UnexpectedCharacter => format!("Unexpected character {}", ch),
+            ErrorKind::UnterminatedBlockComment => "Unterminated block comment".to_string(),
+            ErrorKind::UnknownEscape => format!("Unknown escape sequence \\{}", ch),
+            ErrorKind::InvalidCharLiteral => "Invalid character literal".to_string(),
+            ErrorKind::UnterminatedString => "Unterminated string literal".to_string(),
+            ErrorKind::IntegerOverflow => "Integer literal doesn't fit into i32".to_string(),
+            ErrorKind::InvalidIntegerLiteral => "Invalid integer literal".to_string(),
+        }
+    }
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message(), self.location)?;
+        match &self.source_name {
+            Some(name) => write!(f, " in {}", name),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
 fn error<T>(location: usize, char: Option<char>) -> Result<T, Error> {
-    Err(Error { location, char })
+    Err(Error::new(location, char, ErrorKind::UnexpectedCharacter))
 }
 
 /// A token in the source file, to be emitted by the `Lexer`
-#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub enum Token<'input> {
     // Data
     Ident(&'input str),
     StringValue(&'input str),
-    DecLiteral(i32),
+    /// String literal containing escape sequences
+    StringOwned(String),
+    CharLiteral(char),
+    /// Line or block comment, only yielded by `Lexer::with_trivia`
+    Comment(&'input str),
+    /// Magnitude of an integer literal, up to 2^31 so that `-2147483648` can be written
+    DecLiteral(u32),
 
     // Keywords
     If,
     Else,
     Function,
+    Const,
+    Let,
+    Struct,
+    For,
+    In,
 
     // Data types
     I32,
     Boolean,
     String,
+    CharType,
 
     // true false
     True,
     False,
 
+    /// Wildcard `_`, identifiers may still start with an underscore
+    Underscore,
+
     // Symbols
     Bang,         // !
     BangEqual,    // !=
     Colon,        // :
     Comma,        // ,
+    Dot,          // .
+    DotDot,       // ..
+    DotDotEq,     // ..=
     Equal,        // =
     EqualEqual,   // ==
     ForwardSlash, // /
@@ -74,35 +176,234 @@ pub enum Token<'input> {
     Plus,         // +
     Semi,         // ;
     Star,         // *
+    StarStar,     // **
     Percent,      // %
     AmpAmp,       // &&
     PipePipe,     // ||
+    Arrow,        // ->
 
     // Delimiters
-    LParen, // (
-    RParen, // )
-    LBrace, // {
-    RBrace, // }
+    LParen,   // (
+    RParen,   // )
+    LBrace,   // {
+    RBrace,   // }
+    LBracket, // [
+    RBracket, // ]
+}
+
+impl Token<'_> {
+    /// Name of the token for error messages, symbols and keywords are quoted
+    pub fn name(&self) -> &'static str {
+        match self {
+            Token::Ident(_) => "identifier",
+            Token::StringValue(_) | Token::StringOwned(_) => "string literal",
+            Token::CharLiteral(_) => "character literal",
+            Token::Comment(_) => "comment",
+            Token::DecLiteral(_) => "integer literal",
+            Token::If => "'if'",
+            Token::Else => "'else'",
+            Token::Function => "'fn'",
+            Token::Const => "'const'",
+            Token::Let => "'let'",
+            Token::Struct => "'struct'",
+            Token::For => "'for'",
+            Token::In => "'in'",
+            Token::I32 => "'i32'",
+            Token::Boolean => "'bool'",
+            Token::String => "'String'",
+            Token::CharType => "'char'",
+            Token::True => "'true'",
+            Token::False => "'false'",
+            Token::Underscore => "'_'",
+            Token::Bang => "'!'",
+            Token::BangEqual => "'!='",
+            Token::Colon => "':'",
+            Token::Comma => "','",
+            Token::Dot => "'.'",
+            Token::DotDot => "'..'",
+            Token::DotDotEq => "'..='",
+            Token::Equal => "'='",
+            Token::EqualEqual => "'=='",
+            Token::ForwardSlash => "'/'",
+            Token::Greater => "'>'",
+            Token::GreaterEqual => "'>='",
+            Token::Less => "'<'",
+            Token::LessEqual => "'<='",
+            Token::Minus => "'-'",
+            Token::Plus => "'+'",
+            Token::Semi => "';'",
+            Token::Star => "'*'",
+            Token::StarStar => "'**'",
+            Token::Percent => "'%'",
+            Token::AmpAmp => "'&&'",
+            Token::PipePipe => "'||'",
+            Token::Arrow => "'->'",
+            Token::LParen => "'('",
+            Token::RParen => "')'",
+            Token::LBrace => "'{'",
+            Token::RBrace => "'}'",
+            Token::LBracket => "'['",
+            Token::RBracket => "']'",
+        }
+    }
 }
 
 /// An iterator over a source string that yeilds `Token`s for subsequent use by the parser
+///
+/// Token spans are byte offsets, `&src[start..end]` is exactly the source text of the token,
+/// including quotes of strings and characters
+///
+/// A line break inside braces is yielded as `Token::Semi` spanning the `'\n'` when the line
+/// ends a statement and the next line starts a new one, see `ends_statement` and
+/// `starts_statement`, so semicolons at line ends are optional
+///
+/// A clone continues from the same point independently, to backtrack to it later
+#[derive(Clone)]
 pub struct Lexer<'input> {
     src: &'input str,
     chars: CharIndices<'input>,
     lookahead: Option<(usize, char)>,
+    /// Yield comments as `Token::Comment` instead of skipping them
+    trivia: bool,
+    /// Comments skipped so far, see `take_comments`
+    comments: Vec<Comment<'input>>,
+    /// Position of the first line break since the last token
+    line_break: Option<usize>,
+    /// The last token can end a statement
+    after_statement: bool,
+    /// Token following an inserted semicolon
+    pending: Option<(usize, Token<'input>, usize)>,
+    /// Opening brackets which weren't closed yet
+    delimiters: Vec<char>,
+    /// Result of the next call of `next`, filled by `peek`
+    peeked: Option<Option<Spanned<'input>>>,
+    /// Name of the source put into the errors, see `with_source_name`
+    source_name: Option<String>,
+}
+
+/// Token with its start and end or the error, as yielded by `Lexer`
+type Spanned<'input> = Result<(usize, Token<'input>, usize), Error>;
+
+/// Token which may be the last one of a statement
+fn ends_statement(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Ident(_)
+            | Token::StringValue(_)
+            | Token::StringOwned(_)
+            | Token::CharLiteral(_)
+            | Token::DecLiteral(_)
+            | Token::True
+            | Token::False
+            | Token::I32
+            | Token::Boolean
+            | Token::String
+            | Token::CharType
+            | Token::RParen
+            | Token::RBrace
+            | Token::RBracket
+    )
+}
+
+/// Token starting a statement which can't continue the statement on the previous line,
+/// these can't follow the `ends_statement` tokens so a semicolon is never inserted
+/// into otherwise valid code
+pub(crate) fn starts_statement(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Ident(_)
+            | Token::StringValue(_)
+            | Token::StringOwned(_)
+            | Token::CharLiteral(_)
+            | Token::DecLiteral(_)
+            | Token::True
+            | Token::False
+            | Token::Underscore
+            | Token::Let
+            | Token::Const
+            | Token::If
+            | Token::For
+    )
+}
+
+/// Comment of a source file, `text` includes the comment markers
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Comment<'input> {
+    pub span: (usize, usize),
+    pub text: &'input str,
+}
+
+/// Collect all comments of `src` in order of appearance
+pub fn comments(src: &str) -> Result<Vec<Comment<'_>>, Error> {
+    let mut comments = Vec::new();
+    for token in Lexer::with_trivia(src) {
+        if let (start, Token::Comment(text), end) = token? {
+            comments.push(Comment {
+                span: (start, end),
+                text,
+            });
+        }
+    }
+    Ok(comments)
 }
 
 impl<'input> Lexer<'input> {
     pub fn new(src: &'input str) -> Self {
         let mut chars = src.char_indices();
+        if src.starts_with("#!") {
+            // skip shebang line of a script, `#` isn't valid anywhere else
+            for (_, ch) in &mut chars {
+                if ch == '\n' {
+                    break;
+                }
+            }
+        }
 
         Lexer {
             src,
             lookahead: chars.next(),
             chars,
+            trivia: false,
+            comments: Vec::new(),
+            line_break: None,
+            after_statement: false,
+            pending: None,
+            delimiters: Vec::new(),
+            peeked: None,
+            source_name: None,
+        }
+    }
+
+    /// Name the source, like the path of the file it was read from, errors of the lexer
+    /// then carry the name
+    pub fn with_source_name(self, name: impl Into<String>) -> Self {
+        Lexer {
+            source_name: Some(name.into()),
+            ..self
+        }
+    }
+
+    /// Name given by `with_source_name`
+    pub fn source_name(&self) -> Option<&str> {
+        self.source_name.as_deref()
+    }
+
+    /// Create a lexer which also yields comments, for tools working with the source text,
+    /// the parser doesn't accept them
+    pub fn with_trivia(src: &'input str) -> Self {
+        Lexer {
+            trivia: true,
+            ..Lexer::new(src)
         }
     }
 
+    /// Take the comments skipped so far in order of appearance, e.g. to find the doc
+    /// comments after parsing without lexing the source again
+    pub fn take_comments(&mut self) -> Vec<Comment<'input>> {
+        core::mem::take(&mut self.comments)
+    }
+
     /// Return the next character in the source string
     fn lookahead(&self) -> Option<(usize, char)> {
         self.lookahead
@@ -150,11 +451,176 @@ impl<'input> Lexer<'input> {
         (eof, self.slice(start, eof))
     }
 
-    /// Consume an string token
-    fn string(&mut self, start: usize) -> (usize, Token<'input>, usize) {
-        let (end, content) = self.take_until(start + 1, |ch| ch == '"'); // skip first '"'
-        self.bump(); // skip remaining '"'
-        (start, Token::StringValue(content), end + 1)
+    /// Skip a block comment starting at `start` after its opening `/`,
+    /// nested comments have to be closed as well
+    fn block_comment(&mut self, start: usize) -> Result<(), Error> {
+        self.bump(); // skip '*' of the opening "/*"
+        let mut depth = 1;
+        while let Some((_, ch)) = self.bump() {
+            match (ch, self.lookahead()) {
+                ('/', Some((_, '*'))) => {
+                    self.bump();
+                    depth += 1;
+                }
+                ('*', Some((_, '/'))) => {
+                    self.bump();
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                _ => (),
+            }
+        }
+        Err(Error::new(
+            start,
+            Some('/'),
+            ErrorKind::UnterminatedBlockComment,
+        ))
+    }
+
+    /// Consume the longest operator starting with `ch`, so that `=-` is `=` followed by `-`
+    fn symbol(&mut self, start: usize, ch: char) -> Result<(usize, Token<'input>, usize), Error> {
+        if let Some((next, next_ch)) = self.lookahead() {
+            let end = next + next_ch.len_utf8();
+            let token = match self.slice(start, end) {
+                "!=" => Some(Token::BangEqual),
+                "==" => Some(Token::EqualEqual),
+                ">=" => Some(Token::GreaterEqual),
+                "<=" => Some(Token::LessEqual),
+                "**" => Some(Token::StarStar),
+                "&&" => Some(Token::AmpAmp),
+                "||" => Some(Token::PipePipe),
+                "->" => Some(Token::Arrow),
+                ".." => Some(Token::DotDot),
+                _ => None,
+            };
+            if let Some(token) = token {
+                self.bump();
+                if token == Token::DotDot && self.lookahead().map(|(_, ch)| ch) == Some('=') {
+                    self.bump();
+                    return Ok((start, Token::DotDotEq, end + 1));
+                }
+                return Ok((start, token, end));
+            }
+        }
+        let end = start + 1;
+        match ch {
+            '!' => Ok((start, Token::Bang, end)),
+            ':' => Ok((start, Token::Colon, end)),
+            ',' => Ok((start, Token::Comma, end)),
+            '.' => Ok((start, Token::Dot, end)),
+            '=' => Ok((start, Token::Equal, end)),
+            '/' => Ok((start, Token::ForwardSlash, end)),
+            '>' => Ok((start, Token::Greater, end)),
+            '<' => Ok((start, Token::Less, end)),
+            '-' => Ok((start, Token::Minus, end)),
+            '+' => Ok((start, Token::Plus, end)),
+            ';' => Ok((start, Token::Semi, end)),
+            '*' => Ok((start, Token::Star, end)),
+            '%' => Ok((start, Token::Percent, end)),
+            ch => error(start, Some(ch)),
+        }
+    }
+
+    /// Consume an string token, the content is only copied if it contains escape sequences
+    fn string(&mut self, start: usize) -> Result<(usize, Token<'input>, usize), Error> {
+        let content_start = start + 1; // skip first '"'
+        let mut owned: Option<String> = None;
+        let content_end = loop {
+            let (position, ch) = match self.bump() {
+                Some(next) => next,
+                None => return Err(Error::new(start, Some('"'), ErrorKind::UnterminatedString)),
+            };
+            match ch {
+                '"' => break position,
+                '\\' => {
+                    let escaped = self.escape(position)?;
+                    owned
+                        .get_or_insert_with(|| self.slice(content_start, position).to_string())
+                        .push(escaped);
+                }
+                ch => {
+                    if let Some(owned) = &mut owned {
+                        owned.push(ch);
+                    }
+                }
+            }
+        };
+        let token = match owned {
+            Some(content) => Token::StringOwned(content),
+            None => Token::StringValue(self.slice(content_start, content_end)),
+        };
+        Ok((start, token, content_end + 1))
+    }
+
+    /// Consume a raw triple-quoted string, newlines and backslashes are kept as they are
+    fn multiline_string(&mut self, start: usize) -> Result<(usize, Token<'input>, usize), Error> {
+        let content_start = start + 3;
+        let end = match self.src[content_start..].find("\"\"\"") {
+            Some(length) => content_start + length,
+            None => return Err(Error::new(start, Some('"'), ErrorKind::UnterminatedString)),
+        };
+        while let Some((position, _)) = self.lookahead() {
+            if position >= end + 3 {
+                break;
+            }
+            self.bump();
+        }
+        let content = self.slice(content_start, end);
+        Ok((start, Token::StringValue(content), end + 3))
+    }
+
+    /// Consume a character literal like `'a'` or `'\n'`
+    fn char_literal(&mut self, start: usize) -> Result<(usize, Token<'input>, usize), Error> {
+        let value = match self.bump() {
+            Some((position, '\\')) => Some(self.escape(position)?),
+            Some((_, '\'')) | None => None,
+            Some((_, ch)) => Some(ch),
+        };
+        match (value, self.bump()) {
+            (Some(value), Some((end, '\''))) => Ok((start, Token::CharLiteral(value), end + 1)),
+            _ => Err(Error::new(start, Some('\''), ErrorKind::InvalidCharLiteral)),
+        }
+    }
+
+    /// Consume an escape sequence following the backslash at `position`
+    fn escape(&mut self, position: usize) -> Result<char, Error> {
+        let escaped = match self.bump() {
+            Some((_, 'n')) => '\n',
+            Some((_, 't')) => '\t',
+            Some((_, 'r')) => '\r',
+            Some((_, '0')) => '\0',
+            Some((_, '\\')) => '\\',
+            Some((_, '"')) => '"',
+            Some((_, '\'')) => '\'',
+            Some((_, 'u')) => return self.unicode_escape(position),
+            other => {
+                return Err(Error::new(
+                    position,
+                    other.map(|(_, ch)| ch),
+                    ErrorKind::UnknownEscape,
+                ))
+            }
+        };
+        Ok(escaped)
+    }
+
+    /// Consume the `{1F600}` part of an `\u{1F600}` escape
+    fn unicode_escape(&mut self, position: usize) -> Result<char, Error> {
+        let invalid = Error::new(position, Some('u'), ErrorKind::UnknownEscape);
+        if self.bump().map(|(_, ch)| ch) != Some('{') {
+            return Err(invalid);
+        }
+        let start = self.lookahead().map_or(self.src.len(), |(start, _)| start);
+        let (_, digits) = self.take_while(start, |ch| ch.is_ascii_hexdigit());
+        if self.bump().map(|(_, ch)| ch) != Some('}') || digits.is_empty() || digits.len() > 6 {
+            return Err(invalid);
+        }
+        u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(core::char::from_u32)
+            .ok_or(invalid)
     }
 
     /// Consume an identifier token
@@ -164,20 +630,49 @@ impl<'input> Lexer<'input> {
             "if" => Token::If,
             "else" => Token::Else,
             "fn" => Token::Function,
+            "const" => Token::Const,
+            "let" => Token::Let,
+            "struct" => Token::Struct,
+            "for" => Token::For,
+            "in" => Token::In,
             "i32" => Token::I32,
             "bool" => Token::Boolean,
             "String" => Token::String,
+            "char" => Token::CharType,
             "true" => Token::True,
             "false" => Token::False,
+            "_" => Token::Underscore,
             ident => Token::Ident(ident),
         };
         (start, token, end)
     }
-    /// Consume a decimal literal token
-    fn dec_literal(&mut self, start: usize) -> (usize, Token<'input>, usize) {
-        let (end, src) = self.take_while(start, is_dec_digit);
-        let int = src.parse::<i32>().unwrap();
-        (start, Token::DecLiteral(int), end)
+    /// Consume an integer literal token, decimal or with a `0x`, `0o` or `0b` prefix,
+    /// `_` can separate digits
+    fn int_literal(&mut self, start: usize) -> Result<(usize, Token<'input>, usize), Error> {
+        let (end, src) = self.take_while(start, |ch| ch.is_ascii_alphanumeric() || ch == '_');
+        let (radix, digits) = if let Some(digits) = src.strip_prefix("0x") {
+            (16, digits)
+        } else if let Some(digits) = src.strip_prefix("0o") {
+            (8, digits)
+        } else if let Some(digits) = src.strip_prefix("0b") {
+            (2, digits)
+        } else {
+            (10, src)
+        };
+        let error = |kind| Error::new(start, None, kind);
+        if digits.is_empty()
+            || digits.starts_with('_')
+            || digits.ends_with('_')
+            || !digits.chars().all(|ch| ch == '_' || ch.is_digit(radix))
+        {
+            return Err(error(ErrorKind::InvalidIntegerLiteral));
+        }
+        let digits: String = digits.chars().filter(|&ch| ch != '_').collect();
+        // only digits are left, so parsing fails just on overflow, however long the run is
+        match u32::from_str_radix(&digits, radix) {
+            Ok(int) if int <= i32::MIN.unsigned_abs() => Ok((start, Token::DecLiteral(int), end)),
+            _ => Err(error(ErrorKind::IntegerOverflow)),
+        }
     }
 }
 
@@ -185,47 +680,122 @@ impl<'input> Iterator for Lexer<'input> {
     type Item = Result<(usize, Token<'input>, usize), Error>;
 
     fn next(&mut self) -> Option<Result<(usize, Token<'input>, usize), Error>> {
+        match self.peeked.take() {
+            Some(token) => token,
+            None => self.advance(),
+        }
+    }
+}
+
+impl<'input> Lexer<'input> {
+    /// Return the token the next call of `next` returns, without consuming it
+    pub fn peek(&mut self) -> Option<Result<(usize, Token<'input>, usize), Error>> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.advance());
+        }
+        self.peeked.clone().flatten()
+    }
+
+    fn advance(&mut self) -> Option<Result<(usize, Token<'input>, usize), Error>> {
+        match self.next_token()? {
+            Err(e) if self.source_name.is_some() => Some(Err(Error {
+                source_name: self.source_name.clone(),
+                ..e
+            })),
+            token => Some(token),
+        }
+    }
+
+    fn next_token(&mut self) -> Option<Result<(usize, Token<'input>, usize), Error>> {
+        let token = match self.pending.take() {
+            Some(token) => token,
+            None => match self.token()? {
+                Ok(token) => token,
+                Err(e) => return Some(Err(e)),
+            },
+        };
+        if let Token::Comment(_) = token.1 {
+            return Some(Ok(token));
+        }
+        if let Some(position) = self.line_break.take() {
+            if self.after_statement
+                && starts_statement(&token.1)
+                && self.delimiters.last() == Some(&'{')
+            {
+                self.after_statement = false;
+                self.pending = Some(token);
+                return Some(Ok((position, Token::Semi, position + 1)));
+            }
+        }
+        match token.1 {
+            Token::LParen => self.delimiters.push('('),
+            Token::LBrace => self.delimiters.push('{'),
+            Token::LBracket => self.delimiters.push('['),
+            Token::RParen | Token::RBrace | Token::RBracket => {
+                self.delimiters.pop();
+            }
+            _ => (),
+        }
+        self.after_statement = ends_statement(&token.1);
+        Some(Ok(token))
+    }
+}
+
+impl<'input> Lexer<'input> {
+    /// Consume the next token, ignoring line breaks
+    fn token(&mut self) -> Option<Result<(usize, Token<'input>, usize), Error>> {
         while let Some((start, ch)) = self.bump() {
             let end = start + 1;
 
             return Some(match ch {
-                ch if is_symbol(ch) => {
-                    let (end, symbol) = self.take_while(start, is_symbol);
-
-                    match symbol {
-                        "!" => Ok((start, Token::Bang, end)),
-                        "!=" => Ok((start, Token::BangEqual, end)),
-                        ":" => Ok((start, Token::Colon, end)),
-                        "," => Ok((start, Token::Comma, end)),
-                        "=" => Ok((start, Token::Equal, end)),
-                        "==" => Ok((start, Token::EqualEqual, end)),
-                        "/" => Ok((start, Token::ForwardSlash, end)),
-                        ">" => Ok((start, Token::Greater, end)),
-                        ">=" => Ok((start, Token::GreaterEqual, end)),
-                        "<" => Ok((start, Token::Less, end)),
-                        "<=" => Ok((start, Token::LessEqual, end)),
-                        "-" => Ok((start, Token::Minus, end)),
-                        "+" => Ok((start, Token::Plus, end)),
-                        ";" => Ok((start, Token::Semi, end)),
-                        "*" => Ok((start, Token::Star, end)),
-                        "%" => Ok((start, Token::Percent, end)),
-                        "&&" => Ok((start, Token::AmpAmp, end)),
-                        "||" => Ok((start, Token::PipePipe, end)),
-                        symbol if symbol.starts_with("//") => {
-                            // Line comments
-                            self.take_until(start, |ch| ch == '\n');
-                            continue;
+                '/' if self.lookahead().map(|(_, ch)| ch) == Some('*') => {
+                    match self.block_comment(start) {
+                        Ok(()) => {
+                            let end = self.lookahead().map_or(self.src.len(), |(end, _)| end);
+                            let text = self.slice(start, end);
+                            if !self.trivia {
+                                self.comments.push(Comment {
+                                    span: (start, end),
+                                    text,
+                                });
+                                continue;
+                            }
+                            Ok((start, Token::Comment(text), end))
                         }
-                        s => error(start, s.chars().nth(0)),
+                        Err(e) => Err(e),
+                    }
+                }
+                '/' if self.lookahead().map(|(_, ch)| ch) == Some('/') => {
+                    // Line comments
+                    let (_, text) = self.take_until(start, |ch| ch == '\n');
+                    // the carriage return of a CRLF line ending isn't part of the comment
+                    let text = text.strip_suffix('\r').unwrap_or(text);
+                    let end = start + text.len();
+                    if !self.trivia {
+                        self.comments.push(Comment {
+                            span: (start, end),
+                            text,
+                        });
+                        continue;
                     }
+                    Ok((start, Token::Comment(text), end))
                 }
+                ch if is_symbol(ch) => self.symbol(start, ch),
                 '(' => Ok((start, Token::LParen, end)),
                 ')' => Ok((start, Token::RParen, end)),
                 '{' => Ok((start, Token::LBrace, end)),
                 '}' => Ok((start, Token::RBrace, end)),
-                '"' => Ok(self.string(start)),
-                ch if is_dec_digit(ch) => Ok(self.dec_literal(start)),
+                '[' => Ok((start, Token::LBracket, end)),
+                ']' => Ok((start, Token::RBracket, end)),
+                '"' if self.src[start..].starts_with("\"\"\"") => self.multiline_string(start),
+                '"' => self.string(start),
+                '\'' => self.char_literal(start),
+                ch if is_dec_digit(ch) => self.int_literal(start),
                 ch if is_ident_start(ch) => Ok(self.ident(start)),
+                '\n' => {
+                    self.line_break.get_or_insert(start);
+                    continue;
+                }
                 ch if ch.is_whitespace() => continue,
                 ch => error(start, Some(ch)),
             });
@@ -255,6 +825,69 @@ mod test {
         assert_eq!(tokens, vec![(0, Token::DecLiteral(123), input.len())]);
     }
 
+    #[test]
+    fn dec_literal_overflow() {
+        let err = lex("1 2147483649").unwrap_err();
+        assert_eq!(err.location, 2);
+        assert_eq!(err.kind, ErrorKind::IntegerOverflow);
+        assert_eq!(
+            lex("002147483647").unwrap(),
+            vec![Token::DecLiteral(2147483647)]
+        );
+    }
+
+    #[test]
+    fn int_literal_separators() {
+        use Token::DecLiteral;
+        assert_eq!(
+            lex("1_000 1__0 0xFF_FF 0b1111_0000 0x7fffffff 0").unwrap(),
+            vec![
+                DecLiteral(1000),
+                DecLiteral(10),
+                DecLiteral(0xFFFF),
+                DecLiteral(0b1111_0000),
+                DecLiteral(i32::MAX as u32),
+                DecLiteral(0)
+            ]
+        );
+        let tokens: Vec<_> = Lexer::new("x 0b1_0;").collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens[1], (2, DecLiteral(2), 7));
+        for input in &["5_", "x 0x_FF", "0xFF_", "0b102", "0x", "12ab"] {
+            let err = lex(input).unwrap_err();
+            assert_eq!(err.location, input.find(is_dec_digit).unwrap());
+            assert_eq!(err.kind, ErrorKind::InvalidIntegerLiteral);
+        }
+        assert_eq!(
+            lex("0x1_0000_0000").unwrap_err().kind,
+            ErrorKind::IntegerOverflow
+        );
+        assert_eq!(lex("_5").unwrap(), vec![Token::Ident("_5")]);
+    }
+
+    #[test]
+    fn octal_literal() {
+        use Token::DecLiteral;
+        assert_eq!(
+            lex("0o755 0o0 0o17777777777 0o20000000000").unwrap(),
+            vec![
+                DecLiteral(0o755),
+                DecLiteral(0),
+                DecLiteral(i32::MAX as u32),
+                DecLiteral(i32::MIN.unsigned_abs())
+            ]
+        );
+        assert_eq!(
+            lex("0o40000000000").unwrap_err().kind,
+            ErrorKind::IntegerOverflow
+        );
+        for input in &["0o8", "0o79", "0o"] {
+            assert_eq!(
+                lex(input).unwrap_err().kind,
+                ErrorKind::InvalidIntegerLiteral
+            );
+        }
+    }
+
     #[test]
     fn comments_lexer() {
         let input = "// some text 123";
@@ -262,6 +895,36 @@ mod test {
         assert_eq!(tokens, vec![]);
     }
 
+    #[test]
+    fn crlf_comments_lexer() {
+        use Token::*;
+        let input = "// comment\r\nx = 1; // end\r\n/* a\r\nb */ y\r\n//\r\n";
+        let tokens: Vec<_> = Lexer::new(input).collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (12, Ident("x"), 13),
+                (14, Equal, 15),
+                (16, DecLiteral(1), 17),
+                (17, Semi, 18),
+                (38, Ident("y"), 39),
+            ]
+        );
+        // a line break hidden behind a comment still ends the statement
+        assert_eq!(
+            lex("{ x = 1 // one\r\n y }").unwrap(),
+            vec![
+                LBrace,
+                Ident("x"),
+                Equal,
+                DecLiteral(1),
+                Semi,
+                Ident("y"),
+                RBrace
+            ]
+        );
+    }
+
     #[test]
     fn ident_lexer() {
         let input = "super_duper_variable1";
@@ -286,7 +949,7 @@ mod test {
 
     #[test]
     fn symbol_lexer() {
-        let input = "!  !=  : , = == / > >= < <= - + ; % && ||";
+        let input = "!  !=  : , = == / > >= < <= - + ; % && || * **";
         let tokens: Vec<_> = Lexer::new(input)
             .map(|e| match e {
                 Ok((_, v, _)) => v,
@@ -313,6 +976,8 @@ mod test {
                 Token::Percent,
                 Token::AmpAmp,
                 Token::PipePipe,
+                Token::Star,
+                Token::StarStar,
             ]
         );
     }
@@ -343,4 +1008,626 @@ mod test {
             ]
         );
     }
+
+    fn lex(input: &str) -> Result<Vec<Token<'_>>, Error> {
+        Lexer::new(input).map(|t| t.map(|(_, v, _)| v)).collect()
+    }
+
+    #[test]
+    fn block_comments_lexer() {
+        assert_eq!(lex("/**/").unwrap(), vec![]);
+        assert_eq!(
+            lex("1 /* // */ 2").unwrap(),
+            vec![Token::DecLiteral(1), Token::DecLiteral(2)]
+        );
+        assert_eq!(
+            lex("a /* a /* b */ c */ b").unwrap(),
+            vec![Token::Ident("a"), Token::Ident("b")]
+        );
+        assert_eq!(lex("x/*/ */").unwrap(), vec![Token::Ident("x")]);
+    }
+
+    #[test]
+    fn unterminated_block_comment_lexer() {
+        let err = lex("a /* /* */").unwrap_err();
+        assert_eq!(err.location, 2);
+        assert_eq!(err.kind, ErrorKind::UnterminatedBlockComment);
+    }
+
+    #[test]
+    fn longest_symbol_lexer() {
+        assert_eq!(
+            lex("x=-1").unwrap(),
+            vec![
+                Token::Ident("x"),
+                Token::Equal,
+                Token::Minus,
+                Token::DecLiteral(1)
+            ]
+        );
+        assert_eq!(lex("!==").unwrap(), vec![Token::BangEqual, Token::Equal]);
+        assert_eq!(lex("+-").unwrap(), vec![Token::Plus, Token::Minus]);
+        assert_eq!(lex("***").unwrap(), vec![Token::StarStar, Token::Star]);
+        assert_eq!(lex("a &").unwrap_err().location, 2);
+    }
+
+    #[test]
+    fn adjacent_symbols_lexer() {
+        use Token::*;
+        assert_eq!(
+            lex("a<=-1").unwrap(),
+            vec![Ident("a"), LessEqual, Minus, DecLiteral(1)]
+        );
+        assert_eq!(
+            lex("b==-1").unwrap(),
+            vec![Ident("b"), EqualEqual, Minus, DecLiteral(1)]
+        );
+        assert_eq!(
+            lex("x!=-y").unwrap(),
+            vec![Ident("x"), BangEqual, Minus, Ident("y")]
+        );
+        assert_eq!(
+            lex("1+-2").unwrap(),
+            vec![DecLiteral(1), Plus, Minus, DecLiteral(2)]
+        );
+        assert_eq!(lex(";;").unwrap(), vec![Semi, Semi]);
+        assert_eq!(
+            lex("f();-3").unwrap(),
+            vec![Ident("f"), LParen, RParen, Semi, Minus, DecLiteral(3)]
+        );
+        assert_eq!(
+            lex("x=*p").unwrap(),
+            vec![Ident("x"), Equal, Star, Ident("p")]
+        );
+        assert_eq!(lex("a=// b").unwrap(), vec![Ident("a"), Equal]);
+    }
+
+    #[test]
+    fn bracket_lexer() {
+        assert_eq!(
+            lex("[1, 2]").unwrap(),
+            vec![
+                Token::LBracket,
+                Token::DecLiteral(1),
+                Token::Comma,
+                Token::DecLiteral(2),
+                Token::RBracket
+            ]
+        );
+    }
+
+    #[test]
+    fn arrow_lexer() {
+        assert_eq!(
+            lex("-> - > ->-").unwrap(),
+            vec![
+                Token::Arrow,
+                Token::Minus,
+                Token::Greater,
+                Token::Arrow,
+                Token::Minus
+            ]
+        );
+    }
+
+    #[test]
+    fn range_lexer() {
+        use Token::*;
+        let tokens: Vec<_> = Lexer::new("0..10").collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (0, DecLiteral(0), 1),
+                (1, DotDot, 3),
+                (3, DecLiteral(10), 5)
+            ]
+        );
+        let tokens: Vec<_> = Lexer::new("0..=10").collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (0, DecLiteral(0), 1),
+                (1, DotDotEq, 4),
+                (4, DecLiteral(10), 6)
+            ]
+        );
+        assert_eq!(lex("1.5").unwrap(), vec![DecLiteral(1), Dot, DecLiteral(5)]);
+        assert_eq!(lex("...").unwrap(), vec![DotDot, Dot]);
+        assert_eq!(lex("..==").unwrap(), vec![DotDotEq, Equal]);
+    }
+
+    #[test]
+    fn underscore_lexer() {
+        assert_eq!(lex("_").unwrap(), vec![Token::Underscore]);
+        assert_eq!(
+            lex("_ _x __ _0").unwrap(),
+            vec![
+                Token::Underscore,
+                Token::Ident("_x"),
+                Token::Ident("__"),
+                Token::Ident("_0")
+            ]
+        );
+        assert_eq!(lex("_,").unwrap(), vec![Token::Underscore, Token::Comma]);
+        assert_eq!(
+            lex("_unused=__internal").unwrap(),
+            vec![
+                Token::Ident("_unused"),
+                Token::Equal,
+                Token::Ident("__internal")
+            ]
+        );
+    }
+
+    #[test]
+    fn peek() {
+        let mut lexer = Lexer::new("{ a\n b }");
+        assert_eq!(lexer.peek(), Some(Ok((0, Token::LBrace, 1))));
+        assert_eq!(lexer.peek(), Some(Ok((0, Token::LBrace, 1))));
+        assert_eq!(lexer.next(), Some(Ok((0, Token::LBrace, 1))));
+        assert_eq!(lexer.next(), Some(Ok((2, Token::Ident("a"), 3))));
+        // the inserted semicolon and the token after it
+        assert_eq!(lexer.peek(), Some(Ok((3, Token::Semi, 4))));
+        assert_eq!(lexer.next(), Some(Ok((3, Token::Semi, 4))));
+        assert_eq!(lexer.peek(), Some(Ok((5, Token::Ident("b"), 6))));
+        assert_eq!(lexer.next(), Some(Ok((5, Token::Ident("b"), 6))));
+        assert_eq!(lexer.next(), Some(Ok((7, Token::RBrace, 8))));
+        assert_eq!(lexer.peek(), None);
+        assert_eq!(lexer.next(), None);
+
+        let mut lexer = Lexer::new("$");
+        let err = lexer.peek().unwrap().unwrap_err();
+        assert_eq!(lexer.next(), Some(Err(err)));
+    }
+
+    #[test]
+    fn clone_backtrack() {
+        let mut lexer = Lexer::new("{ a\n b }");
+        lexer.next();
+        lexer.next();
+        lexer.peek();
+        let saved = lexer.clone();
+        let rest: Vec<_> = lexer.map(|t| t.unwrap().1).collect();
+        assert_eq!(rest, [Token::Semi, Token::Ident("b"), Token::RBrace]);
+
+        let mut first = saved.clone();
+        let mut second = saved;
+        assert_eq!(first.next(), Some(Ok((3, Token::Semi, 4))));
+        assert_eq!(first.next(), Some(Ok((5, Token::Ident("b"), 6))));
+        assert_eq!(second.next(), Some(Ok((3, Token::Semi, 4))));
+        assert_eq!(first.next(), Some(Ok((7, Token::RBrace, 8))));
+        assert_eq!(second.next(), Some(Ok((5, Token::Ident("b"), 6))));
+        assert_eq!(first.next(), None);
+        assert_eq!(second.next(), Some(Ok((7, Token::RBrace, 8))));
+    }
+
+    #[test]
+    fn source_name() {
+        let mut lexer = Lexer::new("a $ b").with_source_name("main.pra");
+        assert_eq!(lexer.next(), Some(Ok((0, Token::Ident("a"), 1))));
+        let err = lexer.peek().unwrap().unwrap_err();
+        assert_eq!(err.source_name.as_deref(), Some("main.pra"));
+        assert_eq!(lexer.next(), Some(Err(err.clone())));
+        assert_eq!(
+            err.to_string(),
+            "Unexpected character $ at byte 2 in main.pra"
+        );
+        let err = Lexer::new("a $").find_map(Result::err).unwrap();
+        assert_eq!(err.source_name, None);
+    }
+
+    #[test]
+    fn comments_trivia() {
+        let input = "// main function\nfn main() {\n    x = 1; // one\n    /* two */ x\n} // end";
+        let found = comments(input).unwrap();
+        let texts: Vec<_> = found.iter().map(|c| c.text).collect();
+        assert_eq!(
+            texts,
+            vec!["// main function", "// one", "/* two */", "// end"]
+        );
+        for comment in &found {
+            assert_eq!(&input[comment.span.0..comment.span.1], comment.text);
+        }
+        assert_eq!(found[3].span.1, input.len());
+
+        let tokens = lex(input).unwrap();
+        assert!(!tokens.iter().any(|t| matches!(t, Token::Comment(_))));
+        assert_eq!(tokens.len(), 11);
+
+        // the skipped comments are the same
+        let mut lexer = Lexer::new(input);
+        assert_eq!(lexer.by_ref().count(), 11);
+        assert_eq!(lexer.take_comments(), found);
+        assert!(lexer.take_comments().is_empty());
+        let crlf = "a // one\r\nb";
+        let mut lexer = Lexer::new(crlf);
+        lexer.by_ref().for_each(drop);
+        assert_eq!(lexer.take_comments(), comments(crlf).unwrap());
+    }
+
+    fn spans(input: &str) -> Vec<&str> {
+        Lexer::with_trivia(input)
+            .map(|t| t.map(|(start, _, end)| &input[start..end]))
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn exact_spans() {
+        assert_eq!(
+            spans("é = \"čaj\\n\"; 'ž' // ó"),
+            vec!["é", "=", "\"čaj\\n\"", ";", "'ž'", "// ó"]
+        );
+        assert_eq!(
+            spans("fn main() {\r\n    x = 1; // one\r\n    \"\"\"a\r\nb\"\"\"\r\n}\r\n"),
+            vec![
+                "fn",
+                "main",
+                "(",
+                ")",
+                "{",
+                "x",
+                "=",
+                "1",
+                ";",
+                "// one",
+                "\"\"\"a\r\nb\"\"\"",
+                "}"
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_string_lexer() {
+        for input in &[
+            "x \"unterminated",
+            "x = \"abc\n  y = 2;\n",
+            "\"a\\\"",
+            "x \"\\u{41}",
+        ] {
+            let err = lex(input).unwrap_err();
+            assert_eq!(err.location, input.find('"').unwrap());
+            assert_eq!(err.char, Some('"'));
+            assert_eq!(err.kind, ErrorKind::UnterminatedString);
+        }
+    }
+
+    #[test]
+    fn shebang_lexer() {
+        let script = "#!/usr/bin/env pra\n1 + 2";
+        let tokens: Vec<_> = Lexer::new(script).collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (19, Token::DecLiteral(1), 20),
+                (21, Token::Plus, 22),
+                (23, Token::DecLiteral(2), 24)
+            ]
+        );
+        assert_eq!(lex("#!pra").unwrap(), vec![]);
+        assert_eq!(lex(" #!pra\n1").unwrap_err().location, 1);
+        assert_eq!(lex("1\n#!pra").unwrap_err().location, 2);
+    }
+
+    #[test]
+    fn line_break_semicolons() {
+        use Token::*;
+        let tokens: Vec<_> = Lexer::new("{ x = 1\n  f(a,\n b)\n}")
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (0, LBrace, 1),
+                (2, Ident("x"), 3),
+                (4, Equal, 5),
+                (6, DecLiteral(1), 7),
+                (7, Semi, 8),
+                (10, Ident("f"), 11),
+                (11, LParen, 12),
+                (12, Ident("a"), 13),
+                (13, Comma, 14),
+                (16, Ident("b"), 17),
+                (17, RParen, 18),
+                (19, RBrace, 20),
+            ]
+        );
+        assert_eq!(
+            lex("{ 1 +\n 2\n - 3;\n x\n}\nfn").unwrap(),
+            vec![
+                LBrace,
+                DecLiteral(1),
+                Plus,
+                DecLiteral(2),
+                Minus,
+                DecLiteral(3),
+                Semi,
+                Ident("x"),
+                RBrace,
+                Function
+            ]
+        );
+        assert_eq!(
+            lex("{ }\nelse { x // a\n /* b */ y }").unwrap(),
+            vec![
+                LBrace,
+                RBrace,
+                Else,
+                LBrace,
+                Ident("x"),
+                Semi,
+                Ident("y"),
+                RBrace
+            ]
+        );
+    }
+
+    #[test]
+    fn struct_lexer() {
+        assert_eq!(
+            lex("struct p.x").unwrap(),
+            vec![
+                Token::Struct,
+                Token::Ident("p"),
+                Token::Dot,
+                Token::Ident("x")
+            ]
+        );
+    }
+
+    #[test]
+    fn string_escapes_lexer() {
+        let escaped = |s: &str| Token::StringOwned(s.to_string());
+        assert_eq!(lex(r#""a\nb""#).unwrap(), vec![escaped("a\nb")]);
+        assert_eq!(lex(r#""\t""#).unwrap(), vec![escaped("\t")]);
+        assert_eq!(lex(r#""\r""#).unwrap(), vec![escaped("\r")]);
+        assert_eq!(lex(r#""\0""#).unwrap(), vec![escaped("\0")]);
+        assert_eq!(
+            lex(r#""\\" x"#).unwrap(),
+            vec![escaped("\\"), Token::Ident("x")]
+        );
+        assert_eq!(lex(r#""say \"hi\"""#).unwrap(), vec![escaped("say \"hi\"")]);
+        let input = r#""\n" 1"#;
+        let tokens: Vec<_> = Lexer::new(input).collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens[0], (0, escaped("\n"), 4));
+    }
+
+    #[test]
+    fn unknown_escape_lexer() {
+        let err = lex(r#"x "ab\q""#).unwrap_err();
+        assert_eq!(err.location, 5);
+        assert_eq!(err.char, Some('q'));
+        assert_eq!(err.kind, ErrorKind::UnknownEscape);
+    }
+
+    #[test]
+    fn unicode_escape_lexer() {
+        let escaped = |s: &str| Token::StringOwned(s.to_string());
+        assert_eq!(lex(r#""\u{0041}""#).unwrap(), vec![escaped("A")]);
+        assert_eq!(
+            lex(r#""smile \u{1F600}!""#).unwrap(),
+            vec![escaped("smile \u{1F600}!")]
+        );
+        assert_eq!(lex(r#""\u{10FFFF}""#).unwrap(), vec![escaped("\u{10FFFF}")]);
+        for input in &[r#""\u{D800}""#, r#"x "a\u{200000}""#] {
+            let err = lex(input).unwrap_err();
+            assert_eq!(err.location, input.find('\\').unwrap());
+            assert_eq!(err.kind, ErrorKind::UnknownEscape);
+        }
+    }
+
+    #[test]
+    fn unicode_ident_lexer() {
+        assert_eq!(
+            lex("café naïve_2 名前 if ifé").unwrap(),
+            vec![
+                Token::Ident("café"),
+                Token::Ident("naïve_2"),
+                Token::Ident("名前"),
+                Token::If,
+                Token::Ident("ifé"),
+            ]
+        );
+        assert_eq!(
+            lex("délka Ελλάδα строка").unwrap(),
+            vec![
+                Token::Ident("délka"),
+                Token::Ident("Ελλάδα"),
+                Token::Ident("строка"),
+            ]
+        );
+        assert_eq!(
+            lex("Ω ωμέγα 中文 x中2_y").unwrap(),
+            vec![
+                Token::Ident("Ω"),
+                Token::Ident("ωμέγα"),
+                Token::Ident("中文"),
+                Token::Ident("x中2_y"),
+            ]
+        );
+        let tokens: Vec<_> = Lexer::new("中文 = Ω").collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (0, Token::Ident("中文"), 6),
+                (7, Token::Equal, 8),
+                (9, Token::Ident("Ω"), 11)
+            ]
+        );
+        let tokens: Vec<_> = Lexer::new("é=1").collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens[0], (0, Token::Ident("é"), 2));
+        assert_eq!(tokens[1], (2, Token::Equal, 3));
+    }
+
+    #[test]
+    fn char_lexer() {
+        assert_eq!(
+            lex("'a' 'é' char").unwrap(),
+            vec![
+                Token::CharLiteral('a'),
+                Token::CharLiteral('é'),
+                Token::CharType
+            ]
+        );
+        assert_eq!(
+            lex(r"'\n' '\'' '\\' '\u{1F600}' '\u{41}'").unwrap(),
+            vec![
+                Token::CharLiteral('\n'),
+                Token::CharLiteral('\''),
+                Token::CharLiteral('\\'),
+                Token::CharLiteral('\u{1F600}'),
+                Token::CharLiteral('A'),
+            ]
+        );
+        assert_eq!(
+            lex(r#""\u{48}i""#).unwrap(),
+            vec![Token::StringOwned("Hi".to_string())]
+        );
+        let tokens: Vec<_> = Lexer::new("'é'").collect::<Result<_, _>>().unwrap();
+        assert_eq!(tokens, vec![(0, Token::CharLiteral('é'), 4)]);
+    }
+
+    #[test]
+    fn invalid_char_lexer() {
+        for input in &["''", "'ab'", "'a"] {
+            assert_eq!(lex(input).unwrap_err().kind, ErrorKind::InvalidCharLiteral);
+        }
+        for input in &[r"'\u{D800}'", r"'\u{}'", r"'\u41'", r"'\u{1234567}'"] {
+            assert_eq!(lex(input).unwrap_err().kind, ErrorKind::UnknownEscape);
+        }
+    }
+
+    #[test]
+    fn multiline_string_lexer() {
+        assert_eq!(lex(r#""""""""#).unwrap(), vec![Token::StringValue("")]);
+        let input = "x \"\"\"first\n  second\\n\n\"\"\" y";
+        let tokens: Vec<_> = Lexer::new(input).collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                (0, Token::Ident("x"), 1),
+                (2, Token::StringValue("first\n  second\\n\n"), 25),
+                (26, Token::Ident("y"), 27),
+            ]
+        );
+        assert_eq!(
+            lex(r##""""say "hi", it's ""fine""""##).unwrap(),
+            vec![Token::StringValue(r#"say "hi", it's ""fine"#)]
+        );
+        assert_eq!(
+            lex(r#""" """#).unwrap(),
+            vec![Token::StringValue(""), Token::StringValue("")]
+        );
+    }
+
+    #[test]
+    fn unterminated_multiline_string_lexer() {
+        let err = lex("a \"\"\"text\n\"\"").unwrap_err();
+        assert_eq!(err.location, 2);
+        assert_eq!(err.kind, ErrorKind::UnterminatedString);
+    }
+
+    /// Source of a string literal with value `s`, quotes and backslashes are escaped and so
+    /// are line breaks and other control characters
+    #[cfg(feature = "std")]
+    fn string_literal(s: &str) -> String {
+        let mut literal = String::from("\"");
+        for ch in s.chars() {
+            match ch {
+                '"' | '\\' => literal.extend(&['\\', ch]),
+                '\n' => literal.push_str("\\n"),
+                ch if ch.is_control() => literal.push_str(&format!("\\u{{{:x}}}", ch as u32)),
+                ch => literal.push(ch),
+            }
+        }
+        literal.push('"');
+        literal
+    }
+
+    #[cfg(feature = "std")]
+    const KEYWORDS: &[&str] = &[
+        "if", "else", "fn", "const", "let", "struct", "for", "in", "i32", "bool", "String", "char",
+        "true", "false", "_",
+    ];
+
+    #[cfg(feature = "std")]
+    proptest::proptest! {
+        #[test]
+        fn single_identifier(
+            before in "[ \t\r\n]{0,3}",
+            ident in "[a-zA-Z_][a-zA-Z0-9_]{0,15}",
+            after in "[ \t\r\n]{0,3}",
+        ) {
+            proptest::prop_assume!(!KEYWORDS.contains(&ident.as_str()));
+            let input = format!("{}{}{}", before, ident, after);
+            proptest::prop_assert_eq!(lex(&input).unwrap(), vec![Token::Ident(&ident)]);
+        }
+
+        #[test]
+        fn single_integer(
+            before in "[ \t\r\n]{0,3}",
+            n in proptest::num::i32::ANY,
+            after in "[ \t\r\n]{0,3}",
+        ) {
+            let input = format!("{}{}{}", before, n, after);
+            let mut expected = Vec::new();
+            if n < 0 {
+                expected.push(Token::Minus);
+            }
+            expected.push(Token::DecLiteral(n.unsigned_abs()));
+            proptest::prop_assert_eq!(lex(&input).unwrap(), expected);
+        }
+
+        #[test]
+        fn single_string(
+            before in "[ \t\r\n]{0,3}",
+            value in proptest::prelude::any::<String>(),
+            after in "[ \t\r\n]{0,3}",
+        ) {
+            let input = format!("{}{}{}", before, string_literal(&value), after);
+            let tokens = lex(&input).unwrap();
+            proptest::prop_assert_eq!(tokens.len(), 1);
+            let lexed = match &tokens[0] {
+                Token::StringValue(s) => s.to_string(),
+                Token::StringOwned(s) => s.clone(),
+                token => panic!("{:?} isn't a string", token),
+            };
+            proptest::prop_assert_eq!(lexed, value);
+        }
+    }
+
+    #[test]
+    fn symbol_pairs() {
+        let symbols = "|&%!:,.=/><-+;*(){}[]";
+        let pairs = [
+            ("!=", Token::BangEqual),
+            ("==", Token::EqualEqual),
+            (">=", Token::GreaterEqual),
+            ("<=", Token::LessEqual),
+            ("**", Token::StarStar),
+            ("&&", Token::AmpAmp),
+            ("||", Token::PipePipe),
+            ("->", Token::Arrow),
+            ("..", Token::DotDot),
+        ];
+        for a in symbols.chars() {
+            for b in symbols.chars() {
+                let input = format!("{}{}", a, b);
+                // comments
+                if input == "//" || input == "/*" {
+                    continue;
+                }
+                match pairs.iter().find(|(pair, _)| *pair == input) {
+                    Some((_, token)) => assert_eq!(lex(&input).unwrap(), vec![token.clone()]),
+                    None => match (lex(&a.to_string()), lex(&b.to_string())) {
+                        (Ok(mut first), Ok(second)) => {
+                            first.extend(second);
+                            assert_eq!(lex(&input).unwrap(), first, "{}", input);
+                        }
+                        _ => assert!(lex(&input).is_err(), "{}", input),
+                    },
+                }
+            }
+        }
+    }
 }
@@ -1,23 +1,120 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
 pub mod ast;
+#[cfg(feature = "async")]
+mod async_execute;
+#[cfg(feature = "std")]
+pub mod buildin;
+pub mod check;
+#[cfg(feature = "std")]
+pub mod codegen_c;
+pub mod coverage;
+mod diagnostic;
+pub mod diff;
+mod explain;
+#[cfg(all(feature = "std", feature = "serde"))]
+mod json;
 mod lexer;
+pub mod line_index;
+mod ops;
+mod pretty;
+#[cfg(feature = "std")]
+pub mod profile;
+mod render;
+pub mod typecheck;
 
+use alloc::vec;
+#[cfg(feature = "std")]
+use ast::Item;
 pub use ast::{
-    ArgList, Block, Else, Expr, ExprType, Function, If, Opcode, Program, Stmt, VarVal, Variable,
+    ArgList, Block, DataType, Else, Expr, ExprType, Function, Functions, If, Opcode, Program,
+    Statement, Stmt, StructDef, VarVal, Variable, Visitor,
 };
+#[cfg(feature = "async")]
+pub use async_execute::{blocking_buildin, call_builtin_async, execute_async, AsyncBuildin};
+#[cfg(feature = "std")]
+pub use buildin::{default_buildins, default_buildins_for, SandboxPolicy};
+pub use check::{buildin_arity, check, check_program, CheckError};
+use core::convert::TryFrom;
+use core::fmt;
+pub use coverage::Coverage;
+pub use diagnostic::{Diagnostic, Severity};
+pub use diff::{ast_diff, Difference};
+pub use explain::explain;
+#[cfg(feature = "std")]
 use lalrpop_util::{lalrpop_mod, ParseError};
-pub use lexer::{Error as LexerError, Lexer, Token};
+pub use lexer::{
+    comments, Comment, Error as LexerError, ErrorKind as LexerErrorKind, Lexer, Token,
+};
+pub use line_index::LineIndex;
+#[cfg(feature = "std")]
+pub use pretty::format_source;
+pub use pretty::to_source;
+#[cfg(feature = "std")]
+pub use profile::{FunctionProfile, Profile};
+pub use render::{render_diagnostic, render_parse_error, render_runtime_error};
+#[cfg(feature = "serde")]
 use serde::Serialize;
-use std::collections::HashMap;
-use std::fmt;
+pub use typecheck::{check_types, TypeError};
+
+/// Map and set used by the interpreter, `BTreeMap` and `BTreeSet` without `std`
+#[cfg(not(feature = "std"))]
+pub use alloc::collections::{BTreeMap as Map, BTreeSet as Set};
+/// Map and set used by the interpreter, `HashMap` and `HashSet` with `std`
+#[cfg(feature = "std")]
+pub use std::collections::{HashMap as Map, HashSet as Set};
 
+#[cfg(not(feature = "std"))]
+use prelude::*;
+#[cfg(not(feature = "std"))]
+mod prelude {
+    pub use alloc::boxed::Box;
+    pub use alloc::string::{String, ToString};
+    pub use alloc::vec::Vec;
+}
+
+#[cfg(feature = "std")]
 lalrpop_mod!(#[allow(clippy::all, warnings)] pub parser); // synthesized by LALRPOP
 
-#[derive(Debug, Serialize)]
+#[derive(Debug)]
 pub struct RuntimeError {
     pub position: usize,
+    /// End of the span of the node which failed, at least one byte after `position`
+    pub end: usize,
     pub error_type: RuntimeErrorType,
+    /// 1-based line of `position`, known only when executed with `execute_with_source`
+    pub line: Option<usize>,
+    /// 1-based column of `position` in characters
+    pub column: Option<usize>,
+}
+
+impl RuntimeError {
+    /// Stable code of the error, see `explain`
+    pub fn code(&self) -> &'static str {
+        self.error_type.code()
+    }
 }
-#[derive(Debug, Serialize)]
+
+// the code is serialized after the fields so tools don't need the table of codes
+#[cfg(feature = "serde")]
+impl Serialize for RuntimeError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("RuntimeError", 6)?;
+        state.serialize_field("position", &self.position)?;
+        state.serialize_field("end", &self.end)?;
+        state.serialize_field("error_type", &self.error_type)?;
+        state.serialize_field("line", &self.line)?;
+        state.serialize_field("column", &self.column)?;
+        state.serialize_field("code", self.code())?;
+        state.end()
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub enum RuntimeErrorType {
     UndefinedVariable(String),
     UndefinedFunction(String),
@@ -25,7 +122,57 @@ pub enum RuntimeErrorType {
     InvalidOperands,
     BooleanExpected,
     WrongNumberOfArguments(String),
+    MutationOfImmutable(String),
+    IntegerOverflow,
+    NegativeExponent,
+    Forbidden(String),
+    UndefinedStruct(String),
+    UndefinedField(String),
+    MissingField(String),
+    /// String index or range outside of the string
+    IndexOutOfBounds,
     NoMain,
+    /// Execution was stopped by the debug hook
+    Stopped,
+    /// Condition of `assert` was false or values of `assert_eq` differ
+    AssertionFailed(String),
+    /// Error raised by the program itself with the `error` builtin
+    UserError(String),
+    /// Operand of an operator is null, e.g. a variable declared by `let` and not assigned
+    NullDereference,
+    /// Right operand of `/` or `%` is zero
+    DivisionByZero,
+    /// Range with more than `MAX_RANGE_LEN` elements
+    RangeTooLong,
+}
+
+impl RuntimeErrorType {
+    /// Stable code of the error, see `explain`
+    pub fn code(&self) -> &'static str {
+        match self {
+            RuntimeErrorType::UndefinedVariable(_) => "E0001",
+            RuntimeErrorType::UndefinedFunction(_) => "E0002",
+            RuntimeErrorType::InvalidOpcode => "E0003",
+            RuntimeErrorType::InvalidOperands => "E0004",
+            RuntimeErrorType::BooleanExpected => "E0005",
+            RuntimeErrorType::WrongNumberOfArguments(_) => "E0006",
+            RuntimeErrorType::MutationOfImmutable(_) => "E0007",
+            RuntimeErrorType::IntegerOverflow => "E0008",
+            RuntimeErrorType::NegativeExponent => "E0009",
+            RuntimeErrorType::Forbidden(_) => "E0010",
+            RuntimeErrorType::UndefinedStruct(_) => "E0011",
+            RuntimeErrorType::UndefinedField(_) => "E0012",
+            RuntimeErrorType::MissingField(_) => "E0013",
+            RuntimeErrorType::IndexOutOfBounds => "E0014",
+            RuntimeErrorType::NoMain => "E0015",
+            RuntimeErrorType::Stopped => "E0016",
+            RuntimeErrorType::AssertionFailed(_) => "E0017",
+            RuntimeErrorType::UserError(_) => "E0018",
+            RuntimeErrorType::NullDereference => "E0019",
+            RuntimeErrorType::DivisionByZero => "E0020",
+            RuntimeErrorType::RangeTooLong => "E0021",
+        }
+    }
 }
 
 impl fmt::Display for RuntimeErrorType {
@@ -36,249 +183,2208 @@ impl fmt::Display for RuntimeErrorType {
             RuntimeErrorType::WrongNumberOfArguments(name) => {
                 write!(f, "Wrong number of arguments {}", name)
             }
+            RuntimeErrorType::MutationOfImmutable(name) => {
+                write!(f, "Cannot assign to immutable variable {}", name)
+            }
+            RuntimeErrorType::UndefinedStruct(name) => write!(f, "Undefined struct {}", name),
+            RuntimeErrorType::UndefinedField(name) => write!(f, "Undefined field {}", name),
+            RuntimeErrorType::MissingField(name) => write!(f, "Missing field {}", name),
+            RuntimeErrorType::IndexOutOfBounds => write!(f, "Index out of bounds"),
+            RuntimeErrorType::Forbidden(name) => write!(f, "Use of builtin {} is forbidden", name),
+            RuntimeErrorType::IntegerOverflow => write!(f, "Integer overflow"),
+            RuntimeErrorType::NegativeExponent => write!(f, "Negative exponent"),
             RuntimeErrorType::InvalidOperands => write!(f, "Invalid operands"),
             RuntimeErrorType::InvalidOpcode => write!(f, "Invalid opcode"),
             RuntimeErrorType::BooleanExpected => write!(f, "Expected Boolean value"),
             RuntimeErrorType::NoMain => write!(f, "Function main was't found"),
+            RuntimeErrorType::Stopped => write!(f, "Execution stopped by debugger"),
+            RuntimeErrorType::AssertionFailed(message) => {
+                write!(f, "Assertion failed: {}", message)
+            }
+            RuntimeErrorType::UserError(message) => write!(f, "{}", message),
+            RuntimeErrorType::NullDereference => write!(f, "Null value used as an operand"),
+            RuntimeErrorType::DivisionByZero => write!(f, "Division by zero"),
+            RuntimeErrorType::RangeTooLong => {
+                write!(f, "Range has more than {} elements", MAX_RANGE_LEN)
+            }
         }
     }
 }
 
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error[{}]: {}", self.code(), self.error_type)?;
+        write_location(f, self.position, self.line, self.column)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RuntimeError {}
+
+/// Write ` at line:column`, or the byte offset when the line isn't known
+fn write_location(
+    f: &mut fmt::Formatter<'_>,
+    offset: usize,
+    line: Option<usize>,
+    column: Option<usize>,
+) -> fmt::Result {
+    match (line, column) {
+        (Some(line), Some(column)) => write!(f, " at {}:{}", line, column),
+        _ => write!(f, " at byte {}", offset),
+    }
+}
+
 fn error(error_type: RuntimeErrorType, position: usize) -> RuntimeError {
+    error_in(error_type, (position, position + 1))
+}
+
+/// Error concerning the whole span `(position, end)` of a node
+fn error_in(error_type: RuntimeErrorType, (position, end): (usize, usize)) -> RuntimeError {
     RuntimeError {
         error_type,
         position,
+        end: end.max(position + 1),
+        line: None,
+        column: None,
     }
 }
 
-pub type Buildins<'a> = HashMap<String, Box<dyn FnMut(ArgList) -> VarVal + 'a>>;
+/// Element type of an array or map literal: the first element type all elements fit, so
+/// an empty literal takes the type of its siblings, `()` when there are no elements.
+/// Otherwise the index of the first element not fitting the type of the first one.
+fn element_type<'v>(
+    mut values: impl Iterator<Item = &'v VarVal> + Clone,
+) -> Result<DataType, usize> {
+    let mut tried = Vec::new();
+    for ty in values.clone().map(VarVal::data_type) {
+        if !tried.contains(&ty) && values.clone().all(|v| v.fits(&ty)) {
+            return Ok(ty);
+        }
+        tried.push(ty);
+    }
+    match tried.first() {
+        Some(first) => Err(values.position(|v| !v.fits(first)).unwrap_or(0)),
+        None => Ok(DataType::UNIT),
+    }
+}
 
-fn eval(
-    expr: &Expr,
-    globals: &mut HashMap<String, Variable>,
-    program: &Program,
-    locals: &mut HashMap<String, Variable>,
-    buildins: &mut Buildins,
-) -> Result<VarVal, RuntimeError> {
-    match &expr.expression_type {
-        ExprType::Function(name, expr_list) => {
-            let arglist = ArgList {
-                args: expr_list
-                    .iter()
-                    .map(|expr| eval(expr, globals, program, locals, buildins))
-                    .collect::<Result<_, _>>()?,
-            };
-            if let Some(f) = buildins.get_mut(name) {
-                Ok(f(arglist))
-            } else {
-                match program.functions.get(name) {
-                    Some(f) => eval_function(f, arglist, globals, program, buildins),
-                    None => Err(error(
-                        RuntimeErrorType::UndefinedFunction(name.clone()),
-                        expr.position,
-                    )),
+/// Builtin functions callable by name, an error is reported at the position of the call
+pub type Buildins<'a> =
+    Map<String, Box<dyn FnMut(ArgList) -> Result<VarVal, RuntimeErrorType> + Send + 'a>>;
+
+// programs are shared and builtins are moved between threads
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    fn assert_send<T: Send>() {}
+    assert_send_sync::<Program>();
+    assert_send::<Buildins<'static>>();
+    assert_send::<RuntimeError>();
+};
+
+/// Local variables of a function call, together with names bound by `const`
+#[derive(Default)]
+struct Scope {
+    variables: Map<String, Variable>,
+    /// Changes to undo at the end of each block being evaluated, innermost last
+    blocks: Vec<Vec<Undo>>,
+}
+
+/// Reverts a binding made inside a block once the block ends
+enum Undo {
+    /// Drop the variable introduced by the block
+    Remove(String),
+    /// Bring back the variable hidden by a declaration in the block
+    Restore(Variable),
+}
+
+impl Scope {
+    fn enter_block(&mut self) {
+        self.blocks.push(Vec::new());
+    }
+
+    fn leave_block(&mut self) {
+        for undo in self.blocks.pop().unwrap_or_default().into_iter().rev() {
+            match undo {
+                Undo::Remove(id) => {
+                    self.variables.remove(&id);
+                }
+                Undo::Restore(variable) => {
+                    self.variables.insert(variable.ident.clone(), variable);
                 }
             }
         }
-        ExprType::Value(n) => Ok(n.clone()),
-        ExprType::Op(lhs, opc, rhs) => {
-            let l = eval(lhs, globals, program, locals, buildins)?;
-            let r = eval(rhs, globals, program, locals, buildins)?;
-            if let (VarVal::I32(Some(l)), VarVal::I32(Some(r))) = (&l, &r) {
-                match opc {
-                    Opcode::Add => Ok(VarVal::I32(Some(l + r))),
-                    Opcode::Sub => Ok(VarVal::I32(Some(l - r))),
-                    Opcode::Mul => Ok(VarVal::I32(Some(l * r))),
-                    Opcode::Div => Ok(VarVal::I32(Some(l / r))),
-                    Opcode::Mod => Ok(VarVal::I32(Some(l % r))),
-                    Opcode::Eq => Ok(VarVal::BOOL(Some(l == r))),
-                    Opcode::Ne => Ok(VarVal::BOOL(Some(l != r))),
-                    Opcode::Lt => Ok(VarVal::BOOL(Some(l < r))),
-                    Opcode::Le => Ok(VarVal::BOOL(Some(l <= r))),
-                    Opcode::Gt => Ok(VarVal::BOOL(Some(l > r))),
-                    Opcode::Ge => Ok(VarVal::BOOL(Some(l >= r))),
-                    _ => Err(error(RuntimeErrorType::InvalidOpcode, expr.position)),
+    }
+
+    /// Assign to the variable, one not defined yet belongs to the innermost block
+    fn assign(&mut self, variable: Variable) {
+        if !self.variables.contains_key(&variable.ident) {
+            self.record(Undo::Remove(variable.ident.clone()));
+        }
+        self.variables.insert(variable.ident.clone(), variable);
+    }
+
+    /// Define the variable in the innermost block, hiding one of the same name until it ends
+    fn declare(&mut self, variable: Variable) {
+        let undo = match self.variables.get(&variable.ident) {
+            Some(hidden) => Undo::Restore(hidden.clone()),
+            None => Undo::Remove(variable.ident.clone()),
+        };
+        self.record(undo);
+        self.variables.insert(variable.ident.clone(), variable);
+    }
+
+    /// Test if the variable exists and was bound by `const`
+    fn is_immutable(&self, id: &str) -> bool {
+        self.variables.get(id).is_some_and(|v| !v.is_mutable)
+    }
+
+    fn record(&mut self, undo: Undo) {
+        if let Some(block) = self.blocks.last_mut() {
+            block.push(undo);
+        }
+    }
+}
+
+/// Answer of a `DebugHook` whether the execution should go on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookAction {
+    Continue,
+    /// Abort the execution with `RuntimeErrorType::Stopped`
+    Stop,
+}
+
+/// Callback invoked before each statement with its source span and the local variables,
+/// the expression ending a block counts as a statement too
+pub type DebugHook<'a> =
+    Box<dyn FnMut((usize, usize), &Map<String, Variable>) -> HookAction + Send + 'a>;
+
+/// Tree-walking evaluator of a parsed `Program`
+pub struct Interpreter<'a, 'b> {
+    program: &'a Program,
+    globals: &'a mut Map<String, Variable>,
+    buildins: &'a mut Buildins<'b>,
+    coverage: Option<Coverage>,
+    #[cfg(feature = "std")]
+    profile: Option<Profile>,
+    hook: Option<DebugHook<'a>>,
+    breakpoints: Option<Set<usize>>,
+    /// Arguments `main` is called with
+    args: Vec<VarVal>,
+}
+
+impl<'a, 'b> Interpreter<'a, 'b> {
+    pub fn new(
+        program: &'a Program,
+        globals: &'a mut Map<String, Variable>,
+        buildins: &'a mut Buildins<'b>,
+    ) -> Self {
+        Interpreter {
+            program,
+            globals,
+            buildins,
+            coverage: None,
+            #[cfg(feature = "std")]
+            profile: None,
+            hook: None,
+            breakpoints: None,
+            args: Vec::new(),
+        }
+    }
+
+    /// Call `hook` before evaluating each statement, e.g. to step through the program
+    pub fn with_debug_hook(mut self, hook: DebugHook<'a>) -> Self {
+        self.hook = Some(hook);
+        self
+    }
+
+    /// Call the debug hook only for statements whose span contains one of the byte offsets
+    /// in `breakpoints` instead of before every statement
+    pub fn with_breakpoints(mut self, breakpoints: Set<usize>) -> Self {
+        self.breakpoints = Some(breakpoints);
+        self
+    }
+
+    /// Call `main` with `args`, e.g. the command line arguments, instead of no arguments
+    pub fn with_args(mut self, args: Vec<VarVal>) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Record which expressions get evaluated, see `coverage()`
+    pub fn with_coverage(mut self) -> Self {
+        self.coverage = Some(Coverage::new(self.program));
+        self
+    }
+
+    /// Coverage collected so far, if enabled with `with_coverage()`
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Count calls of functions and time spent in them, see `profile()`
+    #[cfg(feature = "std")]
+    pub fn with_profiling(mut self) -> Self {
+        self.profile = Some(Profile::default());
+        self
+    }
+
+    /// Statistics of function calls so far, if enabled with `with_profiling()`
+    #[cfg(feature = "std")]
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Execute function `main` of the program
+    pub fn run(&mut self) -> Result<VarVal, RuntimeError> {
+        let program = self.program;
+        if let Some(main) = program.functions.get("main") {
+            let args = self.args.clone();
+            self.eval_function(main, ArgList { args })
+        } else {
+            Err(error(RuntimeErrorType::NoMain, 0))
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr, locals: &mut Scope) -> Result<VarVal, RuntimeError> {
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(expr);
+        }
+        match &expr.expression_type {
+            ExprType::Function(name, expr_list) => {
+                let arglist = ArgList {
+                    args: expr_list
+                        .iter()
+                        .map(|expr| self.eval(expr, locals))
+                        .collect::<Result<_, _>>()?,
+                };
+                if let Some(f) = self.buildins.get_mut(name) {
+                    f(arglist).map_err(|e| error_in(e, (expr.position, expr.end)))
+                } else {
+                    let program = self.program;
+                    match program.functions.get(name) {
+                        Some(f) => self.eval_function(f, arglist),
+                        None => Err(error(
+                            RuntimeErrorType::UndefinedFunction(name.clone()),
+                            expr.position,
+                        )),
+                    }
                 }
-            } else if let (VarVal::BOOL(Some(l)), VarVal::BOOL(Some(r))) = (&l, &r) {
-                match opc {
-                    Opcode::Eq => Ok(VarVal::BOOL(Some(l == r))),
-                    Opcode::Ne => Ok(VarVal::BOOL(Some(l != r))),
-                    Opcode::And => Ok(VarVal::BOOL(Some(*l && *r))),
-                    Opcode::Or => Ok(VarVal::BOOL(Some(*l || *r))),
-                    _ => Err(error(RuntimeErrorType::InvalidOpcode, expr.position)),
+            }
+            ExprType::Value(n) => Ok(n.clone()),
+            ExprType::Op(lhs, opc, rhs) => {
+                // walk down the left operands in a loop, generated code may have chains
+                // like `1 + 1 + ... + 1` long enough to overflow the stack by recursion
+                let mut chain = vec![(expr, opc, rhs)];
+                let mut first = lhs;
+                while let ExprType::Op(lhs, opc, rhs) = &first.expression_type {
+                    if let Some(coverage) = &mut self.coverage {
+                        coverage.record(first);
+                    }
+                    chain.push((first, opc, rhs));
+                    first = lhs;
                 }
-            } else if let (VarVal::STRING(Some(l)), VarVal::STRING(Some(r))) = (&l, &r) {
-                match opc {
-                    Opcode::Eq => Ok(VarVal::BOOL(Some(l == r))),
-                    Opcode::Ne => Ok(VarVal::BOOL(Some(l != r))),
-                    _ => Err(error(RuntimeErrorType::InvalidOpcode, expr.position)),
+                let mut value = self.eval(first, locals)?;
+                for (op_expr, opc, rhs) in chain.into_iter().rev() {
+                    let r = self.eval(rhs, locals)?;
+                    value = value
+                        .apply_op(opc, &r)
+                        .map_err(|e| error_in(e, (op_expr.position, op_expr.end)))?;
                 }
-            } else {
-                Err(error(RuntimeErrorType::InvalidOperands, expr.position))
+                Ok(value)
             }
-        }
-        ExprType::Var(id) => globals
-            .get(id)
-            .map(Ok)
-            .unwrap_or_else(|| {
-                locals.get(id).map_or_else(
-                    || {
-                        Err(error(
-                            RuntimeErrorType::UndefinedVariable(id.clone()),
-                            expr.position,
+            // locals shadow globals of the same name
+            ExprType::Var(id) => locals
+                .variables
+                .get(id)
+                .or_else(|| self.globals.get(id))
+                .map(|v| v.value.clone())
+                .ok_or_else(|| {
+                    error(
+                        RuntimeErrorType::UndefinedVariable(id.clone()),
+                        expr.position,
+                    )
+                }),
+            ExprType::If(if_expr) => self.eval_if(if_expr, locals),
+            ExprType::ForIn(id, iter, block) => {
+                let elements = match self.eval(iter, locals)? {
+                    VarVal::ARRAY(_, Some(elements)) => elements,
+                    VarVal::ARRAY(_, None) => {
+                        return Err(error_in(
+                            RuntimeErrorType::NullDereference,
+                            (iter.position, iter.end),
                         ))
-                    },
-                    Ok,
-                )
-            })
-            .map(|v| v.value.clone()),
-        ExprType::If(if_expr) => {
-            eval_if(if_expr, globals, program, locals, buildins, expr.position)
+                    }
+                    _ => {
+                        return Err(error_in(
+                            RuntimeErrorType::InvalidOperands,
+                            (iter.position, iter.end),
+                        ))
+                    }
+                };
+                for element in elements {
+                    // the element is bound in the block of the body so it is dropped with
+                    // the variables declared there
+                    locals.enter_block();
+                    locals.declare(Variable {
+                        ident: id.clone(),
+                        value: element,
+                        is_mutable: true,
+                    });
+                    let res = self.eval_statements(block, locals);
+                    locals.leave_block();
+                    res?;
+                }
+                Ok(VarVal::UNIT)
+            }
+            ExprType::Struct(name, field_exprs) => {
+                let program = self.program;
+                let def = program.structs.get(name).ok_or_else(|| {
+                    error(
+                        RuntimeErrorType::UndefinedStruct(name.clone()),
+                        expr.position,
+                    )
+                })?;
+                let mut fields = Map::new();
+                for (field, value_expr) in field_exprs {
+                    let ty = match def.fields.iter().find(|f| &f.ident == field) {
+                        Some(f) => f.value.data_type(),
+                        None => {
+                            return Err(error(
+                                RuntimeErrorType::UndefinedField(field.clone()),
+                                value_expr.position,
+                            ))
+                        }
+                    };
+                    let value = self.eval(value_expr, locals)?;
+                    if !value.fits(&ty) {
+                        return Err(error_in(
+                            RuntimeErrorType::InvalidOperands,
+                            (value_expr.position, value_expr.end),
+                        ));
+                    }
+                    fields.insert(field.clone(), value.into_type(&ty));
+                }
+                if let Some(missing) = def.fields.iter().find(|f| !fields.contains_key(&f.ident)) {
+                    return Err(error(
+                        RuntimeErrorType::MissingField(missing.ident.clone()),
+                        expr.position,
+                    ));
+                }
+                Ok(VarVal::STRUCT(name.clone(), Some(fields)))
+            }
+            ExprType::Field(value, field) => match self.eval(value, locals)? {
+                VarVal::STRUCT(_, Some(mut fields)) => fields.remove(field).ok_or_else(|| {
+                    error(
+                        RuntimeErrorType::UndefinedField(field.clone()),
+                        expr.position,
+                    )
+                }),
+                _ => Err(error_in(
+                    RuntimeErrorType::InvalidOperands,
+                    (expr.position, expr.end),
+                )),
+            },
+            ExprType::Index(value, index) => {
+                match (self.eval(value, locals)?, self.eval(index, locals)?) {
+                    (VarVal::STRING(Some(s)), VarVal::I32(Some(i))) => usize::try_from(i)
+                        .ok()
+                        .and_then(|i| s.chars().nth(i))
+                        .map(|ch| VarVal::CHAR(Some(ch)))
+                        .ok_or_else(|| {
+                            error_in(
+                                RuntimeErrorType::IndexOutOfBounds,
+                                (index.position, index.end),
+                            )
+                        }),
+                    (VarVal::STRING(_), VarVal::I32(_)) => Ok(VarVal::CHAR(None)),
+                    (VarVal::ARRAY(_, Some(mut elements)), VarVal::I32(Some(i))) => {
+                        match usize::try_from(i) {
+                            Ok(i) if i < elements.len() => Ok(elements.swap_remove(i)),
+                            _ => Err(error_in(
+                                RuntimeErrorType::IndexOutOfBounds,
+                                (index.position, index.end),
+                            )),
+                        }
+                    }
+                    (VarVal::ARRAY(element, _), VarVal::I32(_)) => Ok(element.null_value()),
+                    // a missing key gives null like an absent value of a null map
+                    (VarVal::MAP(value, Some(mut entries)), VarVal::STRING(Some(key))) => {
+                        Ok(entries.remove(&key).unwrap_or_else(|| value.null_value()))
+                    }
+                    (VarVal::MAP(value, _), VarVal::STRING(_)) => Ok(value.null_value()),
+                    _ => Err(error_in(
+                        RuntimeErrorType::InvalidOperands,
+                        (expr.position, expr.end),
+                    )),
+                }
+            }
+            ExprType::Array(element_exprs) => {
+                let mut elements = Vec::new();
+                for value_expr in element_exprs {
+                    elements.push(self.eval(value_expr, locals)?);
+                }
+                let element = element_type(elements.iter()).map_err(|i| {
+                    error(RuntimeErrorType::InvalidOperands, element_exprs[i].position)
+                })?;
+                let elements = elements
+                    .into_iter()
+                    .map(|e| e.into_type(&element))
+                    .collect();
+                Ok(VarVal::ARRAY(element, Some(elements)))
+            }
+            ExprType::Map(entry_exprs) => {
+                // like in arrays all values have one type
+                let mut entries = Vec::new();
+                for (key_expr, value_expr) in entry_exprs {
+                    let key = match self.eval(key_expr, locals)? {
+                        VarVal::STRING(Some(key)) => key,
+                        VarVal::STRING(None) => {
+                            return Err(error(RuntimeErrorType::NullDereference, key_expr.position))
+                        }
+                        _ => {
+                            return Err(error(RuntimeErrorType::InvalidOperands, key_expr.position))
+                        }
+                    };
+                    entries.push((key, self.eval(value_expr, locals)?));
+                }
+                let value_type = element_type(entries.iter().map(|(_, v)| v)).map_err(|i| {
+                    error(RuntimeErrorType::InvalidOperands, entry_exprs[i].1.position)
+                })?;
+                let entries = entries
+                    .into_iter()
+                    .map(|(k, v)| (k, v.into_type(&value_type)))
+                    .collect();
+                Ok(VarVal::MAP(value_type, Some(entries)))
+            }
         }
     }
-}
 
-fn eval_if(
-    if_expr: &If,
-    globals: &mut HashMap<String, Variable>,
-    program: &Program,
-    locals: &mut HashMap<String, Variable>,
-    buildins: &mut Buildins,
-    position: usize,
-) -> Result<VarVal, RuntimeError> {
-    let predicate = eval(&if_expr.condition, globals, program, locals, buildins)?;
-    match predicate {
-        VarVal::BOOL(Some(v)) => {
-            if v {
-                eval_block(&if_expr.if_block, globals, program, locals, buildins)
-            } else {
-                match &if_expr.else_part {
-                    Else::Else(block) => eval_block(block, globals, program, locals, buildins),
-                    Else::ElseIf(next_if) => {
-                        eval_if(next_if, globals, program, locals, buildins, position)
+    fn eval_if(&mut self, if_expr: &If, locals: &mut Scope) -> Result<VarVal, RuntimeError> {
+        let condition = &if_expr.condition;
+        let predicate = self.eval(condition, locals)?;
+        match predicate {
+            VarVal::BOOL(Some(v)) => {
+                if v {
+                    self.eval_block(&if_expr.if_block, locals)
+                } else {
+                    match &if_expr.else_part {
+                        Else::Else(block) => self.eval_block(block, locals),
+                        Else::ElseIf(next_if) => self.eval_if(next_if, locals),
+                        Else::None => Ok(VarVal::UNIT),
                     }
-                    Else::None => Ok(VarVal::UNIT),
                 }
             }
+            _ => Err(error_in(
+                RuntimeErrorType::BooleanExpected,
+                (condition.position, condition.end),
+            )),
         }
-        _ => Err(error(RuntimeErrorType::BooleanExpected, position)),
     }
-}
 
-fn eval_block(
-    block: &Block,
-    globals: &mut HashMap<String, Variable>,
-    program: &Program,
-    locals: &mut HashMap<String, Variable>,
-    buildins: &mut Buildins,
-) -> Result<VarVal, RuntimeError> {
-    for stmt in &block.statements {
-        match stmt {
-            Stmt::Expr(expr) => {
-                eval(expr, globals, program, locals, buildins)?;
+    /// Let the debug hook decide whether to evaluate statement at `span`
+    fn step(&mut self, span: (usize, usize), locals: &Scope) -> Result<(), RuntimeError> {
+        if let Some(breakpoints) = &self.breakpoints {
+            let (start, end) = (span.0, span.1.max(span.0 + 1));
+            if !breakpoints.iter().any(|b| (start..end).contains(b)) {
+                return Ok(());
             }
-            Stmt::Asgn(id, expr) => {
-                let res = eval(expr, globals, program, locals, buildins)?;
-                locals.insert(
-                    id.to_string(),
-                    Variable {
+        }
+        let action = match &mut self.hook {
+            Some(hook) => hook(span, &locals.variables),
+            None => HookAction::Continue,
+        };
+        match action {
+            HookAction::Continue => Ok(()),
+            HookAction::Stop => Err(error_in(RuntimeErrorType::Stopped, span)),
+        }
+    }
+
+    /// Evaluate the block, variables defined inside it are gone once it ends
+    fn eval_block(&mut self, block: &Block, locals: &mut Scope) -> Result<VarVal, RuntimeError> {
+        locals.enter_block();
+        let res = self.eval_statements(block, locals);
+        locals.leave_block();
+        res
+    }
+
+    fn eval_statements(
+        &mut self,
+        block: &Block,
+        locals: &mut Scope,
+    ) -> Result<VarVal, RuntimeError> {
+        for statement in &block.statements {
+            let span = (statement.position, statement.end);
+            if self.hook.is_some() {
+                self.step(span, locals)?;
+            }
+            let stmt = &statement.stmt;
+            match stmt {
+                Stmt::Expr(expr) => {
+                    self.eval(expr, locals)?;
+                }
+                Stmt::Asgn(id, expr) | Stmt::Const(id, expr) | Stmt::Let(id, expr) => {
+                    // `let` binds a new variable, so it may hide a constant
+                    if !matches!(stmt, Stmt::Let(..)) && locals.is_immutable(id) {
+                        return Err(error_in(
+                            RuntimeErrorType::MutationOfImmutable(id.clone()),
+                            span,
+                        ));
+                    }
+                    let variable = Variable {
                         ident: id.to_string(),
-                        value: res,
-                    },
-                );
+                        value: self.eval(expr, locals)?,
+                        is_mutable: !matches!(stmt, Stmt::Const(..)),
+                    };
+                    match stmt {
+                        Stmt::Asgn(..) => locals.assign(variable),
+                        _ => locals.declare(variable),
+                    }
+                }
+                Stmt::Decl(var) => locals.declare(var.clone()),
+            };
+        }
+        if self.hook.is_some() {
+            self.step((block.expr.position, block.expr.end), locals)?;
+        }
+        self.eval(&block.expr, locals)
+    }
+
+    fn eval_function(
+        &mut self,
+        function: &Function,
+        arglist: ArgList,
+    ) -> Result<VarVal, RuntimeError> {
+        #[cfg(feature = "std")]
+        {
+            if self.profile.is_some() {
+                let start = std::time::Instant::now();
+                let res = self.call_function(function, arglist);
+                if let Some(profile) = &mut self.profile {
+                    profile.record(&function.name, start.elapsed());
+                }
+                return res;
             }
-        };
+        }
+        self.call_function(function, arglist)
+    }
+
+    fn call_function(
+        &mut self,
+        function: &Function,
+        arglist: ArgList,
+    ) -> Result<VarVal, RuntimeError> {
+        let mut locals = Scope::default();
+        if arglist.args.len() != function.arguments.len() {
+            return Err(error(
+                RuntimeErrorType::WrongNumberOfArguments(function.name.clone()),
+                function.position,
+            ));
+        }
+        for (var, arg_value) in function.arguments.iter().zip(arglist.args.iter()) {
+            let mut var = var.clone();
+            var.value = arg_value.clone();
+            locals.variables.insert(var.ident.clone(), var);
+        }
+        self.eval_block(&function.block, &mut locals)
     }
-    eval(&block.expr, globals, program, locals, buildins)
 }
 
-fn eval_function(
-    function: &Function,
-    arglist: ArgList,
-    globals: &mut HashMap<String, Variable>,
+pub fn execute(
     program: &Program,
+    globals: &mut Map<String, Variable>,
     buildins: &mut Buildins,
 ) -> Result<VarVal, RuntimeError> {
-    let mut locals = HashMap::new();
-    if arglist.args.len() != function.arguments.len() {
-        return Err(error(
-            RuntimeErrorType::WrongNumberOfArguments(function.name.clone()),
-            function.position,
-        ));
-    }
-    for (var, arg_value) in function.arguments.iter().zip(arglist.args.iter()) {
-        let mut var = var.clone();
-        var.value = arg_value.clone();
-        locals.insert(var.ident.clone(), var);
-    }
-    eval_block(&function.block, globals, program, &mut locals, buildins)
+    Interpreter::new(program, globals, buildins).run()
 }
 
-pub fn execute(
+/// Like `execute`, runtime errors get line and column of their position in `src`,
+/// the source `program` was parsed from
+pub fn execute_with_source(
+    src: &str,
     program: &Program,
-    globals: &mut HashMap<String, Variable>,
+    globals: &mut Map<String, Variable>,
     buildins: &mut Buildins,
 ) -> Result<VarVal, RuntimeError> {
-    if let Some(main) = program.functions.get("main") {
-        eval_function(
-            main,
-            ArgList { args: Vec::new() },
-            globals,
-            program,
-            buildins,
-        )
-    } else {
-        Err(error(RuntimeErrorType::NoMain, 0))
-    }
+    execute(program, globals, buildins).map_err(|mut e| {
+        let (line, column) = LineIndex::new(src).position(e.position);
+        e.line = Some(line);
+        e.column = Some(column);
+        e
+    })
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct ParsingError {
     pub from: usize,
     pub to: usize,
     pub description: String,
+    /// 1-based line of `from`
+    pub line: Option<usize>,
+    /// 1-based column of `from` in characters
+    pub column: Option<usize>,
+    /// Names of tokens the parser would accept at `from`, like `';'` or `identifier`
+    pub expected: Vec<String>,
+    /// Name of the unexpected token, `None` at the end of input and for lexer errors
+    pub found: Option<String>,
+    /// Other places of the source the error refers to, with a note about each
+    pub related: Vec<((usize, usize), String)>,
+    /// Name of the source given to `parse_named`
+    pub source_name: Option<String>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "code", serialize_with = "serialize_code")
+    )]
+    pub kind: ParsingErrorKind,
+}
+
+impl ParsingError {
+    /// Stable code of the kind of the error, see `explain`
+    pub fn code(&self) -> &'static str {
+        self.kind.code()
+    }
+}
+
+#[cfg(feature = "serde")]
+fn serialize_code<S: serde::Serializer>(
+    kind: &ParsingErrorKind,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(kind.code())
+}
+
+/// Kind of a parsing error
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParsingErrorKind {
+    /// Error of the lexer
+    Lexer(LexerErrorKind),
+    /// Token which can't continue the program
+    UnexpectedToken,
+    /// Two statements without `;` between them
+    MissingSemicolon,
+    /// End of input in the middle of a declaration
+    UnexpectedEof,
+    /// Token after the end of the program
+    ExtraToken,
+    /// Token the parser doesn't know
+    InvalidToken,
+    /// Comparison used as an operand of another comparison
+    ChainedComparison,
+    /// Second function or struct of the same name
+    DuplicateDefinition,
+    /// Expression nested deeper than the limit of `parse_with_max_depth`
+    NestingTooDeep,
+}
+
+impl ParsingErrorKind {
+    /// Stable code of the error, see `explain`
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParsingErrorKind::Lexer(kind) => kind.code(),
+            ParsingErrorKind::UnexpectedToken => "E0102",
+            ParsingErrorKind::MissingSemicolon => "E0103",
+            ParsingErrorKind::UnexpectedEof => "E0104",
+            ParsingErrorKind::ExtraToken => "E0105",
+            ParsingErrorKind::InvalidToken => "E0106",
+            ParsingErrorKind::ChainedComparison => "E0113",
+            ParsingErrorKind::DuplicateDefinition => "E0114",
+            ParsingErrorKind::NestingTooDeep => "E0115",
+        }
+    }
+}
+
+impl fmt::Display for ParsingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error[{}]: {}", self.code(), self.description)?;
+        write_location(f, self.from, self.line, self.column)?;
+        match &self.source_name {
+            Some(name) => write!(f, " in {}", name),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParsingError {}
+
+impl From<LexerError> for ParsingError {
+    fn from(error: LexerError) -> Self {
+        ParsingError {
+            from: error.location,
+            to: error.location + 1,
+            description: error.message(),
+            line: None,
+            column: None,
+            expected: Vec::new(),
+            found: None,
+            related: Vec::new(),
+            source_name: error.source_name,
+            kind: ParsingErrorKind::Lexer(error.kind),
+        }
+    }
+}
+
+/// Error of loading a program with `Program::from_json`
+#[cfg(all(feature = "std", feature = "serde"))]
+#[derive(Debug)]
+pub enum LoadError {
+    /// Input isn't a JSON encoded program
+    Json(serde_json::Error),
+    /// Program was decoded but doesn't pass the static checks
+    Invalid(CheckError),
+    /// Input doesn't start with the header of `Program::to_bytes`
+    #[cfg(feature = "cache")]
+    NotBinary,
+    /// Input was encoded by `Program::to_bytes` of another format version
+    #[cfg(feature = "cache")]
+    Version(u16),
+    /// Input has the right header but the encoded program is truncated or corrupted
+    #[cfg(feature = "cache")]
+    Binary(bincode::Error),
+}
+
+#[cfg(all(feature = "std", feature = "serde"))]
+impl Program {
+    /// Decode a program serialized to JSON and check it calls only existing functions
+    /// and builtins the way `parse` would require
+    pub fn from_json(s: &str) -> Result<Program, LoadError> {
+        let program: Program = serde_json::from_str(s).map_err(LoadError::Json)?;
+        let buildins = default_buildins();
+        let names: Vec<&str> = buildins.keys().map(String::as_str).collect();
+        check_program(&program, &names).map_err(LoadError::Invalid)?;
+        Ok(program)
+    }
+}
+
+/// Start of the output of `Program::to_bytes`, followed by the format version
+#[cfg(feature = "cache")]
+const BINARY_MAGIC: &[u8; 4] = b"PRAB";
+
+/// Version of the binary format, changed with every change of the AST
+#[cfg(feature = "cache")]
+pub const BINARY_VERSION: u16 = 4;
+
+#[cfg(feature = "cache")]
+impl Program {
+    /// Encode the program to load it with `from_bytes` faster than parsing the source
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.extend(&BINARY_VERSION.to_le_bytes());
+        // serializing into memory fails only for types serde can't encode
+        bytes.extend(bincode::serialize(self).expect("AST is serializable"));
+        bytes
+    }
+
+    /// Decode a program encoded by `to_bytes` of the same version and check it like
+    /// `from_json` does
+    pub fn from_bytes(bytes: &[u8]) -> Result<Program, LoadError> {
+        let header = BINARY_MAGIC.len() + 2;
+        if bytes.len() < header || &bytes[..BINARY_MAGIC.len()] != BINARY_MAGIC {
+            return Err(LoadError::NotBinary);
+        }
+        let version = u16::from_le_bytes([bytes[header - 2], bytes[header - 1]]);
+        if version != BINARY_VERSION {
+            return Err(LoadError::Version(version));
+        }
+        let program: Program = bincode::deserialize(&bytes[header..]).map_err(LoadError::Binary)?;
+        let buildins = default_buildins();
+        let names: Vec<&str> = buildins.keys().map(String::as_str).collect();
+        check_program(&program, &names).map_err(LoadError::Invalid)?;
+        Ok(program)
+    }
 }
 
-fn parsing_err(from: usize, to: usize, description: String) -> ParsingError {
+#[cfg(feature = "std")]
+fn parsing_err(
+    kind: ParsingErrorKind,
+    from: usize,
+    to: usize,
+    description: String,
+) -> ParsingError {
     ParsingError {
         from,
         to,
         description,
+        line: None,
+        column: None,
+        expected: Vec::new(),
+        found: None,
+        related: Vec::new(),
+        source_name: None,
+        kind,
+    }
+}
+
+/// User-facing names of the terminals LALRPOP expected, without duplicates
+#[cfg(feature = "std")]
+fn expected_names(expected: &[String]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for terminal in expected {
+        let name = match terminal.as_str() {
+            "identifier" => "identifier".to_string(),
+            "number" => "integer literal".to_string(),
+            "string" | "escaped_string" => "string literal".to_string(),
+            "character" => "character literal".to_string(),
+            quoted => format!("'{}'", quoted.trim_matches('"')),
+        };
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+#[cfg(feature = "std")]
+fn one_of(names: &[String]) -> String {
+    match names {
+        [name] => name.clone(),
+        names => format!("one of {}", names.join(", ")),
     }
 }
 
+/// Parse `input`, reporting only the first syntax error
+///
+/// ```
+/// use mylib::{execute, parse, Map, VarVal};
+/// use std::error::Error;
+///
+/// fn run(src: &str) -> Result<VarVal, Box<dyn Error>> {
+///     let program = parse(src)?;
+///     Ok(execute(&program, &mut Map::new(), &mut Map::new())?)
+/// }
+///
+/// fn main() -> Result<(), Box<dyn Error>> {
+///     assert_eq!(run("fn main() { 1 + 2 }")?, VarVal::I32(Some(3)));
+///     let err = run("fn main() {\n  1 ? 2\n}").unwrap_err();
+///     assert_eq!(err.to_string(), "error[E0101]: Unexpected character ? at 2:5");
+///     let err = run("fn main() { a }").unwrap_err();
+///     assert_eq!(err.to_string(), "error[E0001]: Undefined variable a at byte 12");
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "std")]
+#[allow(clippy::result_large_err)] // errors are rare and returned once per parse
 pub fn parse(input: &str) -> Result<Program, ParsingError> {
-    let lexer = lexer::Lexer::new(input);
-    parser::ProgramParser::new()
-        .parse(input, lexer)
-        .map_err(|e| match e {
-            ParseError::User { error } => parsing_err(
-                error.location,
-                error.location + 1,
-                format!("Unexpected character {}", error.char.unwrap_or(' ')),
-            ),
-            ParseError::InvalidToken { location } => {
-                parsing_err(location, location, "invalid token".to_string())
+    parse_with_max_depth(input, MAX_DEPTH)
+}
+
+/// Nesting of expressions `parse` accepts, deeper ones fail with `NestingTooDeep` instead
+/// of overflowing the stack of the evaluator, long chains like `1 + 1 + ... + 1` are flat
+pub const MAX_DEPTH: usize = 128;
+
+/// Number of elements of the longest array `start..end`, `start..=end` and `range` build, longer ranges
+/// fail with `RangeTooLong` instead of allocating arbitrary amounts of memory
+pub const MAX_RANGE_LEN: usize = 1 << 20;
+
+/// Parse `input` like `parse` with a different limit of the nesting, deeper expressions
+/// need a larger stack to be evaluated
+#[cfg(feature = "std")]
+#[allow(clippy::result_large_err)]
+pub fn parse_with_max_depth(input: &str, max_depth: usize) -> Result<Program, ParsingError> {
+    parse_recovering(Lexer::new(input), input, max_depth).map_err(|mut errors| errors.remove(0))
+}
+
+/// Parse `input` like `parse` and put `name`, like the path of the file the source was
+/// read from, into the error
+#[cfg(feature = "std")]
+#[allow(clippy::result_large_err)]
+pub fn parse_named(input: &str, name: &str) -> Result<Program, ParsingError> {
+    let lexer = Lexer::new(input).with_source_name(name);
+    parse_recovering(lexer, input, MAX_DEPTH).map_err(|mut errors| errors.remove(0))
+}
+
+/// Parse `input` and report every syntax error in order, after an error the parser skips
+/// to the end of the statement or block and goes on, errors of the lexer are still fatal
+#[cfg(feature = "std")]
+pub fn parse_all(input: &str) -> Result<Program, Vec<Diagnostic>> {
+    parse_recovering(Lexer::new(input), input, MAX_DEPTH)
+        .map_err(|errors| errors.iter().map(Diagnostic::from).collect())
+}
+
+/// Parse `input` like `parse_all` and put `name` into the diagnostics, like `parse_named`
+#[cfg(feature = "std")]
+pub fn parse_all_named(input: &str, name: &str) -> Result<Program, Vec<Diagnostic>> {
+    let lexer = Lexer::new(input).with_source_name(name);
+    parse_recovering(lexer, input, MAX_DEPTH)
+        .map_err(|errors| errors.iter().map(Diagnostic::from).collect())
+}
+
+#[cfg(feature = "std")]
+fn parse_recovering(
+    mut lexer: Lexer,
+    input: &str,
+    max_depth: usize,
+) -> Result<Program, Vec<ParsingError>> {
+    let source_name = lexer.source_name().map(str::to_string);
+    let mut recovered = Vec::new();
+    let tokens = lexer
+        .by_ref()
+        .map(|token| token.map_err(GrammarError::Lexer));
+    let result = parser::ItemsParser::new().parse(input, &mut recovered, tokens);
+    let recovered = recovered.into_iter().map(|r| r.error);
+    let mut errors: Vec<_> = recovered.map(|e| parsing_error(input, e)).collect();
+    match result {
+        Ok(items) => {
+            let comments = lexer.take_comments();
+            let program = collect_items(input, items, &comments, max_depth, &mut errors);
+            if errors.is_empty() {
+                return Ok(program);
             }
-            ParseError::UnrecognizedToken {
-                token: (l, token, r),
+            errors.sort_by_key(|e| e.from);
+        }
+        Err(e) => errors.push(parsing_error(input, e)),
+    }
+    // errors of the grammar and of collecting the items don't come from the lexer
+    for error in &mut errors {
+        error.source_name = source_name.clone();
+    }
+    Err(errors)
+}
+
+/// Build a `Program` from the parsed items, a name defined again is reported at
+/// its second definition and the first one is kept, so is a function nested deeper
+/// than `max_depth`
+#[cfg(feature = "std")]
+fn collect_items(
+    input: &str,
+    items: Vec<Item>,
+    comments: &[lexer::Comment],
+    max_depth: usize,
+    errors: &mut Vec<ParsingError>,
+) -> Program {
+    let mut functions = Functions::new();
+    let mut structs: Map<String, StructDef> = Map::new();
+    let index = LineIndex::new(input);
+    for item in items {
+        let (kind, keyword, name, position, first) = match item {
+            Item::Function(f) => match functions.get(&f.name) {
+                Some(first) => ("function", "fn", f.name, f.position, first.position),
+                None => {
+                    if let Some(expr) = ast::too_deep(&f.block, max_depth) {
+                        let (line, column) = index.position(expr.position);
+                        errors.push(ParsingError {
+                            line: Some(line),
+                            column: Some(column),
+                            ..parsing_err(
+                                ParsingErrorKind::NestingTooDeep,
+                                expr.position,
+                                expr.end,
+                                "Expression is nested too deeply".to_string(),
+                            )
+                        });
+                    }
+                    let doc = doc_comment(input, comments, f.position);
+                    functions.insert(Function { doc, ..f });
+                    continue;
+                }
+            },
+            Item::Struct(s) => match structs.get(&s.name) {
+                Some(first) => ("struct", "struct", s.name, s.position, first.position),
+                None => {
+                    structs.insert(s.name.clone(), s);
+                    continue;
+                }
+            },
+        };
+        // the spans cover the keyword and the name
+        let span = |position: usize| {
+            let start = position + keyword.len();
+            let to = start + input[start..].find(&name).map_or(0, |i| i + name.len());
+            (position, to)
+        };
+        let (first_line, first_column) = index.position(first);
+        let description = format!(
+            "{} {} is already defined at {}:{}",
+            kind, name, first_line, first_column
+        );
+        let note = format!("{} {} is first defined here", kind, name);
+        let (line, column) = index.position(position);
+        errors.push(ParsingError {
+            line: Some(line),
+            column: Some(column),
+            related: vec![(span(first), note)],
+            ..parsing_err(
+                ParsingErrorKind::DuplicateDefinition,
+                position,
+                span(position).1,
+                description,
+            )
+        });
+    }
+    Program { functions, structs }
+}
+
+/// Documentation of the item at `position`, the text of the `//` or `///` comments on
+/// the lines right above it, a blank line or code in between detaches them
+#[cfg(feature = "std")]
+fn doc_comment(input: &str, comments: &[lexer::Comment], position: usize) -> Option<String> {
+    let mut lines = Vec::new();
+    let mut next = position;
+    for comment in comments.iter().rev().skip_while(|c| c.span.0 >= position) {
+        let (start, end) = comment.span;
+        let gap = &input[end..next];
+        let line_start = input[..start].rfind('\n').map_or(0, |i| i + 1);
+        let own_line = input[line_start..start].trim().is_empty();
+        let adjacent = gap.trim().is_empty() && gap.matches('\n').count() == 1;
+        if !comment.text.starts_with("//") || !own_line || !adjacent {
+            break;
+        }
+        let text = comment.text.trim_start_matches('/');
+        lines.push(text.strip_prefix(' ').unwrap_or(text).trim_end());
+        next = start;
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    lines.reverse();
+    Some(lines.join("\n"))
+}
+
+/// Error of the parser besides the syntax errors, the errors of the lexer pass through it
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GrammarError {
+    Lexer(LexerError),
+    /// Comparison used as an operand of another comparison, with the span of its operator
+    ChainedComparison((usize, usize)),
+}
+
+#[cfg(feature = "std")]
+fn parsing_error(input: &str, e: ParseError<usize, Token, GrammarError>) -> ParsingError {
+    let mut e = match e {
+        ParseError::User {
+            error: GrammarError::ChainedComparison((from, to)),
+        } => parsing_err(
+            ParsingErrorKind::ChainedComparison,
+            from,
+            to,
+            "Comparison operators can't be chained, combine comparisons with &&".to_string(),
+        ),
+        ParseError::User {
+            error: GrammarError::Lexer(error),
+        } => {
+            let mut e = ParsingError::from(error.clone());
+            match error.kind {
+                LexerErrorKind::UnknownEscape => e.to += 1,
+                LexerErrorKind::UnterminatedBlockComment | LexerErrorKind::UnterminatedString => {
+                    e.to = input.len()
+                }
+                LexerErrorKind::IntegerOverflow | LexerErrorKind::InvalidIntegerLiteral => {
+                    let digits = &input[error.location..];
+                    let end = digits
+                        .find(|ch: char| !ch.is_ascii_alphanumeric() && ch != '_')
+                        .unwrap_or(digits.len());
+                    e.to = error.location + end;
+                    e.description = if error.kind == LexerErrorKind::IntegerOverflow {
+                        format!("Integer literal {} doesn't fit into i32", &digits[..end])
+                    } else {
+                        format!("Invalid integer literal {}", &digits[..end])
+                    };
+                }
+                _ => (),
+            }
+            e
+        }
+        ParseError::InvalidToken { location } => parsing_err(
+            ParsingErrorKind::InvalidToken,
+            location,
+            location,
+            "invalid token".to_string(),
+        ),
+        ParseError::UnrecognizedToken {
+            token: (l, token, r),
+            expected,
+        } => {
+            let expected = expected_names(&expected);
+            let found = token.name();
+            // a statement may end before the token and the token may start the next one
+            let after_statement = expected.iter().any(|e| e == "';'");
+            let (kind, description) = if after_statement && lexer::starts_statement(&token) {
+                (
+                    ParsingErrorKind::MissingSemicolon,
+                    format!("missing ';' before {}", found),
+                )
+            } else {
+                let expected = one_of(&expected);
+                (
+                    ParsingErrorKind::UnexpectedToken,
+                    format!("unexpected {}, expected {}", found, expected),
+                )
+            };
+            ParsingError {
                 expected,
-            } => parsing_err(
+                found: Some(found.to_string()),
+                ..parsing_err(kind, l, r, description)
+            }
+        }
+        ParseError::ExtraToken {
+            token: (l, token, r),
+        } => ParsingError {
+            found: Some(token.name().to_string()),
+            ..parsing_err(
+                ParsingErrorKind::ExtraToken,
                 l,
                 r,
-                format!(
-                    "unexpected token {:?}, expected {}",
-                    token,
-                    expected.join(",")
-                ),
-            ),
-            ParseError::ExtraToken {
-                token: (l, token, r),
-            } => parsing_err(l, r, format!("extra token '{:?}' encountered", token)),
-            ParseError::UnrecognizedEOF { location, expected } => parsing_err(
-                location,
-                location,
-                format!("unexpected end of file, expecting {}", expected.join(", ")),
-            ),
+                format!("extra {} encountered", token.name()),
+            )
+        },
+        ParseError::UnrecognizedEOF { location, expected } => {
+            let expected = expected_names(&expected);
+            let description = format!("unexpected end of file, expected {}", one_of(&expected));
+            ParsingError {
+                expected,
+                ..parsing_err(
+                    ParsingErrorKind::UnexpectedEof,
+                    location,
+                    location,
+                    description,
+                )
+            }
+        }
+    };
+    let (line, column) = LineIndex::new(input).position(e.from);
+    e.line = Some(line);
+    e.column = Some(column);
+    e
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(feature = "std")]
+    fn run(input: &str) -> Result<VarVal, RuntimeError> {
+        let program = parse(input).unwrap();
+        execute(&program, &mut Map::new(), &mut Map::new())
+    }
+
+    fn value(position: usize, value: i32) -> Box<Expr> {
+        Box::new(Expr {
+            position,
+            end: position + 2,
+            expression_type: ExprType::Value(VarVal::I32(Some(value))),
         })
+    }
+
+    /// `fn main() { 40 + 2 }` built without the parser, which needs `std`
+    #[test]
+    fn execute_ast() {
+        let main = Function {
+            position: 0,
+            end: 20,
+            arguments: Vec::new(),
+            argument_spans: Vec::new(),
+            name: "main".to_string(),
+            return_type: None,
+            doc: None,
+            block: Block {
+                position: 10,
+                end: 20,
+                statements: Vec::new(),
+                expr: Box::new(Expr {
+                    position: 12,
+                    end: 18,
+                    expression_type: ExprType::Op(value(12, 40), Opcode::Add, value(17, 2)),
+                }),
+            },
+        };
+        let mut program = Program {
+            functions: Functions::new(),
+            structs: Map::new(),
+        };
+        program.functions.insert(main);
+        let res = execute(&program, &mut Map::new(), &mut Map::new()).unwrap();
+        assert_eq!(res, VarVal::I32(Some(42)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn const_read() {
+        let res = run("fn main() { const answer = 40 + 2; answer }").unwrap();
+        assert_eq!(res, VarVal::I32(Some(42)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn const_reassign() {
+        let err = run("fn main() { const answer = 42; answer = 1; answer }").unwrap_err();
+        match err.error_type {
+            RuntimeErrorType::MutationOfImmutable(name) => assert_eq!(name, "answer"),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn variable_reassign() {
+        let res = run("fn main() { answer = 1; answer = answer + 41; answer }").unwrap();
+        assert_eq!(res, VarVal::I32(Some(42)));
+        let res = run("fn main() { let answer = 1; answer = 42; answer }").unwrap();
+        assert_eq!(res, VarVal::I32(Some(42)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn const_redeclare() {
+        let err = run("fn main() { const answer = 42; const answer = 1; answer }").unwrap_err();
+        match err.error_type {
+            RuntimeErrorType::MutationOfImmutable(name) => assert_eq!(name, "answer"),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn declared_is_null() {
+        let res = run("fn main() { let flag: bool; flag }").unwrap();
+        assert_eq!(res, VarVal::BOOL(None));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn declared_then_assigned() {
+        let res = run("fn main() { let x: i32; x = 4; let s: String; x * 2 }").unwrap();
+        assert_eq!(res, VarVal::I32(Some(8)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn power() {
+        let res = run("fn main() { 2 ** 10 == 1024 && 2 ** 3 ** 2 == 512 }").unwrap();
+        assert_eq!(res, VarVal::BOOL(Some(true)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn power_errors() {
+        let err = run("fn main() { 2 ** (0 - 1) }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::NegativeExponent));
+        let err = run("fn main() { 2 ** 31 }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::IntegerOverflow));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn arithmetic_errors() {
+        let err = run("fn main() { a = 2147483647; a + 1 }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::IntegerOverflow));
+        let input = "fn main() { a = 0; 7 % a }";
+        let err = run(input).unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::DivisionByZero));
+        assert_eq!(err.to_string(), "error[E0020]: Division by zero at byte 19");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn shared_program_threads() {
+        let program = parse(
+            "fn fib(n: i32) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }
+            fn main() { n = read_int(); print(fib(n)); n }",
+        )
+        .unwrap();
+        let outputs: Vec<String> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (10..14)
+                .map(|n| {
+                    let program = &program;
+                    scope.spawn(move || {
+                        let input = format!("{}\n", n);
+                        let mut output = Vec::new();
+                        let mut buildins = buildin::buildins_with_io(input.as_bytes(), &mut output);
+                        let res = execute(program, &mut Map::new(), &mut buildins);
+                        drop(buildins);
+                        assert_eq!(res.unwrap(), VarVal::I32(Some(n)));
+                        String::from_utf8(output).unwrap()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(outputs, vec!["55\n", "89\n", "144\n", "233\n"]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn char_values() {
+        let res = run("fn main() { 'z' - 'a' }").unwrap();
+        assert_eq!(res, VarVal::I32(Some(25)));
+        let res = run("fn main() { let c: char; c = '\\u{e9}'; c > 'a' && c != 'e' }").unwrap();
+        assert_eq!(res, VarVal::BOOL(Some(true)));
+        assert_eq!(VarVal::CHAR(Some('é')).to_string(), "é");
+        assert_eq!(VarVal::CHAR(None).to_string(), "null");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn struct_fields() {
+        let res = run("
+            struct Point { x: i32, y: i32 }
+            fn shift(p: Point, by: i32) { Point { x: p.x + by, y: p.y } }
+            fn main() {
+                p = shift(Point { y: 2, x: 1 }, 10);
+                if p.x > p.y { p } else { Point { x: 0, y: 0 } }
+            }")
+        .unwrap();
+        assert_eq!(res.to_string(), "Point { x: 11, y: 2 }");
+        let res = run("
+            struct L { items: [[i32]], last: Point }
+            struct Point { x: i32, y: i32 }
+            fn main() { let p: Point; L { items: [], last: p }.items }")
+        .unwrap();
+        let element = DataType::ARRAY(Box::new(DataType::I32));
+        assert_eq!(res.data_type(), DataType::ARRAY(Box::new(element)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn struct_errors() {
+        let err = run("struct P { x: i32 } fn main() { P { x: 1 }.y }").unwrap_err();
+        match err.error_type {
+            RuntimeErrorType::UndefinedField(name) => assert_eq!(name, "y"),
+            e => panic!("unexpected error {:?}", e),
+        }
+        let err = run("struct P { x: i32, y: i32 } fn main() { P { x: 1 } }").unwrap_err();
+        match err.error_type {
+            RuntimeErrorType::MissingField(name) => assert_eq!(name, "y"),
+            e => panic!("unexpected error {:?}", e),
+        }
+        let input = "struct P { x: i32 } fn main() { P { x: \"a\" } }";
+        let err = run(input).unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOperands));
+        assert_eq!(err.position, input.find("\"a\"").unwrap());
+        let err = run("fn main() { Q { } }").unwrap_err();
+        assert!(matches!(
+            err.error_type,
+            RuntimeErrorType::UndefinedStruct(_)
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn integer_literal_overflow() {
+        let err = parse("fn main(){99999999999}").unwrap_err();
+        assert_eq!((err.from, err.to), (10, 21));
+        assert_eq!(
+            err.description,
+            "Integer literal 99999999999 doesn't fit into i32"
+        );
+        let err = parse("fn main() { 9999999999 }").unwrap_err();
+        assert_eq!(
+            err.description,
+            "Integer literal 9999999999 doesn't fit into i32"
+        );
+        let long = format!("fn main() {{ {} }}", "9".repeat(10_000));
+        assert!(parse(&long).is_err());
+        assert!(parse("fn main() { 2147483647 }").is_ok());
+
+        let err = parse("fn main() { 1_000_ }").unwrap_err();
+        assert_eq!((err.from, err.to), (12, 18));
+        assert_eq!(err.description, "Invalid integer literal 1_000_");
+        let res = run("fn main() { 1_000 + 0xFF_FF + -0x8000_0000 + 0b1_0 }");
+        assert_eq!(
+            res.unwrap(),
+            VarVal::I32(Some(1000 + 0xFFFF + i32::MIN + 2))
+        );
+    }
+
+    #[cfg(all(feature = "std", feature = "serde"))]
+    #[test]
+    fn json_round_trip() {
+        let program = parse(
+            "struct P { x: i32, c: char }
+            fn fib(n: i32) -> i32 { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }
+            fn main() { let s: String; const p = P { x: 10, c: 'a' }; fib(p.x) ** 2 }",
+        )
+        .unwrap();
+        let json = serde_json::to_string(&program).unwrap();
+        let copy: Program = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy, program);
+        let res = execute(&copy, &mut Map::new(), &mut Map::new()).unwrap();
+        assert_eq!(res, VarVal::I32(Some(55 * 55)));
+        let direct = execute(&program, &mut Map::new(), &mut Map::new()).unwrap();
+        assert_eq!(res, direct);
+
+        // fields added by another version of the AST aren't silently dropped
+        let drifted = json.replacen("\"position\":", "\"offset\":0,\"position\":", 1);
+        match Program::from_json(&drifted).unwrap_err() {
+            LoadError::Json(e) => assert!(e.to_string().contains("unknown field `offset`")),
+            e => panic!("unexpected error {:?}", e),
+        }
+        let drifted = json.replacen("{", "{\"version\":1,", 1);
+        assert!(Program::from_json(&drifted).is_err());
+    }
+
+    #[cfg(all(feature = "std", feature = "serde"))]
+    #[test]
+    fn function_order() {
+        let input = "fn zeta() { 1 } fn main() { zeta() + alpha() } fn alpha() { 2 }";
+        let first = serde_json::to_string(&parse(input).unwrap()).unwrap();
+        let second = serde_json::to_string(&parse(input).unwrap()).unwrap();
+        assert_eq!(first, second);
+        let order = first.find("\"zeta\"") < first.find("\"main\"")
+            && first.find("\"main\"") < first.find("\"alpha\"");
+        assert!(order, "{}", first);
+        let loaded = Program::from_json(&first).unwrap();
+        let names: Vec<_> = loaded.functions.keys().collect();
+        assert_eq!(names, ["zeta", "main", "alpha"]);
+    }
+
+    #[cfg(all(feature = "std", feature = "serde"))]
+    #[test]
+    fn doc_comments() {
+        let program = parse(
+            "/// Add one to `a`
+            ///
+            ///  indented
+            fn inc(a: i32) { a + 1 }
+
+            // not attached
+
+            fn plain() { 1 } // trailing
+            // first
+            /// second
+            fn main() { /* inside */ inc(plain()) }",
+        )
+        .unwrap();
+        let doc = |name: &str| program.functions[name].doc.clone();
+        assert_eq!(doc("inc").unwrap(), "Add one to `a`\n\n indented");
+        assert_eq!(doc("plain"), None);
+        assert_eq!(doc("main").unwrap(), "first\nsecond");
+        let json = serde_json::to_string(&program).unwrap();
+        assert!(json.contains("\"doc\":\"first\\nsecond\""));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn nesting_limit() {
+        let nested = |open: &str, close: &str, n: usize| {
+            format!("fn main() {{ {}1{} }}", open.repeat(n), close.repeat(n))
+        };
+        for input in [
+            nested("1 + (", ")", 100_000),
+            nested("[", "]", 100_000),
+            nested("if true { ", " }", 100_000),
+            nested("f(", ")", 100_000),
+            format!("fn main() {{ a = [1]; a{} }}", "[0]".repeat(200_000)),
+            format!("fn main() {{ a = 1; 2{} }}", " ** a".repeat(100_000)),
+        ] {
+            let err = parse(&input).unwrap_err();
+            assert_eq!(err.code(), "E0115");
+            assert_eq!(err.description, "Expression is nested too deeply");
+        }
+        let input = format!("fn main() {{ a = [[1]]; a{} }}", "[0]".repeat(MAX_DEPTH));
+        let err = parse(&input).unwrap_err();
+        assert_eq!(err.kind, ParsingErrorKind::NestingTooDeep);
+        assert_eq!((err.from, err.to), (23, 24));
+        assert_eq!((err.line, err.column), (Some(1), Some(24)));
+
+        // brackets without operators inside don't nest the expressions
+        assert_eq!(
+            run(&nested("(", ")", 10_000)).unwrap(),
+            VarVal::I32(Some(1))
+        );
+        let res = run(&nested("[", "]", MAX_DEPTH - 1)).unwrap();
+        assert_eq!(res.to_string().len(), 2 * MAX_DEPTH - 1);
+        let input = nested("1 + (", ")", MAX_DEPTH + 10);
+        assert!(parse(&input).is_err());
+        let program = parse_with_max_depth(&input, MAX_DEPTH + 20).unwrap();
+        let res = execute(&program, &mut Map::new(), &mut Map::new());
+        assert_eq!(res.unwrap(), VarVal::I32(Some(MAX_DEPTH as i32 + 11)));
+        let input = format!("fn main() {{ 1{} }}", " + 1".repeat(10_000));
+        assert_eq!(run(&input).unwrap(), VarVal::I32(Some(10_001)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn long_operator_chain() {
+        let input = format!("fn main() {{ x = 2; 0{} }}", " + x - 1".repeat(20_000));
+        assert_eq!(run(&input).unwrap(), VarVal::I32(Some(20_000)));
+        // the chain is dropped with the program, here nested in a call in a branch
+        let chain = "0 + x - 1".to_string() + &" + x - 1".repeat(20_000);
+        let input = format!(
+            "fn f(a: i32) {{ a }} fn main() {{ x = 2; if true {{ f({}) }} else {{ 0 }} }}",
+            chain
+        );
+        assert_eq!(run(&input).unwrap(), VarVal::I32(Some(20_001)));
+        // so do the checks, the printer and the C code generator
+        let program = parse(&input).unwrap();
+        assert!(check(&program, &[]).is_empty());
+        assert!(to_source(&program).contains(&chain));
+        let code = codegen_c::generate_c(&program);
+        assert!(!code.contains("#error"));
+        assert_eq!(code.matches(" + v_x) - 1)").count(), 20_001);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn negative_literals() {
+        let res = run("fn neg(a: i32) { a } fn main() { x = -5; x-1 + neg(-10) }").unwrap();
+        assert_eq!(res, VarVal::I32(Some(-16)));
+        let res = run("fn main() { -2147483648 }").unwrap();
+        assert_eq!(res, VarVal::I32(Some(i32::MIN)));
+        let err = parse("fn main() { 2147483648 }").unwrap_err();
+        assert_eq!(
+            err.description,
+            "Integer literal 2147483648 doesn't fit into i32"
+        );
+    }
+
+    #[cfg(all(feature = "std", feature = "serde"))]
+    #[test]
+    fn from_json_load() {
+        let program =
+            parse("fn inc(a: i32) { a + 1 } fn main() { print(inc(1)); inc(2) }").unwrap();
+        let json = serde_json::to_string(&program).unwrap();
+        let loaded = Program::from_json(&json).unwrap();
+        assert_eq!(loaded, program);
+
+        let err = Program::from_json("{\"functions\": [").unwrap_err();
+        assert!(matches!(err, LoadError::Json(_)));
+
+        let renamed = json.replacen("\"inc\":", "\"dec\":", 1);
+        match Program::from_json(&renamed).unwrap_err() {
+            LoadError::Json(e) => assert!(e
+                .to_string()
+                .starts_with("function inc is stored under the key dec")),
+            e => panic!("unexpected error {:?}", e),
+        }
+        let inc = &json[json.find("\"inc\":").unwrap()..json.find(",\"main\":").unwrap()];
+        let duplicate = json.replacen("\"main\":", &format!("{},\"main\":", inc), 1);
+        match Program::from_json(&duplicate).unwrap_err() {
+            LoadError::Json(e) => {
+                assert!(e.to_string().starts_with("function inc is defined twice"))
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+
+        let broken = json.replace("\"inc\",[", "\"dec\",[");
+        match Program::from_json(&broken).unwrap_err() {
+            LoadError::Invalid(e) => assert_eq!(e.description, "Undefined function dec"),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[cfg(feature = "cache")]
+    #[test]
+    fn binary_round_trip() {
+        let source = "/// adds one\nfn inc(a: i32) { a + 1 }\nfn main() { inc(1) + inc(2) }";
+        let program = parse(source).unwrap();
+        let bytes = program.to_bytes();
+        let loaded = Program::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded, program);
+        let res = execute(&loaded, &mut Map::new(), &mut Map::new()).unwrap();
+        assert_eq!(res, VarVal::I32(Some(5)));
+
+        let mut newer = bytes.clone();
+        newer[4..6].copy_from_slice(&(BINARY_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            Program::from_bytes(&newer),
+            Err(LoadError::Version(v)) if v == BINARY_VERSION + 1
+        ));
+        assert!(matches!(
+            Program::from_bytes(b"{\"functions\": {}}"),
+            Err(LoadError::NotBinary)
+        ));
+        for end in 0..bytes.len() {
+            assert!(Program::from_bytes(&bytes[..end]).is_err());
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn unicode_names() {
+        let res = run("
+            fn délka_strany(počet: i32) { počet * 2 }
+            fn main() { 長さ = 3; const ŽLUŤOUČKÝ = délka_strany(長さ); ŽLUŤOUČKÝ + 1 }")
+        .unwrap();
+        assert_eq!(res, VarVal::I32(Some(7)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn underscore_names() {
+        let res = run("fn _helper(_a: i32) { _a } fn main() { _x = 5; __y = _helper(_x); __y }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(5)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn chained_comparison() {
+        for input in &["fn main() { 1 < 2 < 3 }", "fn main() { 1 < 2 == 3 > 4 }"] {
+            let err = parse(input).unwrap_err();
+            assert_eq!(err.from, 18);
+            assert_eq!(
+                err.description,
+                "Comparison operators can't be chained, combine comparisons with &&"
+            );
+        }
+        let err = parse("fn main() { 1 < 2 <= 3 }").unwrap_err();
+        assert_eq!((err.from, err.to, err.code()), (18, 20, "E0113"));
+        let res = run("fn main() { 1 < 2 && 2 < 3 && (1 < 2) == true }");
+        assert_eq!(res.unwrap(), VarVal::BOOL(Some(true)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn debug_hook() {
+        use std::sync::{Arc, Mutex};
+        let input = "fn main() { a = 1; let b: i32; b = a + 1; b * 2 }";
+        let program = parse(input).unwrap();
+        let (mut globals, mut buildins) = (Map::new(), Map::new());
+        let steps = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&steps);
+        let mut interpreter = Interpreter::new(&program, &mut globals, &mut buildins)
+            .with_debug_hook(Box::new(move |span, locals| {
+                let value = locals.get("a").map(|a| a.value.clone());
+                recorded.lock().unwrap().push((span, value));
+                HookAction::Continue
+            }));
+        assert_eq!(interpreter.run().unwrap(), VarVal::I32(Some(4)));
+        drop(interpreter);
+        let steps = steps.lock().unwrap();
+        assert_eq!(steps.len(), 4);
+        // statements span up to their `;`
+        assert_eq!(&input[(steps[0].0).0..(steps[0].0).1], "a = 1;");
+        assert_eq!(steps[0].1, None);
+        assert_eq!(&input[(steps[3].0).0..(steps[3].0).1], "b * 2");
+        assert_eq!(steps[3].1, Some(VarVal::I32(Some(1))));
+
+        let mut calls = 0;
+        let mut interpreter = Interpreter::new(&program, &mut globals, &mut buildins)
+            .with_debug_hook(Box::new(|_, _| {
+                calls += 1;
+                if calls == 2 {
+                    HookAction::Stop
+                } else {
+                    HookAction::Continue
+                }
+            }));
+        let err = interpreter.run().unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::Stopped));
+        assert_eq!(err.position, input.find("let").unwrap());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn breakpoints() {
+        let input = "fn f(x: i32) { y = x + 1; y * 2 }
+            fn main() { a = f(1); let b: i32; b = f(a); b }";
+        let program = parse(input).unwrap();
+        let (mut globals, mut buildins) = (Map::new(), Map::new());
+        let mut hits = Vec::new();
+        let breakpoints = [input.find("y * 2").unwrap() + 2, input.find("let").unwrap()];
+        let mut interpreter = Interpreter::new(&program, &mut globals, &mut buildins)
+            .with_debug_hook(Box::new(|span, locals| {
+                let y = locals.get("y").map(|y| y.value.clone());
+                hits.push((span, y));
+                HookAction::Continue
+            }))
+            .with_breakpoints(breakpoints.iter().cloned().collect());
+        assert_eq!(interpreter.run().unwrap(), VarVal::I32(Some(10)));
+        drop(interpreter);
+        let tail = input.find("y * 2").unwrap();
+        let decl = input.find("let").unwrap();
+        assert_eq!(
+            hits,
+            vec![
+                ((tail, tail + 5), Some(VarVal::I32(Some(2)))),
+                ((decl, decl + "let b: i32;".len()), None),
+                ((tail, tail + 5), Some(VarVal::I32(Some(5)))),
+            ]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn optional_semicolons() {
+        let res = run("
+            struct P { x: i32, y: i32 }
+            fn add(a: i32, b: i32) { a + b }
+            fn main() {
+                let unused: i32
+                const p = P {
+                    x: 1,
+                    y: 2
+                }
+                a = add(
+                    p.x,
+                    p.y
+                )
+                b = a *
+                    10
+                    + 2
+                if a > 100 { a }
+                else { b }
+            }")
+        .unwrap();
+        assert_eq!(res, VarVal::I32(Some(32)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn missing_semicolon() {
+        let err = parse("fn main() { a = 1 b = 2; a }").unwrap_err();
+        assert_eq!(err.from, 18);
+        assert_eq!(err.description, "missing ';' before identifier");
+
+        // a closing bracket can't start a statement
+        let input = "fn main() { a = (1 + 2)); a }";
+        let err = parse(input).unwrap_err();
+        assert_eq!(err.from, input.find("); a").unwrap());
+        assert!(matches!(err.kind, ParsingErrorKind::UnexpectedToken));
+        assert!(err
+            .description
+            .starts_with("unexpected ')', expected one of"));
+        let err = parse("fn main() { a = [1, 2]]; a }").unwrap_err();
+        assert!(err.description.starts_with("unexpected ']', expected"));
+    }
+
+    #[cfg(all(feature = "std", feature = "serde"))]
+    #[test]
+    fn error_line_column() {
+        let err = parse("fn main() {\r\n  a = 1;\r\n  a +\r\n}").unwrap_err();
+        assert_eq!((err.line, err.column), (Some(4), Some(1)));
+        let err = parse("fn main() {\n  é = 1 ? 2; é\n}").unwrap_err();
+        assert_eq!((err.line, err.column), (Some(2), Some(9)));
+
+        let input = "fn main() {\n  a = 1;\n  a + b }";
+        let program = parse(input).unwrap();
+        let err = execute(&program, &mut Map::new(), &mut Map::new()).unwrap_err();
+        assert_eq!((err.line, err.column), (None, None));
+        let err =
+            execute_with_source(input, &program, &mut Map::new(), &mut Map::new()).unwrap_err();
+        assert_eq!(err.position, input.find("b }").unwrap());
+        assert_eq!((err.line, err.column), (Some(3), Some(7)));
+        let json = serde_json::to_string(&err).unwrap();
+        assert!(json.ends_with(r#""line":3,"column":7,"code":"E0001"}"#));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn multiple_parse_errors() {
+        let input = "fn f(a: i32) {
+            b = a + ;
+            c = b;
+            d = (c * 2;
+            c + d
+        }
+        fn main() {
+            x = 1 2;
+            f(x) +
+        }";
+        let errors = parse_recovering(Lexer::new(input), input, MAX_DEPTH).unwrap_err();
+        let spans: Vec<_> = errors.iter().map(|e| (e.line, e.column)).collect();
+        assert_eq!(
+            spans,
+            vec![
+                (Some(2), Some(21)),
+                (Some(4), Some(23)),
+                (Some(8), Some(19)),
+                (Some(10), Some(9))
+            ]
+        );
+        assert_eq!(errors[2].description, "missing ';' before integer literal");
+        assert_eq!(parse(input).unwrap_err().from, errors[0].from);
+
+        let errors = parse_all("fn main() { a = 1 + ; a } fn f( { 1 }").unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(parse_all("fn main() { 1 }").is_ok());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn arrays() {
+        let res = run("fn main() { a = [1, 2 * 3, 4]; a[1] + a[2] }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(10)));
+        let res = run("fn first(a: [String]) { a[0] } fn main() { first([\"x\", \"y\"]) }");
+        assert_eq!(res.unwrap(), VarVal::STRING(Some("x".to_string())));
+        let res = run("fn main() { let a: [[bool]]; a }");
+        let nested = DataType::ARRAY(Box::new(DataType::BOOL));
+        assert_eq!(res.unwrap(), VarVal::ARRAY(nested, None));
+        let res = run("fn main() { [[1], [2, 3]][1] }").unwrap();
+        assert_eq!(res.to_string(), "[2, 3]");
+        let res = run("fn main() { [[1], []] }").unwrap();
+        assert_eq!(res.to_string(), "[[1], []]");
+        assert_eq!(res.data_type().to_string(), "[[i32]]");
+        let res = run("fn main() { [[], [[]], [[true]]] }").unwrap();
+        assert_eq!(res.data_type().to_string(), "[[[bool]]]");
+        let res = run("fn main() { {\"a\": [], \"b\": [1]} }").unwrap();
+        assert_eq!(res.data_type().to_string(), "{String: [i32]}");
+
+        let input = "fn main() { a = [1, 2]; a[2] }";
+        let err = run(input).unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::IndexOutOfBounds));
+        assert_eq!(err.position, input.rfind('2').unwrap());
+        let input = "fn main() { [1, true] }";
+        let err = run(input).unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOperands));
+        assert_eq!(err.position, input.find("true").unwrap());
+        let input = "fn main() { [[1], [], [true]] }";
+        let err = run(input).unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOperands));
+        assert_eq!(err.position, input.find("[true]").unwrap());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn error_display() {
+        let err = parse("fn main() {\n  \"\\q\"\n}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "error[E0108]: Unknown escape sequence \\q at 2:4"
+        );
+        assert_eq!((err.from, err.to), (15, 17));
+        let lexer_err = Lexer::new("a # b").find_map(Result::err).unwrap();
+        assert_eq!(lexer_err.to_string(), "Unexpected character # at byte 2");
+        let err = ParsingError::from(lexer_err);
+        assert_eq!((err.from, err.to), (2, 3));
+        assert_eq!(
+            err.to_string(),
+            "error[E0101]: Unexpected character # at byte 2"
+        );
+        let err: Box<dyn std::error::Error> = Box::new(err);
+        assert_eq!(
+            err.to_string(),
+            "error[E0101]: Unexpected character # at byte 2"
+        );
+
+        let input = "fn main() {\n  a = 1;\n  a + b }";
+        let program = parse(input).unwrap();
+        let err =
+            execute_with_source(input, &program, &mut Map::new(), &mut Map::new()).unwrap_err();
+        assert_eq!(err.to_string(), "error[E0001]: Undefined variable b at 3:7");
+
+        let input = "fn main() {\n  a = \"abc;\n  a\n}\n";
+        let err = parse(input).unwrap_err();
+        assert_eq!((err.from, err.to), (input.find('"').unwrap(), input.len()));
+        assert_eq!(
+            err.to_string(),
+            "error[E0110]: Unterminated string literal at 2:7"
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn parse_named_errors() {
+        let err = parse_named("fn main() {\n  a $ b\n}", "main.pra").unwrap_err();
+        assert_eq!(err.source_name.as_deref(), Some("main.pra"));
+        assert_eq!(
+            err.to_string(),
+            "error[E0101]: Unexpected character $ at 2:5 in main.pra"
+        );
+        let err = parse_named("fn main() { a + }", "main.pra").unwrap_err();
+        assert_eq!(err.source_name.as_deref(), Some("main.pra"));
+        // errors of the grammar are named too
+        for input in &["fn main() { 99999999999 }", "fn main() { 1 < 2 < 3 }"] {
+            let err = parse_named(input, "main.pra").unwrap_err();
+            assert_eq!(err.source_name.as_deref(), Some("main.pra"), "{}", input);
+        }
+        let errors = parse_all_named("fn f() { 1 } fn f() { 2 }", "main.pra").unwrap_err();
+        assert_eq!(errors[0].source_name.as_deref(), Some("main.pra"));
+        assert_eq!(parse("fn main() { a $ b }").unwrap_err().source_name, None);
+        assert!(parse_named("fn main() { 1 }", "main.pra").is_ok());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn expected_tokens() {
+        let err = parse("fn main() { a = 1 b = 2; a }").unwrap_err();
+        assert_eq!(err.found.as_deref(), Some("identifier"));
+        assert!(err.expected.contains(&"';'".to_string()));
+        assert!(err.expected.contains(&"'+'".to_string()));
+        assert_eq!(err.description, "missing ';' before identifier");
+
+        let err = parse("fn main() { a = }").unwrap_err();
+        assert_eq!(err.found.as_deref(), Some("'}'"));
+        let mut expected = err.expected.clone();
+        expected.sort();
+        assert_eq!(
+            expected,
+            vec![
+                "'('",
+                "'-'",
+                "'['",
+                "'false'",
+                "'for'",
+                "'if'",
+                "'true'",
+                "'{'",
+                "character literal",
+                "identifier",
+                "integer literal",
+                "string literal"
+            ]
+        );
+
+        let err = parse("fn main() { 1 } }").unwrap_err();
+        assert_eq!(err.expected, vec!["'fn'", "'struct'"]);
+        assert_eq!(
+            err.description,
+            "unexpected '}', expected one of 'fn', 'struct'"
+        );
+        let err = parse("fn main() { 1 ").unwrap_err();
+        assert_eq!(err.found, None);
+        assert_eq!(parse("fn main() { 1 # }").unwrap_err().expected.len(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn locals_shadow_globals() {
+        let program = parse(
+            "fn f(y: i32) { x = 10; x + y }
+            fn g(x: i32) { x }
+            fn main() { a = f(1); a * 100 + g(2) * 10 + x }",
+        )
+        .unwrap();
+        let mut globals = Map::new();
+        let x = Variable {
+            ident: "x".to_string(),
+            value: VarVal::I32(Some(1)),
+            is_mutable: true,
+        };
+        globals.insert("x".to_string(), x.clone());
+        let res = execute(&program, &mut globals, &mut Map::new());
+        assert_eq!(res.unwrap(), VarVal::I32(Some(1100 + 20 + 1)));
+        assert_eq!(globals["x"], x);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn wrong_number_of_arguments() {
+        let input = "fn main() { f(1, 2) }\nfn f(a: i32) { a }";
+        let program = parse(input).unwrap();
+        assert_eq!(program.functions["f"].position, input.find("fn f").unwrap());
+        let err = execute(&program, &mut Map::new(), &mut Map::new()).unwrap_err();
+        assert!(
+            matches!(&err.error_type, RuntimeErrorType::WrongNumberOfArguments(name) if name == "f")
+        );
+        assert_eq!(err.position, program.functions["f"].position);
+        let err = execute_with_source(input, &program, &mut Map::new(), &mut Map::new());
+        let err = err.unwrap_err();
+        assert_eq!((err.line, err.column), (Some(2), Some(1)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn node_spans() {
+        let input = "struct P { x: i32 }
+fn main() {
+    a = 1;
+    if a > 0 { if a > 1 { 1 } else { 2 } } else { 3 }
+}";
+        let program = parse(input).unwrap();
+        let text = |(from, to): (usize, usize)| &input[from..to];
+        assert_eq!(
+            text((program.structs["P"].position, program.structs["P"].end)),
+            "struct P { x: i32 }"
+        );
+        let main = &program.functions["main"];
+        assert_eq!(main.position, input.find("fn main").unwrap());
+        assert_eq!(main.end, input.len());
+        let block = &main.block;
+        assert_eq!(
+            (block.position, block.end),
+            (input.find("{\n").unwrap(), input.len())
+        );
+        let statement = &block.statements[0];
+        assert_eq!(text((statement.position, statement.end)), "a = 1;");
+
+        let outer = match &block.expr.expression_type {
+            ExprType::If(outer) => outer,
+            e => panic!("unexpected expression {:?}", e),
+        };
+        assert_eq!(
+            text((outer.position, outer.end)),
+            "if a > 0 { if a > 1 { 1 } else { 2 } } else { 3 }"
+        );
+        let inner_block = &outer.if_block;
+        assert_eq!(
+            text((inner_block.position, inner_block.end)),
+            "{ if a > 1 { 1 } else { 2 } }"
+        );
+        match &inner_block.expr.expression_type {
+            ExprType::If(inner) => {
+                assert_eq!(
+                    text((inner.position, inner.end)),
+                    "if a > 1 { 1 } else { 2 }"
+                );
+                let else_block = match &inner.else_part {
+                    Else::Else(block) => block,
+                    e => panic!("unexpected else {:?}", e),
+                };
+                assert_eq!(text((else_block.position, else_block.end)), "{ 2 }");
+            }
+            e => panic!("unexpected expression {:?}", e),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn runtime_error_spans() {
+        let span = |input: &str| {
+            let err = run(input).unwrap_err();
+            input[err.position..err.end].to_string()
+        };
+        assert_eq!(span("fn main() { 1 + (2 + true) }"), "2 + true");
+        assert_eq!(span("fn main() { if 1 + 1 { 2 } }"), "1 + 1");
+        assert_eq!(span("fn main() { [1, 2][1 + 1] }"), "1 + 1");
+        assert_eq!(span("fn main() { const c = 1; c = 2; c }"), "c = 2;");
+        assert_eq!(span("fn main() { a }"), "a");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn duplicate_definitions() {
+        let input = "fn main() { 1 }\n\nfn main() { 2 }";
+        let err = parse(input).unwrap_err();
+        assert_eq!(err.kind, ParsingErrorKind::DuplicateDefinition);
+        assert_eq!(err.code(), "E0114");
+        assert_eq!(err.description, "function main is already defined at 1:1");
+        assert_eq!((err.from, err.to), (17, 24));
+        assert_eq!((err.line, err.column), (Some(3), Some(1)));
+
+        let input = "fn f() { 1 }
+            struct P { x: i32 }
+            fn main() { f() }
+            struct P { y: i32 }
+            fn f(a: i32) { a }";
+        let errors = parse_recovering(Lexer::new(input), input, MAX_DEPTH).unwrap_err();
+        let descriptions: Vec<_> = errors.iter().map(|e| e.description.as_str()).collect();
+        assert_eq!(
+            descriptions,
+            [
+                "struct P is already defined at 2:13",
+                "function f is already defined at 1:1"
+            ]
+        );
+        assert_eq!(errors[1].from, input.rfind("fn f").unwrap());
+
+        let errors = parse_all("fn f() { 1 }\nfn f() { a = ; 2 }").unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].code, Some("E0114"));
+        assert_eq!(errors[1].code, Some("E0102"));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn block_scope() {
+        let err = run("fn main() { if true { a = 1; a } else { b = 2; b }; a }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::UndefinedVariable(name) if name == "a"));
+        let err = run("fn main() { if false { a = 1; a } else { b = 2; b }; b }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::UndefinedVariable(name) if name == "b"));
+
+        // assignment to a variable of the enclosing block changes it
+        let res = run("fn main() { a = 1; if true { a = a + 1; b = 10; a = a + b; a }; a }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(12)));
+        // declarations hide it only until the block ends
+        let res = run("fn main() { a = 1; x = if true { let a: i32; a = 5; a * 2 }; a + x }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(11)));
+        let res = run("fn main() { c = 1; if true { const c = 2; c }; c = c + 1; c }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(2)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn null_operands() {
+        let input = "fn main() { let a: i32; b = 1; b + a * 2 }";
+        let err = run(input).unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::NullDereference));
+        assert_eq!(err.position, input.find("a * 2").unwrap());
+        let err = run("fn main() { let s: String; s == \"a\" }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::NullDereference));
+        let err = run("fn main() { 1 + true }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOperands));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn array_equality() {
+        let res = run("fn main() { [1, 2] == [1, 2] && [1, 2] != [1, 3] }");
+        assert_eq!(res.unwrap(), VarVal::BOOL(Some(true)));
+        let res = run("fn main() { a = [[1], [2, 3]]; a == [[1], [2]] || [1] == [] }");
+        assert_eq!(res.unwrap(), VarVal::BOOL(Some(false)));
+        let err = run("fn main() { [1, 2] < [1, 3] }").unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOpcode));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn main_arguments() {
+        let program = parse("fn main(arg: String) { arg }").unwrap();
+        let args = vec![VarVal::STRING(Some("echo".to_string()))];
+        let res = Interpreter::new(&program, &mut Map::new(), &mut Map::new())
+            .with_args(args.clone())
+            .run();
+        assert_eq!(res.unwrap(), args[0]);
+        let err = execute(&program, &mut Map::new(), &mut Map::new()).unwrap_err();
+        assert!(matches!(
+            err.error_type,
+            RuntimeErrorType::WrongNumberOfArguments(_)
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn let_binding() {
+        // assignment updates the counter wherever it was defined
+        let res = run("fn main() {
+                count = 0;
+                if true { count = count + 1; if true { count = count + 1; count } else { 0 } };
+                count
+            }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(2)));
+        // counting in a recursive "loop" through an argument and an outer variable
+        let res = run("fn count(n: i32) {
+                total = 0;
+                if n > 0 { total = n + count(n - 1); total } else { 0 };
+                total
+            }
+            fn main() { count(4) }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(10)));
+
+        // let binds a new variable hiding the outer one until the block ends
+        let res = run("fn main() {
+                x = 1;
+                y = if true { let x = 10; x = x + 1; x };
+                x * 100 + y
+            }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(111)));
+        let res = run("fn main() { let x = \"a\"; let x = 2; x }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(2)));
+        // and it may hide a constant, which is still immutable once the block ends
+        let res = run("fn main() { const c = 1; x = if true { let c = 2; c = c + 1; c }; c + x }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(4)));
+        let res = run("fn main() { const c = 1; let c: i32; c = 3; c }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(3)));
+        let err = run("fn main() { const c = 1; if true { let c = 2; c }; c = 2; c }").unwrap_err();
+        assert!(matches!(
+            err.error_type,
+            RuntimeErrorType::MutationOfImmutable(_)
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn for_in_loop() {
+        let res = run("fn main() { sum = 0; for x in [1, 2, 3] { sum = sum + x; sum }; sum }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(6)));
+        // the element is only bound inside the loop
+        let res = run("fn main() { x = 0; for x in [5] { x = x + 1; x }; x }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(0)));
+        let res = run("fn main() { for s in [\"a\"] { s } }");
+        assert_eq!(res.unwrap(), VarVal::UNIT);
+
+        let res = run("fn main() { p = 1; for i in 1..5 { p = p * i; p }; p }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(24)));
+        let res = run("fn main() { p = 1; for i in 1..=5 { p = p * i; p }; p }");
+        assert_eq!(res.unwrap(), VarVal::I32(Some(120)));
+        let input = format!("fn main() {{ for i in 0..={} {{ i }} }}", MAX_RANGE_LEN);
+        let err = run(&input).unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::RangeTooLong));
+        assert!(parse("fn main() { 0..=1..=2 }").is_err());
+
+        let input = "fn main() { for x in 3 { x } }";
+        let err = run(input).unwrap_err();
+        assert!(matches!(err.error_type, RuntimeErrorType::InvalidOperands));
+        assert_eq!((err.position, err.end), (21, 22));
+    }
 }
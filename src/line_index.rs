@@ -0,0 +1,88 @@
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+
+/// Conversion of byte offsets in a source to lines and columns
+pub struct LineIndex<'src> {
+    src: &'src str,
+    /// Byte offset of the first character of each line
+    line_starts: Vec<usize>,
+}
+
+impl<'src> LineIndex<'src> {
+    pub fn new(src: &'src str) -> Self {
+        let line_starts = core::iter::once(0)
+            .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        LineIndex { src, line_starts }
+    }
+
+    /// 1-based line and column of byte `offset`, columns count characters,
+    /// offsets past the end of the source point behind its last character
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.src.len());
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let start = self.line_starts[line];
+        let column = self.src[start..]
+            .char_indices()
+            .take_while(|(i, _)| start + i < offset)
+            .count();
+        (line + 1, column + 1)
+    }
+
+    /// Text of 1-based `line` without its line ending, empty for lines past the end
+    pub fn line(&self, line: usize) -> &'src str {
+        let start = match self.line_starts.get(line.wrapping_sub(1)) {
+            Some(&start) => start,
+            None => return "",
+        };
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.src.len(), |&next| next - 1);
+        let text = &self.src[start..end.max(start)];
+        text.strip_suffix('\r').unwrap_or(text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn multi_line() {
+        let src = "fn main() {\n    a = 1;\n\n    a\n}\n";
+        let index = LineIndex::new(src);
+        assert_eq!(index.position(0), (1, 1));
+        assert_eq!(index.position(src.find("a =").unwrap()), (2, 5));
+        assert_eq!(index.position(src.find('\n').unwrap()), (1, 12));
+        assert_eq!(index.position(src.find("\n\n").unwrap() + 1), (3, 1));
+        assert_eq!(index.position(src.rfind('}').unwrap()), (5, 1));
+        assert_eq!(index.position(src.len()), (6, 1));
+        assert_eq!(index.position(src.len() + 10), (6, 1));
+    }
+
+    #[test]
+    fn crlf() {
+        let src = "a\r\nbc\r\nd";
+        let index = LineIndex::new(src);
+        assert_eq!(index.position(1), (1, 2));
+        assert_eq!(index.position(4), (2, 2));
+        assert_eq!(index.position(7), (3, 1));
+        assert_eq!(index.line(1), "a");
+        assert_eq!(index.line(2), "bc");
+        assert_eq!(index.line(3), "d");
+        assert_eq!(index.line(4), "");
+        assert_eq!(index.line(0), "");
+    }
+
+    #[test]
+    fn last_line_without_newline() {
+        let src = "x\nčaj = 1";
+        let index = LineIndex::new(src);
+        assert_eq!(index.position(src.find('=').unwrap()), (2, 5));
+        assert_eq!(index.position(src.len()), (2, 8));
+    }
+}
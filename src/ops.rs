@@ -0,0 +1,347 @@
+use crate::ast::{DataType, Opcode, VarVal};
+use crate::{RuntimeErrorType, MAX_RANGE_LEN};
+use core::convert::TryFrom;
+
+impl VarVal {
+    /// Apply binary operator `op` to the value and `rhs`, operands must be non-null
+    /// values of the same type, each type supports its own set of operators
+    pub fn apply_op(&self, op: &Opcode, rhs: &VarVal) -> Result<VarVal, RuntimeErrorType> {
+        if is_null(self) || is_null(rhs) {
+            return Err(RuntimeErrorType::NullDereference);
+        }
+        match (self, rhs) {
+            (VarVal::I32(Some(l)), VarVal::I32(Some(r))) => i32_op(*l, op, *r),
+            (VarVal::BOOL(Some(l)), VarVal::BOOL(Some(r))) => bool_op(*l, op, *r),
+            (VarVal::CHAR(Some(l)), VarVal::CHAR(Some(r))) => char_op(*l, op, *r),
+            (VarVal::STRING(Some(l)), VarVal::STRING(Some(r))) => string_op(l, op, r),
+            // an empty array literal has unknown element type and fits arrays of any type
+            (VarVal::ARRAY(le, Some(l)), VarVal::ARRAY(re, Some(r)))
+                if le == re || l.is_empty() || r.is_empty() =>
+            {
+                array_op(l, op, r)
+            }
+            _ => Err(RuntimeErrorType::InvalidOperands),
+        }
+    }
+}
+
+/// Test if the value is null, unit is a value of its own type and isn't null here
+fn is_null(value: &VarVal) -> bool {
+    match value {
+        VarVal::I32(v) => v.is_none(),
+        VarVal::BOOL(v) => v.is_none(),
+        VarVal::STRING(v) => v.is_none(),
+        VarVal::CHAR(v) => v.is_none(),
+        VarVal::STRUCT(_, v) => v.is_none(),
+        VarVal::ARRAY(_, v) => v.is_none(),
+        VarVal::MAP(_, v) => v.is_none(),
+        VarVal::UNIT => false,
+    }
+}
+
+fn i32_op(l: i32, op: &Opcode, r: i32) -> Result<VarVal, RuntimeErrorType> {
+    let checked = |result: Option<i32>| {
+        result
+            .map(|v| VarVal::I32(Some(v)))
+            .ok_or(RuntimeErrorType::IntegerOverflow)
+    };
+    match op {
+        Opcode::Add => checked(l.checked_add(r)),
+        Opcode::Sub => checked(l.checked_sub(r)),
+        Opcode::Mul => checked(l.checked_mul(r)),
+        Opcode::Div | Opcode::Mod if r == 0 => Err(RuntimeErrorType::DivisionByZero),
+        Opcode::Div => checked(l.checked_div(r)),
+        Opcode::Mod => checked(l.checked_rem(r)),
+        Opcode::Pow => match u32::try_from(r) {
+            Ok(r) => checked(l.checked_pow(r)),
+            Err(_) => Err(RuntimeErrorType::NegativeExponent),
+        },
+        Opcode::Eq => Ok(VarVal::BOOL(Some(l == r))),
+        Opcode::Ne => Ok(VarVal::BOOL(Some(l != r))),
+        Opcode::Lt => Ok(VarVal::BOOL(Some(l < r))),
+        Opcode::Le => Ok(VarVal::BOOL(Some(l <= r))),
+        Opcode::Gt => Ok(VarVal::BOOL(Some(l > r))),
+        Opcode::Ge => Ok(VarVal::BOOL(Some(l >= r))),
+        Opcode::Range => range(l, r),
+        Opcode::RangeInclusive => checked_range(l, i64::from(r) + 1),
+        _ => Err(RuntimeErrorType::InvalidOpcode),
+    }
+}
+
+/// Array of integers from `start` up to `end` excluded, empty if `end` isn't greater,
+/// ranges longer than `MAX_RANGE_LEN` are an error
+pub(crate) fn range(start: i32, end: i32) -> Result<VarVal, RuntimeErrorType> {
+    checked_range(start, i64::from(end))
+}
+
+/// Like `range` with `end` one past `i32::MAX` allowed for inclusive ranges
+fn checked_range(start: i32, end: i64) -> Result<VarVal, RuntimeErrorType> {
+    if end - i64::from(start) > MAX_RANGE_LEN as i64 {
+        return Err(RuntimeErrorType::RangeTooLong);
+    }
+    let elements = (i64::from(start)..end)
+        .map(|v| VarVal::I32(Some(v as i32)))
+        .collect();
+    Ok(VarVal::ARRAY(DataType::I32, Some(elements)))
+}
+
+fn bool_op(l: bool, op: &Opcode, r: bool) -> Result<VarVal, RuntimeErrorType> {
+    match op {
+        Opcode::Eq => Ok(VarVal::BOOL(Some(l == r))),
+        Opcode::Ne => Ok(VarVal::BOOL(Some(l != r))),
+        Opcode::And => Ok(VarVal::BOOL(Some(l && r))),
+        Opcode::Or => Ok(VarVal::BOOL(Some(l || r))),
+        _ => Err(RuntimeErrorType::InvalidOpcode),
+    }
+}
+
+fn char_op(l: char, op: &Opcode, r: char) -> Result<VarVal, RuntimeErrorType> {
+    match op {
+        // ordinal difference of the characters
+        Opcode::Sub => Ok(VarVal::I32(Some(l as i32 - r as i32))),
+        Opcode::Eq => Ok(VarVal::BOOL(Some(l == r))),
+        Opcode::Ne => Ok(VarVal::BOOL(Some(l != r))),
+        Opcode::Lt => Ok(VarVal::BOOL(Some(l < r))),
+        Opcode::Le => Ok(VarVal::BOOL(Some(l <= r))),
+        Opcode::Gt => Ok(VarVal::BOOL(Some(l > r))),
+        Opcode::Ge => Ok(VarVal::BOOL(Some(l >= r))),
+        _ => Err(RuntimeErrorType::InvalidOpcode),
+    }
+}
+
+fn string_op(l: &str, op: &Opcode, r: &str) -> Result<VarVal, RuntimeErrorType> {
+    match op {
+        Opcode::Eq => Ok(VarVal::BOOL(Some(l == r))),
+        Opcode::Ne => Ok(VarVal::BOOL(Some(l != r))),
+        _ => Err(RuntimeErrorType::InvalidOpcode),
+    }
+}
+
+/// Arrays are equal when they have the same length and equal elements
+fn array_op(l: &[VarVal], op: &Opcode, r: &[VarVal]) -> Result<VarVal, RuntimeErrorType> {
+    match op {
+        Opcode::Eq => Ok(VarVal::BOOL(Some(l == r))),
+        Opcode::Ne => Ok(VarVal::BOOL(Some(l != r))),
+        _ => Err(RuntimeErrorType::InvalidOpcode),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ast::DataType;
+    #[cfg(not(feature = "std"))]
+    use crate::prelude::*;
+
+    const OPCODES: [Opcode; 16] = [
+        Opcode::Mul,
+        Opcode::Div,
+        Opcode::Mod,
+        Opcode::Pow,
+        Opcode::Add,
+        Opcode::Sub,
+        Opcode::Eq,
+        Opcode::Ne,
+        Opcode::Lt,
+        Opcode::Le,
+        Opcode::Gt,
+        Opcode::Ge,
+        Opcode::And,
+        Opcode::Or,
+        Opcode::Range,
+        Opcode::RangeInclusive,
+    ];
+
+    /// Results of all operators in the order of `OPCODES`, `None` for `InvalidOpcode`
+    fn results(l: &VarVal, r: &VarVal) -> Vec<Option<VarVal>> {
+        OPCODES
+            .iter()
+            .map(|op| match l.apply_op(op, r) {
+                Ok(v) => Some(v),
+                Err(RuntimeErrorType::InvalidOpcode) => None,
+                Err(e) => panic!("{} {:?} {}: {:?}", l, op, r, e),
+            })
+            .collect()
+    }
+
+    fn int(v: i32) -> Option<VarVal> {
+        Some(VarVal::I32(Some(v)))
+    }
+
+    fn boolean(v: bool) -> Option<VarVal> {
+        Some(VarVal::BOOL(Some(v)))
+    }
+
+    #[test]
+    fn i32_ops() {
+        let (t, f) = (boolean(true), boolean(false));
+        assert_eq!(
+            results(&VarVal::I32(Some(7)), &VarVal::I32(Some(2))),
+            [
+                int(14),
+                int(3),
+                int(1),
+                int(49),
+                int(9),
+                int(5),
+                f.clone(),
+                t.clone(),
+                f.clone(),
+                f.clone(),
+                t.clone(),
+                t,
+                None,
+                None,
+                Some(VarVal::ARRAY(DataType::I32, Some(Vec::new()))),
+                Some(VarVal::ARRAY(DataType::I32, Some(Vec::new())))
+            ]
+        );
+        let range = VarVal::I32(Some(-1)).apply_op(&Opcode::Range, &VarVal::I32(Some(2)));
+        assert_eq!(range.unwrap().to_string(), "[-1, 0, 1]");
+        let range =
+            |l, r| VarVal::I32(Some(l)).apply_op(&Opcode::RangeInclusive, &VarVal::I32(Some(r)));
+        assert_eq!(range(-1, 2).unwrap().to_string(), "[-1, 0, 1, 2]");
+        assert_eq!(range(3, 3).unwrap().to_string(), "[3]");
+        assert_eq!(
+            range(i32::MAX, i32::MAX).unwrap().to_string(),
+            "[2147483647]"
+        );
+        let len = MAX_RANGE_LEN as i32;
+        assert!(range(1, len).is_ok());
+        assert!(matches!(range(0, len), Err(RuntimeErrorType::RangeTooLong)));
+        let pow = |l, r| VarVal::I32(Some(l)).apply_op(&Opcode::Pow, &VarVal::I32(Some(r)));
+        assert!(matches!(
+            pow(2, -1),
+            Err(RuntimeErrorType::NegativeExponent)
+        ));
+        assert!(matches!(pow(2, 31), Err(RuntimeErrorType::IntegerOverflow)));
+        assert_eq!(pow(-2, 31).unwrap(), VarVal::I32(Some(i32::MIN)));
+
+        let op = |l, op, r| VarVal::I32(Some(l)).apply_op(&op, &VarVal::I32(Some(r)));
+        for (l, o, r) in [
+            (i32::MAX, Opcode::Add, 1),
+            (i32::MIN, Opcode::Sub, 1),
+            (i32::MAX, Opcode::Mul, 2),
+            (i32::MIN, Opcode::Div, -1),
+            (i32::MIN, Opcode::Mod, -1),
+        ] {
+            assert!(matches!(
+                op(l, o, r),
+                Err(RuntimeErrorType::IntegerOverflow)
+            ));
+        }
+        for o in [Opcode::Div, Opcode::Mod] {
+            assert!(matches!(op(1, o, 0), Err(RuntimeErrorType::DivisionByZero)));
+        }
+    }
+
+    #[test]
+    fn bool_ops() {
+        let (t, f) = (boolean(true), boolean(false));
+        let mut expected = vec![None; 6];
+        expected.extend(vec![f.clone(), t.clone()]);
+        expected.extend(vec![None; 4]);
+        expected.extend(vec![f, t, None, None]);
+        assert_eq!(
+            results(&VarVal::BOOL(Some(true)), &VarVal::BOOL(Some(false))),
+            expected
+        );
+    }
+
+    #[test]
+    fn char_ops() {
+        let (t, f) = (boolean(true), boolean(false));
+        let mut expected = vec![None; 5];
+        expected.extend(vec![
+            int(-2),
+            f.clone(),
+            t.clone(),
+            t.clone(),
+            t,
+            f.clone(),
+            f,
+        ]);
+        expected.extend(vec![None; 4]);
+        assert_eq!(
+            results(&VarVal::CHAR(Some('a')), &VarVal::CHAR(Some('c'))),
+            expected
+        );
+    }
+
+    #[test]
+    fn string_ops() {
+        let mut expected = vec![None; 6];
+        expected.extend(vec![boolean(false), boolean(true)]);
+        expected.extend(vec![None; 8]);
+        let (l, r) = ("a".to_string(), "b".to_string());
+        assert_eq!(
+            results(&VarVal::STRING(Some(l)), &VarVal::STRING(Some(r))),
+            expected
+        );
+    }
+
+    #[test]
+    fn array_ops() {
+        let array = |elements: &[i32]| {
+            let elements = elements.iter().map(|e| VarVal::I32(Some(*e))).collect();
+            VarVal::ARRAY(DataType::I32, Some(elements))
+        };
+        let mut expected = vec![None; 6];
+        expected.extend(vec![boolean(true), boolean(false)]);
+        expected.extend(vec![None; 8]);
+        assert_eq!(results(&array(&[1, 2]), &array(&[1, 2])), expected);
+        expected[6..8].swap(0, 1);
+        assert_eq!(results(&array(&[1, 2]), &array(&[1, 3])), expected);
+        assert_eq!(results(&array(&[1, 2]), &array(&[1, 2, 3])), expected);
+        let empty = VarVal::ARRAY(DataType::UNIT, Some(Vec::new()));
+        assert_eq!(results(&array(&[1]), &empty), expected);
+        assert_eq!(results(&array(&[]), &empty)[6], boolean(true));
+
+        let strings = VarVal::ARRAY(DataType::STRING, Some(vec![VarVal::STRING(None)]));
+        let res = array(&[1]).apply_op(&Opcode::Eq, &strings);
+        assert!(matches!(res, Err(RuntimeErrorType::InvalidOperands)));
+    }
+
+    #[test]
+    fn invalid_operands() {
+        let values = [
+            VarVal::I32(Some(1)),
+            VarVal::BOOL(Some(true)),
+            VarVal::CHAR(Some('a')),
+            VarVal::STRING(Some("a".to_string())),
+            VarVal::UNIT,
+        ];
+        for (i, l) in values.iter().enumerate() {
+            for (j, r) in values.iter().enumerate() {
+                if i != j {
+                    for op in &OPCODES {
+                        let res = l.apply_op(op, r);
+                        assert!(matches!(res, Err(RuntimeErrorType::InvalidOperands)));
+                    }
+                }
+            }
+        }
+        for op in &OPCODES {
+            let res = VarVal::UNIT.apply_op(op, &VarVal::UNIT);
+            assert!(matches!(res, Err(RuntimeErrorType::InvalidOperands)));
+        }
+    }
+
+    #[test]
+    fn null_operands() {
+        let nulls = [
+            VarVal::I32(None),
+            VarVal::BOOL(None),
+            VarVal::STRING(None),
+            VarVal::CHAR(None),
+        ];
+        for null in &nulls {
+            for op in &OPCODES {
+                let res = null.apply_op(op, &VarVal::I32(Some(1)));
+                assert!(matches!(res, Err(RuntimeErrorType::NullDereference)));
+                let res = VarVal::BOOL(Some(true)).apply_op(op, null);
+                assert!(matches!(res, Err(RuntimeErrorType::NullDereference)));
+            }
+        }
+    }
+}
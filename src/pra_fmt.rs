@@ -0,0 +1,52 @@
+use mylib::{format_source, render_parse_error};
+use std::fs;
+use std::io::Read;
+use std::process::exit;
+
+fn usage() {
+    eprintln!("pra-fmt [--check] [<file>]");
+}
+
+/// Format the file in place, or stdin to stdout when no file or `-` is given. With
+/// `--check` nothing is written and the exit code is 1 when the input isn't formatted
+fn main() {
+    let mut args = std::env::args().skip(1).peekable();
+    let check = args.next_if(|arg| arg == "--check").is_some();
+    let file = args.next().filter(|file| file != "-");
+    if args.next().is_some() {
+        usage();
+        exit(1);
+    }
+    let input = match &file {
+        Some(file) => fs::read_to_string(file),
+        None => {
+            let mut input = String::new();
+            std::io::stdin().read_to_string(&mut input).map(|_| input)
+        }
+    };
+    let input = input.unwrap_or_else(|e| {
+        eprintln!("OS error: {:#?}", e);
+        exit(1)
+    });
+    let formatted = format_source(&input).unwrap_or_else(|e| {
+        eprint!("{}", render_parse_error(&input, &e));
+        exit(1)
+    });
+    match file {
+        _ if check => {
+            if formatted != input {
+                eprintln!("{} is not formatted", file.as_deref().unwrap_or("<stdin>"));
+                exit(1);
+            }
+        }
+        Some(file) => {
+            if formatted != input {
+                if let Err(e) = fs::write(&file, formatted) {
+                    eprintln!("OS error: {:#?}", e);
+                    exit(1);
+                }
+            }
+        }
+        None => print!("{}", formatted),
+    }
+}
@@ -0,0 +1,608 @@
+use crate::ast::{
+    operator_chain, Block, Else, Expr, ExprType, Function, If, Opcode, Program, Stmt, StructDef,
+    VarVal,
+};
+use crate::lexer::Comment;
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+#[cfg(feature = "std")]
+use crate::{comments, parse, ParsingError};
+#[cfg(not(feature = "std"))]
+use alloc::format;
+
+/// Binding strength of the terms, nothing but terms can be an operand of `**` or the
+/// value of a field access
+const TERM: u8 = 7;
+
+/// Render `program` as source text which parses back into the same program apart from
+/// the positions, declarations keep their order and blocks are indented by four spaces
+///
+/// Only doc comments of functions are kept and method calls `a.len()` are written as
+/// the calls `len(a)` they stand for
+pub fn to_source(program: &Program) -> String {
+    Printer {
+        src: "",
+        comments: Vec::new(),
+        next_comment: 0,
+        last_end: 0,
+        docs: true,
+    }
+    .program(program)
+}
+
+/// Reformat `src` in the style of `to_source`, formatting the result again doesn't
+/// change it
+///
+/// Comments stay on their own line in front of the statement or declaration following
+/// them, or at the end of the line they end in the source. Single blank lines between
+/// statements and comments are kept too
+#[cfg(feature = "std")]
+#[allow(clippy::result_large_err)]
+pub fn format_source(src: &str) -> Result<String, ParsingError> {
+    let program = parse(src)?;
+    let mut printer = Printer {
+        src,
+        comments: comments(src)?,
+        next_comment: 0,
+        last_end: 0,
+        docs: false,
+    };
+    // the lexer skips the shebang line of a script
+    let shebang = match src.find('\n') {
+        Some(end) if src.starts_with("#!") => &src[..=end],
+        _ => "",
+    };
+    Ok(format!("{}{}", shebang, printer.program(&program)))
+}
+
+/// Declaration of the program, they are written in the order of their positions
+enum Declaration<'p> {
+    Struct(&'p StructDef),
+    Function(&'p Function),
+}
+
+impl Declaration<'_> {
+    fn span(&self) -> (usize, usize) {
+        match self {
+            Declaration::Struct(def) => (def.position, def.end),
+            Declaration::Function(function) => (function.position, function.end),
+        }
+    }
+}
+
+struct Printer<'a> {
+    src: &'a str,
+    /// Comments of `src` in the order of their positions
+    comments: Vec<Comment<'a>>,
+    /// Index of the first comment which wasn't written yet
+    next_comment: usize,
+    /// Position in `src` after the last written statement or comment, for keeping the
+    /// blank lines following it
+    last_end: usize,
+    /// Write `doc` of functions, when formatting source they are among `comments`
+    docs: bool,
+}
+
+impl<'a> Printer<'a> {
+    fn program(&mut self, program: &Program) -> String {
+        let structs = program.structs.values().map(Declaration::Struct);
+        let functions = program.functions.values().map(Declaration::Function);
+        let mut declarations: Vec<Declaration> = structs.chain(functions).collect();
+        declarations.sort_by_key(|declaration| declaration.span().0);
+        let mut out = String::new();
+        for declaration in &declarations {
+            // declarations are always separated by a blank line
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            let (position, end) = declaration.span();
+            out += &self.leading(position, 0, true);
+            out += &match declaration {
+                Declaration::Struct(def) => self.struct_def(def),
+                Declaration::Function(function) => self.function(function),
+            };
+            out += &self.trailing(end);
+            out.push('\n');
+            self.last_end = self.last_end.max(end);
+        }
+        out + &self.leading(usize::MAX, 0, declarations.is_empty())
+    }
+
+    fn function(&mut self, function: &Function) -> String {
+        let mut out = String::new();
+        let docs = function.doc.iter().filter(|_| self.docs);
+        for line in docs.flat_map(|doc| doc.lines()) {
+            let separator = if line.is_empty() { "" } else { " " };
+            out += &format!("///{}{}\n", separator, line);
+        }
+        let arguments: Vec<String> = function
+            .arguments
+            .iter()
+            .map(|arg| format!("{}: {}", arg.ident, arg.value.data_type()))
+            .collect();
+        out += &format!("fn {}({})", function.name, arguments.join(", "));
+        if let Some(return_type) = &function.return_type {
+            out += &format!(" -> {}", return_type);
+        }
+        out + " " + &self.block(&function.block, 0)
+    }
+
+    fn struct_def(&mut self, def: &StructDef) -> String {
+        if def.fields.is_empty() {
+            return format!("struct {} {{}}", def.name);
+        }
+        // a comma after each field, a line break after a type would end a statement
+        let mut out = format!("struct {} {{\n", def.name);
+        for (i, field) in def.fields.iter().enumerate() {
+            // structs loaded from JSON may come without the spans of fields
+            let (position, end) = def
+                .field_spans
+                .get(i)
+                .copied()
+                .unwrap_or((def.end, def.end));
+            out += &self.leading(position, 1, i == 0);
+            let ty = field.value.data_type();
+            out += &format!("    {}: {},{}\n", field.ident, ty, self.trailing(end));
+            self.last_end = self.last_end.max(end);
+        }
+        out += &self.leading(def.end, 1, false);
+        out + "}"
+    }
+
+    /// Block starting at the current position of a line indented `indent` levels
+    fn block(&mut self, block: &Block, indent: usize) -> String {
+        let inner = "    ".repeat(indent + 1);
+        let mut out = String::from("{\n");
+        for statement in &block.statements {
+            out += &self.leading(statement.position, indent + 1, out.len() == 2);
+            let (text, end) = match &statement.stmt {
+                Stmt::Expr(expr) => (self.expression(expr, indent + 1), expr.end),
+                Stmt::Asgn(id, expr) => {
+                    let value = self.expression(expr, indent + 1);
+                    (format!("{} = {}", id, value), expr.end)
+                }
+                Stmt::Const(id, expr) => {
+                    let value = self.expression(expr, indent + 1);
+                    (format!("const {} = {}", id, value), expr.end)
+                }
+                Stmt::Let(id, expr) => {
+                    let value = self.expression(expr, indent + 1);
+                    (format!("let {} = {}", id, value), expr.end)
+                }
+                Stmt::Decl(var) => {
+                    let decl = format!("let {}: {}", var.ident, var.value.data_type());
+                    (decl, statement.end)
+                }
+            };
+            out += &format!("{}{};{}\n", inner, text, self.trailing(end));
+            self.last_end = self.last_end.max(statement.end);
+        }
+        out += &self.leading(block.expr.position, indent + 1, out.len() == 2);
+        let tail = self.expression(&block.expr, indent + 1);
+        out += &format!("{}{}{}\n", inner, tail, self.trailing(block.expr.end));
+        self.last_end = self.last_end.max(block.expr.end);
+        out += &self.leading(block.end, indent + 1, false);
+        out + &"    ".repeat(indent) + "}"
+    }
+
+    fn expression(&mut self, expr: &Expr, indent: usize) -> String {
+        match &expr.expression_type {
+            ExprType::Var(id) => id.clone(),
+            ExprType::Value(value) => literal(value),
+            ExprType::Op(..) => {
+                let (mut lhs, chain) = operator_chain(expr);
+                let mut out: Option<String> = None;
+                for (op_expr, opc, rhs) in chain {
+                    let (symbol, left, right) = operator(opc);
+                    let mut text = match out {
+                        Some(text) if level(lhs) < left => format!("({})", text),
+                        Some(text) => text,
+                        None => self.operand(lhs, indent, left),
+                    };
+                    let rhs = self.operand(rhs, indent, right);
+                    match opc {
+                        Opcode::Range | Opcode::RangeInclusive => text.push_str(symbol),
+                        _ => text += &format!(" {} ", symbol),
+                    }
+                    out = Some(text + &rhs);
+                    lhs = op_expr;
+                }
+                out.unwrap_or_default()
+            }
+            ExprType::Function(id, args) => format!("{}({})", id, self.list(args, indent)),
+            ExprType::If(if_expr) => self.if_chain(if_expr, indent),
+            ExprType::ForIn(id, iter, body) => {
+                let iter = self.condition(iter, indent);
+                format!("for {} in {} {}", id, iter, self.block(body, indent))
+            }
+            ExprType::Struct(name, fields) if fields.is_empty() => format!("{} {{}}", name),
+            ExprType::Struct(name, fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field, value)| format!("{}: {}", field, self.expression(value, indent)))
+                    .collect();
+                format!("{} {{ {} }}", name, fields.join(", "))
+            }
+            ExprType::Field(value, field) => {
+                format!("{}.{}", self.operand(value, indent, TERM), field)
+            }
+            ExprType::Index(value, index) => {
+                let value = self.operand(value, indent, TERM);
+                format!("{}[{}]", value, self.expression(index, indent))
+            }
+            ExprType::Array(elements) if self.comment_inside(expr) => {
+                self.array_lines(elements, indent, expr.end)
+            }
+            ExprType::Array(elements) => format!("[{}]", self.list(elements, indent)),
+            ExprType::Map(entries) => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| {
+                        let key = self.expression(key, indent);
+                        format!("{}: {}", key, self.expression(value, indent))
+                    })
+                    .collect();
+                format!("{{{}}}", entries.join(", "))
+            }
+        }
+    }
+
+    fn list(&mut self, exprs: &[Box<Expr>], indent: usize) -> String {
+        let exprs: Vec<String> = exprs.iter().map(|e| self.expression(e, indent)).collect();
+        exprs.join(", ")
+    }
+
+    /// Array literal with each element on its own line, keeping the comments between them
+    fn array_lines(&mut self, elements: &[Box<Expr>], indent: usize, end: usize) -> String {
+        let inner = "    ".repeat(indent + 1);
+        let mut out = String::from("[\n");
+        for element in elements {
+            out += &self.leading(element.position, indent + 1, out.len() == 2);
+            let value = self.expression(element, indent + 1);
+            out += &format!("{}{},{}\n", inner, value, self.trailing(element.end));
+            self.last_end = self.last_end.max(element.end);
+        }
+        out += &self.leading(end, indent + 1, false);
+        out + &"    ".repeat(indent) + "]"
+    }
+
+    /// The next comment to write is inside the span of `expr`
+    fn comment_inside(&self, expr: &Expr) -> bool {
+        self.comments
+            .get(self.next_comment)
+            .is_some_and(|comment| (expr.position..expr.end).contains(&comment.span.0))
+    }
+
+    fn if_chain(&mut self, if_expr: &If, indent: usize) -> String {
+        let condition = self.condition(&if_expr.condition, indent);
+        let mut out = format!("if {} {}", condition, self.block(&if_expr.if_block, indent));
+        match &if_expr.else_part {
+            Else::Else(else_block) => out += &format!(" else {}", self.block(else_block, indent)),
+            Else::ElseIf(next) => out += &format!(" else {}", self.if_chain(next, indent)),
+            Else::None => (),
+        }
+        out
+    }
+
+    /// Condition of `if` or iterable of `for`, where the braces of a struct literal would
+    /// be taken for the block
+    fn condition(&mut self, expr: &Expr, indent: usize) -> String {
+        if has_struct_literal(expr) {
+            format!("({})", self.expression(expr, indent))
+        } else {
+            self.operand(expr, indent, 1)
+        }
+    }
+
+    /// Expression in a place which needs at least the binding strength `min`, see `level`
+    fn operand(&mut self, expr: &Expr, indent: usize, min: u8) -> String {
+        if level(expr) < min {
+            format!("({})", self.expression(expr, indent))
+        } else {
+            self.expression(expr, indent)
+        }
+    }
+
+    /// Comments before `position` which weren't written yet, each on its own line indented
+    /// by `indent` levels, and the blank line before `position` if the source has one.
+    /// Blank lines are dropped at the start of a block, where `first` is true
+    fn leading(&mut self, position: usize, indent: usize, mut first: bool) -> String {
+        let mut out = String::new();
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.span.0 >= position {
+                break;
+            }
+            let Comment { span, text } = comment.clone();
+            if !first && self.blank_line(span.0) {
+                out.push('\n');
+            }
+            out += &format!("{}{}\n", "    ".repeat(indent), text);
+            self.next_comment += 1;
+            self.last_end = span.1;
+            first = false;
+        }
+        if !first && self.blank_line(position) {
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Comment following the code ending at `end` and its `;` or `,` on the same line of
+    /// the source, with a space before it
+    fn trailing(&mut self, end: usize) -> String {
+        let comment = match self.comments.get(self.next_comment) {
+            Some(comment) if comment.span.0 >= end => comment.clone(),
+            _ => return String::new(),
+        };
+        let gap = &self.src[end..comment.span.0];
+        let separator = |ch: char| ch.is_whitespace() || ch == ';' || ch == ',';
+        if gap.contains('\n') || !gap.chars().all(separator) {
+            return String::new();
+        }
+        self.next_comment += 1;
+        self.last_end = comment.span.1;
+        format!(" {}", comment.text)
+    }
+
+    /// There is a blank line between the last written code and `position`
+    fn blank_line(&self, position: usize) -> bool {
+        self.src
+            .get(self.last_end..position)
+            .is_some_and(|gap| gap.trim().is_empty() && gap.matches('\n').count() > 1)
+    }
+}
+
+/// The expression contains a struct literal which isn't inside brackets
+fn has_struct_literal(expr: &Expr) -> bool {
+    match &expr.expression_type {
+        ExprType::Struct(..) => true,
+        ExprType::Op(..) => {
+            let (first, chain) = operator_chain(expr);
+            has_struct_literal(first) || chain.iter().any(|(_, _, rhs)| has_struct_literal(rhs))
+        }
+        ExprType::Field(value, _) | ExprType::Index(value, _) => has_struct_literal(value),
+        _ => false,
+    }
+}
+
+/// Binding strength of the expression following the levels of the grammar, `if` and
+/// `for` bind the loosest as they end with a block
+fn level(expr: &Expr) -> u8 {
+    match &expr.expression_type {
+        ExprType::If(_) | ExprType::ForIn(..) => 0,
+        ExprType::Op(_, opc, _) => match opc {
+            Opcode::Or | Opcode::Range | Opcode::RangeInclusive => 1,
+            Opcode::And => 2,
+            Opcode::Eq | Opcode::Ne | Opcode::Lt | Opcode::Le | Opcode::Gt | Opcode::Ge => 3,
+            Opcode::Add | Opcode::Sub => 4,
+            Opcode::Mul | Opcode::Div | Opcode::Mod => 5,
+            Opcode::Pow => 6,
+        },
+        _ => TERM,
+    }
+}
+
+/// Symbol of the operator and the levels its left and right operand need
+fn operator(opc: &Opcode) -> (&'static str, u8, u8) {
+    match opc {
+        Opcode::Or => ("||", 1, 2),
+        // ranges don't chain
+        Opcode::Range => ("..", 2, 2),
+        Opcode::RangeInclusive => ("..=", 2, 2),
+        Opcode::And => ("&&", 2, 3),
+        // comparisons don't chain either
+        Opcode::Eq => ("==", 4, 4),
+        Opcode::Ne => ("!=", 4, 4),
+        Opcode::Lt => ("<", 4, 4),
+        Opcode::Le => ("<=", 4, 4),
+        Opcode::Gt => (">", 4, 4),
+        Opcode::Ge => (">=", 4, 4),
+        Opcode::Add => ("+", 4, 5),
+        Opcode::Sub => ("-", 4, 5),
+        Opcode::Mul => ("*", 5, 6),
+        Opcode::Div => ("/", 5, 6),
+        Opcode::Mod => ("%", 5, 6),
+        // right associative
+        Opcode::Pow => ("**", TERM, 6),
+    }
+}
+
+fn literal(value: &VarVal) -> String {
+    match value {
+        VarVal::I32(Some(v)) => v.to_string(),
+        VarVal::BOOL(Some(v)) => v.to_string(),
+        VarVal::STRING(Some(v)) => quote(v, '"'),
+        VarVal::CHAR(Some(v)) => quote(v.encode_utf8(&mut [0; 4]), '\''),
+        // the parser produces no other values, they have no literals
+        value => value.to_string(),
+    }
+}
+
+/// Enclose `s` in `quote` escaping the characters the lexer needs escaped
+fn quote(s: &str, quote: char) -> String {
+    let mut out = String::new();
+    out.push(quote);
+    for ch in s.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            ch if ch == quote => {
+                out.push('\\');
+                out.push(ch);
+            }
+            ch if ch.is_control() => out += &format!("\\u{{{:x}}}", ch as u32),
+            ch => out.push(ch),
+        }
+    }
+    out.push(quote);
+    out
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    /// Source of the tail expression of `main` after a round trip
+    fn tail(expr: &str) -> String {
+        let source = to_source(&parse(&format!("fn main() {{ {} }}", expr)).unwrap());
+        let body = source.strip_prefix("fn main() {\n    ").unwrap();
+        body.strip_suffix("\n}\n").unwrap().to_string()
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(tail("(1 + 2) * 3 - (4 - 5)"), "(1 + 2) * 3 - (4 - 5)");
+        assert_eq!(tail("((1 * 2)) + (3 % 4)"), "1 * 2 + 3 % 4");
+        assert_eq!(tail("(2 ** 3) ** 2 ** -1"), "(2 ** 3) ** 2 ** -1");
+        assert_eq!(tail("(a < b) == (c || d && e)"), "(a < b) == (c || d && e)");
+        assert_eq!(tail("(a || b) || (c || d)"), "a || b || (c || d)");
+        assert_eq!(tail("(0..n)[1 + 1]"), "(0..n)[1 + 1]");
+        assert_eq!(tail("(a + b).x - -1"), "(a + b).x - -1");
+        assert_eq!(
+            tail("[(if a { 1 } else { 2 }) + 1]"),
+            "[(if a {\n        1\n    } else {\n        2\n    }) + 1]"
+        );
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(tail(r#""a\"b\\c\n\u{7}é""#), r#""a\"b\\c\n\u{7}é""#);
+        assert_eq!(tail(r"'\''"), r"'\''");
+        assert_eq!(tail("\"\"\"two\nlines\"\"\""), r#""two\nlines""#);
+        assert_eq!(tail("-2147483648"), "-2147483648");
+        assert_eq!(tail("{}.len() + [].len()"), "len({}) + len([])");
+    }
+
+    #[test]
+    fn struct_conditions() {
+        let input = "struct P { x: i32, y: [i32] }
+            struct E {}
+            fn main() { if (P { x: 1, y: [] }).x == 1 { E {} } else { E {} } }";
+        assert_eq!(
+            to_source(&parse(input).unwrap()),
+            "struct P {
+    x: i32,
+    y: [i32],
+}
+
+struct E {}
+
+fn main() {
+    if (P { x: 1, y: [] }.x == 1) {
+        E {}
+    } else {
+        E {}
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn statements() {
+        let input = "/// Sum of `items`
+            ///
+            ///  ignoring none
+            fn sum(items: [i32]) -> i32 { let total: i32; total = 0; const one = 1; for x in items { let y = x * one; total = total + y; total }; total }
+            fn main() { if false { 1 } else if true { sum(0..10) } else { sum(1..=3) } }";
+        assert_eq!(
+            to_source(&parse(input).unwrap()),
+            "/// Sum of `items`
+///
+///  ignoring none
+fn sum(items: [i32]) -> i32 {
+    let total: i32;
+    total = 0;
+    const one = 1;
+    for x in items {
+        let y = x * one;
+        total = total + y;
+        total
+    };
+    total
+}
+
+fn main() {
+    if false {
+        1
+    } else if true {
+        sum(0..10)
+    } else {
+        sum(1..=3)
+    }
+}
+"
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let input = "#!/usr/bin/env pra
+// header
+
+/// Doc of f
+fn f(a: i32) { // opening
+    x = a + 1 // after x
+
+
+    /* block */ y = x*2;   // after y
+    y // tail
+    // closing
+} // end f
+// before P
+struct P { // fields
+    x: i32, // x
+
+    // y next
+    y: i32
+    // last
+}
+fn main() { a = [1, // one
+  // two
+  2]; f(a[0]) }
+
+// end";
+        assert_eq!(
+            format_source(input).unwrap(),
+            "#!/usr/bin/env pra
+// header
+
+/// Doc of f
+fn f(a: i32) {
+    // opening
+    x = a + 1; // after x
+
+    /* block */
+    y = x * 2; // after y
+    y // tail
+    // closing
+} // end f
+
+// before P
+struct P {
+    // fields
+    x: i32, // x
+
+    // y next
+    y: i32,
+    // last
+}
+
+fn main() {
+    a = [
+        1, // one
+        // two
+        2,
+    ];
+    f(a[0])
+}
+
+// end
+"
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use crate::Map;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::time::Duration;
+
+/// Number of calls of a function and the time spent in them,
+/// the time includes nested calls, so recursive calls are counted repeatedly
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct FunctionProfile {
+    pub calls: u64,
+    pub time: Duration,
+}
+
+/// Per function statistics of a run, collected with `Interpreter::with_profiling`
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Profile {
+    functions: Map<String, FunctionProfile>,
+}
+
+impl Profile {
+    pub(crate) fn record(&mut self, name: &str, time: Duration) {
+        let function = self.functions.entry(name.to_string()).or_default();
+        function.calls += 1;
+        function.time += time;
+    }
+
+    /// Statistics of function `name`, `None` if it was never called
+    pub fn function(&self, name: &str) -> Option<&FunctionProfile> {
+        self.functions.get(name)
+    }
+
+    /// Called functions ordered by the time spent in them, the slowest first
+    pub fn report(&self) -> Vec<(&str, &FunctionProfile)> {
+        let mut report: Vec<_> = self
+            .functions
+            .iter()
+            .map(|(name, function)| (name.as_str(), function))
+            .collect();
+        report.sort_by(|a, b| b.1.time.cmp(&a.1.time).then(a.0.cmp(b.0)));
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parse, Interpreter};
+    use std::collections::HashMap;
+
+    #[test]
+    fn recursive_calls() {
+        let program = parse(
+            "fn fib(n: i32) { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }
+            fn unused() { 0 }
+            fn main() { fib(10) }",
+        )
+        .unwrap();
+        let (mut globals, mut buildins) = (HashMap::new(), HashMap::new());
+        let mut interpreter =
+            Interpreter::new(&program, &mut globals, &mut buildins).with_profiling();
+        interpreter.run().unwrap();
+        let profile = interpreter.profile().unwrap();
+
+        assert_eq!(profile.function("fib").unwrap().calls, 177);
+        assert_eq!(profile.function("main").unwrap().calls, 1);
+        assert!(profile.function("unused").is_none());
+        let report = profile.report();
+        assert_eq!(report.len(), 2);
+        assert!(report[0].1.time >= report[1].1.time);
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let program = parse("fn main() { 0 }").unwrap();
+        let (mut globals, mut buildins) = (HashMap::new(), HashMap::new());
+        let mut interpreter = Interpreter::new(&program, &mut globals, &mut buildins);
+        interpreter.run().unwrap();
+        assert!(interpreter.profile().is_none());
+    }
+}
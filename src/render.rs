@@ -0,0 +1,247 @@
+use crate::line_index::LineIndex;
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use crate::{Diagnostic, ParsingError, RuntimeError, Severity};
+use alloc::format;
+use core::fmt::Write;
+
+/// Describe a parsing error the way rustc does, with the source lines of its span underlined
+pub fn render_parse_error(src: &str, error: &ParsingError) -> String {
+    render_diagnostic(src, &error.into())
+}
+
+/// Describe a runtime error the way rustc does, with the span of the failed node underlined
+pub fn render_runtime_error(src: &str, error: &RuntimeError) -> String {
+    render_diagnostic(src, &error.into())
+}
+
+/// Describe a diagnostic the way rustc does, with its span underlined, followed by notes
+/// about the related places
+pub fn render_diagnostic(src: &str, diagnostic: &Diagnostic) -> String {
+    let severity = match diagnostic.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+    };
+    let header = match diagnostic.code {
+        Some(code) => format!("{}[{}]", severity, code),
+        None => severity.to_string(),
+    };
+    let name = diagnostic.source_name.as_deref();
+    let (from, to) = diagnostic.span;
+    let mut out = render(src, name, (from, to), &header, &diagnostic.message);
+    for (span, note) in &diagnostic.related {
+        out += &render(src, name, *span, "note", note);
+    }
+    out
+}
+
+/// Render `message` under `header`, like `error[E0001]`, followed by the span in the source
+/// named `name`
+fn render(
+    src: &str,
+    name: Option<&str>,
+    (from, to): (usize, usize),
+    header: &str,
+    message: &str,
+) -> String {
+    // an error at the end of input points behind the last line, not on a new empty one
+    let clamp = |offset: usize| match offset.min(src.len()) {
+        offset if offset == src.len() && src.ends_with('\n') => offset - 1,
+        offset => offset,
+    };
+    let (from, to) = (clamp(from), clamp(to.max(from)));
+    let index = LineIndex::new(src);
+    let (first, column) = index.position(from);
+    let (last, end_column) = index.position(to);
+
+    let width = last.to_string().len();
+    let mut out = format!("{}: {}\n", header, message);
+    let name = name.map_or(String::new(), |name| format!("{}:", name));
+    let _ = writeln!(out, "{:w$}--> {}{}:{}", "", name, first, column, w = width);
+    let _ = writeln!(out, "{:w$} |", "", w = width);
+    if first == last {
+        snippet(
+            &mut out,
+            width,
+            first,
+            index.line(first),
+            column,
+            end_column,
+        );
+    } else {
+        let text = index.line(first);
+        snippet(
+            &mut out,
+            width,
+            first,
+            text,
+            column,
+            text.chars().count() + 1,
+        );
+        if last > first + 1 {
+            let _ = writeln!(out, "...");
+        }
+        // a span ending with a line break doesn't underline the following line
+        if end_column > 1 {
+            snippet(&mut out, width, last, index.line(last), 1, end_column);
+        }
+    }
+    out
+}
+
+/// Write line `number` of the source with columns `start..end` underlined, at least one
+fn snippet(out: &mut String, width: usize, number: usize, text: &str, start: usize, end: usize) {
+    let line = format!("{:>w$} | {}", number, text, w = width);
+    let _ = writeln!(out, "{}", line.trim_end());
+    let _ = writeln!(
+        out,
+        "{:w$} | {}{}",
+        "",
+        " ".repeat(start - 1),
+        "^".repeat(end.saturating_sub(start).max(1)),
+        w = width
+    );
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::{execute, parse, Map};
+
+    fn parse_error(src: &str) -> String {
+        render_parse_error(src, &parse(src).unwrap_err())
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            parse_error("fn main() {\n    a = 99999999999;\n    a\n}\n"),
+            "error[E0111]: Integer literal 99999999999 doesn't fit into i32
+ --> 2:9
+  |
+2 |     a = 99999999999;
+  |         ^^^^^^^^^^^
+"
+        );
+        assert_eq!(
+            parse_error("fn main() {\n    \"čaj\" ? 1\n}"),
+            "error[E0101]: Unexpected character ?
+ --> 2:11
+  |
+2 |     \"čaj\" ? 1
+  |           ^
+"
+        );
+    }
+
+    #[test]
+    fn spans_across_lines() {
+        assert_eq!(
+            parse_error("fn main() {\n  a = \"\"\"one\n\n  three\n"),
+            "error[E0110]: Unterminated string literal
+ --> 2:7
+  |
+2 |   a = \"\"\"one
+  |       ^^^^^^
+...
+4 |   three
+  | ^^^^^^^
+"
+        );
+        let src = "\n".repeat(9) + "fn main() { a = /* x\n*";
+        assert_eq!(
+            parse_error(&src),
+            "error[E0107]: Unterminated block comment
+  --> 10:17
+   |
+10 | fn main() { a = /* x
+   |                 ^^^^
+11 | *
+   | ^
+"
+        );
+    }
+
+    #[test]
+    fn errors_at_eof() {
+        let out = parse_error("fn main() {\n  1\n");
+        assert!(out.starts_with("error[E0104]: unexpected end of file, expected one of"));
+        assert!(out.ends_with("\n --> 2:4\n  |\n2 |   1\n  |    ^\n"));
+        assert_eq!(
+            render("", None, (0, 0), "error[E0015]", "no main function"),
+            "error[E0015]: no main function
+ --> 1:1
+  |
+1 |
+  | ^
+"
+        );
+    }
+
+    #[test]
+    fn runtime_error() {
+        let src = "fn main() {\r\n  a = 1;\r\n  a + b\r\n}";
+        let program = parse(src).unwrap();
+        let err = execute(&program, &mut Map::new(), &mut Map::new()).unwrap_err();
+        assert_eq!(
+            render_runtime_error(src, &err),
+            "error[E0001]: Undefined variable b
+ --> 3:7
+  |
+3 |   a + b
+  |       ^
+"
+        );
+    }
+
+    #[test]
+    fn related_notes() {
+        let src = "fn f() { 1 }\nfn f() { 2 }\nfn main() { f() }";
+        let errors = crate::parse_all(src).unwrap_err();
+        assert_eq!(
+            render_diagnostic(src, &errors[0]),
+            "error[E0114]: function f is already defined at 1:1
+ --> 2:1
+  |
+2 | fn f() { 2 }
+  | ^^^^
+note: function f is first defined here
+ --> 1:1
+  |
+1 | fn f() { 1 }
+  | ^^^^
+"
+        );
+    }
+
+    #[test]
+    fn source_names() {
+        let src = "fn main() {\n  a = 1 < 2 < 3;\n  a\n}";
+        let errors = crate::parse_all_named(src, "src/main.pra").unwrap_err();
+        assert_eq!(
+            render_diagnostic(src, &errors[0]),
+            "error[E0113]: Comparison operators can't be chained, combine comparisons with &&
+ --> src/main.pra:2:13
+  |
+2 |   a = 1 < 2 < 3;
+  |             ^
+"
+        );
+    }
+
+    #[test]
+    fn diagnostic() {
+        let src = "fn main() {\n  if true { a = 1; a }\n  a\n}";
+        let diagnostics = crate::check(&parse(src).unwrap(), &[]);
+        assert_eq!(
+            render_diagnostic(src, &diagnostics[0]),
+            "error: Undefined variable a, it is only assigned inside an if or a for loop
+ --> 3:3
+  |
+3 |   a
+  |   ^
+"
+        );
+    }
+}
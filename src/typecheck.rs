@@ -0,0 +1,194 @@
+use crate::ast::{Block, DataType, Else, Expr, ExprType, If, Opcode, Program, Statement, Stmt};
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use crate::Map;
+use alloc::format;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// Mismatch between a declared and an inferred type
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct TypeError {
+    pub position: usize,
+    pub description: String,
+}
+
+/// Check that functions return values of their declared return types,
+/// expressions whose type can't be inferred without running the program are accepted
+pub fn check_types(program: &Program) -> Result<(), TypeError> {
+    let checker = Checker { program };
+    for function in program.functions.values() {
+        let expected = match &function.return_type {
+            Some(ty) => ty,
+            None => continue,
+        };
+        let mut locals = Map::new();
+        for var in &function.arguments {
+            locals.insert(var.ident.clone(), var.value.data_type());
+        }
+        match checker.block(&function.block, &mut locals) {
+            Some(actual) if &actual != expected => {
+                return Err(TypeError {
+                    position: function.block.expr.position,
+                    description: format!(
+                        "function {} should return {} but returns {}",
+                        function.name, expected, actual
+                    ),
+                })
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+struct Checker<'a> {
+    program: &'a Program,
+}
+
+impl Checker<'_> {
+    fn block(&self, block: &Block, locals: &mut Map<String, DataType>) -> Option<DataType> {
+        for Statement { stmt, .. } in &block.statements {
+            match stmt {
+                Stmt::Asgn(id, expr) | Stmt::Const(id, expr) | Stmt::Let(id, expr) => {
+                    match self.expr(expr, locals) {
+                        Some(ty) => {
+                            locals.insert(id.clone(), ty);
+                        }
+                        None => {
+                            locals.remove(id);
+                        }
+                    }
+                }
+                Stmt::Decl(var) => {
+                    locals.insert(var.ident.clone(), var.value.data_type());
+                }
+                Stmt::Expr(_) => (),
+            }
+        }
+        self.expr(&block.expr, locals)
+    }
+
+    fn if_expr(&self, if_expr: &If, locals: &Map<String, DataType>) -> Option<DataType> {
+        let if_type = self.block(&if_expr.if_block, &mut locals.clone())?;
+        let else_type = match &if_expr.else_part {
+            Else::Else(block) => self.block(block, &mut locals.clone()),
+            Else::ElseIf(next_if) => self.if_expr(next_if, locals),
+            Else::None => Some(DataType::UNIT),
+        }?;
+        if if_type == else_type {
+            Some(if_type)
+        } else {
+            None
+        }
+    }
+
+    fn expr(&self, expr: &Expr, locals: &Map<String, DataType>) -> Option<DataType> {
+        match &expr.expression_type {
+            ExprType::Value(value) => Some(value.data_type()),
+            ExprType::Var(id) => locals.get(id).cloned(),
+            ExprType::Op(_, opcode, _) => Some(match opcode {
+                Opcode::Mul
+                | Opcode::Div
+                | Opcode::Mod
+                | Opcode::Pow
+                | Opcode::Add
+                | Opcode::Sub => DataType::I32,
+                Opcode::Eq
+                | Opcode::Ne
+                | Opcode::Lt
+                | Opcode::Le
+                | Opcode::Gt
+                | Opcode::Ge
+                | Opcode::And
+                | Opcode::Or => DataType::BOOL,
+                Opcode::Range | Opcode::RangeInclusive => DataType::ARRAY(Box::new(DataType::I32)),
+            }),
+            ExprType::Function(name, _) => self.program.functions.get(name)?.return_type.clone(),
+            ExprType::If(if_expr) => self.if_expr(if_expr, locals),
+            ExprType::ForIn(..) => Some(DataType::UNIT),
+            ExprType::Struct(name, _) => Some(DataType::STRUCT(name.clone())),
+            ExprType::Field(value, field) => match self.expr(value, locals)? {
+                DataType::STRUCT(name) => self
+                    .program
+                    .structs
+                    .get(&name)?
+                    .fields
+                    .iter()
+                    .find(|f| &f.ident == field)
+                    .map(|f| f.value.data_type()),
+                _ => None,
+            },
+            ExprType::Index(value, _) => match self.expr(value, locals)? {
+                DataType::STRING => Some(DataType::CHAR),
+                DataType::ARRAY(element) | DataType::MAP(element) => Some(*element),
+                _ => None,
+            },
+            // empty literals take the element type of their siblings, alone it's unknown
+            ExprType::Array(elements) => self
+                .element_type(elements.iter(), locals)
+                .map(|ty| DataType::ARRAY(Box::new(ty))),
+            ExprType::Map(entries) => self
+                .element_type(entries.iter().map(|(_, value)| value), locals)
+                .map(|ty| DataType::MAP(Box::new(ty))),
+        }
+    }
+
+    fn element_type<'e>(
+        &self,
+        elements: impl Iterator<Item = &'e Box<Expr>>,
+        locals: &Map<String, DataType>,
+    ) -> Option<DataType> {
+        elements
+            .map(|element| self.expr(element, locals))
+            .find(|ty| ty.as_ref().is_none_or(|ty| !ty.has_unknown()))
+            .flatten()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn return_type_parsed() {
+        let program = parse("fn answer() -> i32 { 42 } fn main() { answer() }").unwrap();
+        assert_eq!(program.functions["answer"].return_type, Some(DataType::I32));
+        assert_eq!(program.functions["main"].return_type, None);
+        assert!(check_types(&program).is_ok());
+    }
+
+    #[test]
+    fn return_type_mismatch() {
+        let input = "fn answer(a: i32) -> i32 { b = a; b > 1 } fn main() { answer(1) }";
+        let program = parse(input).unwrap();
+        let err = check_types(&program).unwrap_err();
+        assert_eq!(err.position, input.find("b >").unwrap());
+        assert_eq!(
+            err.description,
+            "function answer should return i32 but returns bool"
+        );
+    }
+
+    #[test]
+    fn return_type_inferred() {
+        let program = parse(
+            "struct P { x: i32 }
+            fn flag() -> bool { true }
+            fn x(p: P) -> i32 { if flag() { p.x } else { 0 } }
+            fn maybe() -> i32 { if flag() { 1 } }
+            fn not(b: bool) -> i32 { if b { false } else if flag() { true } else { b } }
+            fn unknown() -> String { read_line() }
+            fn empty() -> [i32] { [] }
+            fn nested() -> {String: [i32]} { {\"a\": [], \"b\": [1]} }",
+        )
+        .unwrap();
+        let err = check_types(&program).unwrap_err();
+        assert_eq!(
+            err.description,
+            "function not should return i32 but returns bool"
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn check(file: &PathBuf) -> (i32, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_mybin"))
+        .arg("--check")
+        .arg(file)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    (output.status.code().unwrap(), stderr)
+}
+
+fn write_program(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("pra-lang-{}-{}", std::process::id(), name));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+#[test]
+fn check_valid_file() {
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("example_programs/fizzbuzz.srs");
+    let (code, stderr) = check(&file);
+    assert_eq!(stderr, "");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn check_does_not_execute() {
+    let file = write_program("print.srs", "fn main() { print(\"side effect\") }");
+    let output = Command::new(env!("CARGO_BIN_EXE_mybin"))
+        .arg("--check")
+        .arg(&file)
+        .output()
+        .unwrap();
+    fs::remove_file(&file).unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn check_invalid_files() {
+    let undefined = write_program("undefined.srs", "fn main() {\n  print(x)\n}\n");
+    let syntax = write_program("syntax.srs", "fn main() { 1 +; }");
+    let types = write_program("types.srs", "fn main() -> i32 { true }");
+    for file in &[undefined, syntax, types] {
+        let (code, stderr) = check(file);
+        fs::remove_file(file).unwrap();
+        assert_eq!(code, 1, "{}", stderr);
+        assert!(stderr.starts_with("error"), "{}", stderr);
+        let location = format!("--> {}:", file.display());
+        assert!(stderr.contains(&location), "{}", stderr);
+    }
+    let (code, _) = check(&PathBuf::from("no/such/file.srs"));
+    assert_eq!(code, 1);
+}
+
+#[test]
+fn main_arguments() {
+    let file = write_program("echo.srs", "fn main(arg: String) { print(arg) }");
+    let output = Command::new(env!("CARGO_BIN_EXE_mybin"))
+        .arg(&file)
+        .arg("hello world")
+        .output()
+        .unwrap();
+    fs::remove_file(&file).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hello world\n");
+}
+
+#[test]
+fn ast_json() {
+    let file = write_program("ast.srs", "fn helper() { 1 }\nfn main() { helper() }");
+    let output = Command::new(env!("CARGO_BIN_EXE_mybin"))
+        .arg("--ast")
+        .arg(&file)
+        .output()
+        .unwrap();
+    fs::remove_file(&file).unwrap();
+    assert!(output.status.success());
+    let ast: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let functions = ast["functions"].as_object().unwrap();
+    assert!(functions.contains_key("main"));
+    assert_eq!(functions["helper"]["name"], "helper");
+
+    let file = write_program("ast-error.srs", "fn main() { 1 + }");
+    let output = Command::new(env!("CARGO_BIN_EXE_mybin"))
+        .arg("--ast")
+        .arg(&file)
+        .output()
+        .unwrap();
+    fs::remove_file(&file).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let errors: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(errors[0]["code"], "E0102");
+}
+
+fn pra_fmt(args: &[&PathBuf]) -> (i32, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_pra-fmt"))
+        .args(args)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    (output.status.code().unwrap(), stderr)
+}
+
+#[test]
+fn format_check() {
+    let source = "fn main() { x = 1 // one\n  x*2 }\n";
+    let file = write_program("misformatted.srs", source);
+    let check = PathBuf::from("--check");
+    let (code, stderr) = pra_fmt(&[&check, &file]);
+    assert_eq!(code, 1);
+    assert!(
+        stderr.ends_with("misformatted.srs is not formatted\n"),
+        "{}",
+        stderr
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), source);
+
+    let (code, stderr) = pra_fmt(&[&file]);
+    assert_eq!((code, stderr.as_str()), (0, ""));
+    let formatted = fs::read_to_string(&file).unwrap();
+    assert_eq!(formatted, "fn main() {\n    x = 1; // one\n    x * 2\n}\n");
+    let (code, _) = pra_fmt(&[&check, &file]);
+    fs::remove_file(&file).unwrap();
+    assert_eq!(code, 0);
+
+    let file = write_program("broken.srs", "fn main() { 1 + }");
+    let (code, stderr) = pra_fmt(&[&file]);
+    assert_eq!(fs::read_to_string(&file).unwrap(), "fn main() { 1 + }");
+    fs::remove_file(&file).unwrap();
+    assert_eq!(code, 1);
+    assert!(stderr.starts_with("error"), "{}", stderr);
+}
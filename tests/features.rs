@@ -0,0 +1,32 @@
+//! Checks that the library builds without the default features, the dependencies are
+//! already downloaded by the build of the tests so this runs offline
+use std::path::Path;
+use std::process::Command;
+
+fn check(features: &[&str]) {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // a separate target directory, the one of the tests is locked while they run
+    let output = Command::new(env!("CARGO"))
+        .args(["check", "--lib", "--offline", "--no-default-features"])
+        .args(features)
+        .arg("--target-dir")
+        .arg(manifest_dir.join("target/features"))
+        .current_dir(manifest_dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn without_default_features() {
+    check(&[]);
+}
+
+#[test]
+fn without_serde() {
+    check(&["--features", "std"]);
+}
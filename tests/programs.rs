@@ -0,0 +1,125 @@
+//! Runs every `tests/programs/*.pra` program and compares the displayed result, or the
+//! error, with the paired `.expected` file. Set `UPDATE_EXPECTED=1` to rewrite the files
+use mylib::buildin::buildins_with_output;
+use mylib::{ast_diff, execute_with_source, format_source, parse, to_source};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Result of the program as written in `.expected` files, printed output is dropped
+fn run(source: &str) -> String {
+    let program = match parse(source) {
+        Ok(program) => program,
+        Err(e) => return e.to_string(),
+    };
+    let mut buildins = buildins_with_output(std::io::sink());
+    match execute_with_source(source, &program, &mut HashMap::new(), &mut buildins) {
+        Ok(value) => value.to_string(),
+        Err(e) => e.to_string(),
+    }
+}
+
+/// Lines of both texts side by side, `-` marks expected lines and `+` actual ones
+fn diff(expected: &str, actual: &str) -> String {
+    let (expected, actual): (Vec<_>, Vec<_>) =
+        (expected.lines().collect(), actual.lines().collect());
+    let mut out = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => out += &format!("  {}\n", e),
+            (e, a) => {
+                if let Some(e) = e {
+                    out += &format!("- {}\n", e);
+                }
+                if let Some(a) = a {
+                    out += &format!("+ {}\n", a);
+                }
+            }
+        }
+    }
+    out
+}
+
+fn programs() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "pra"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// The test programs together with the example programs
+fn corpus() -> Vec<PathBuf> {
+    let examples = Path::new(env!("CARGO_MANIFEST_DIR")).join("example_programs");
+    let mut paths = programs();
+    paths.extend(
+        fs::read_dir(examples)
+            .unwrap()
+            .map(|entry| entry.unwrap().path()),
+    );
+    paths
+}
+
+#[test]
+fn expected_results() {
+    let update = std::env::var_os("UPDATE_EXPECTED").is_some();
+    let mut failures = Vec::new();
+    let paths = programs();
+    assert!(!paths.is_empty());
+    for path in paths {
+        let source = fs::read_to_string(&path).unwrap();
+        // loops are written as recursion, which needs more than the default stack
+        let actual = std::thread::Builder::new()
+            .stack_size(64 * 1024 * 1024)
+            .spawn(move || run(&source))
+            .unwrap()
+            .join()
+            .unwrap();
+        let expected_path = path.with_extension("expected");
+        if update {
+            fs::write(&expected_path, format!("{}\n", actual)).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&expected_path).unwrap_or_default();
+        if expected.trim_end() != actual {
+            failures.push(format!("{}:\n{}", path.display(), diff(&expected, &actual)));
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn source_round_trip() {
+    for path in corpus() {
+        let program = parse(&fs::read_to_string(&path).unwrap()).unwrap();
+        let source = to_source(&program);
+        let reparsed =
+            parse(&source).unwrap_or_else(|e| panic!("{}: {}\n{}", path.display(), e, source));
+        assert_eq!(ast_diff(&program, &reparsed), [], "{}", path.display());
+        let names: Vec<_> = program.functions.keys().collect();
+        assert!(reparsed.functions.keys().eq(names), "{}", path.display());
+        assert_eq!(to_source(&reparsed), source, "{}", path.display());
+    }
+}
+
+#[test]
+fn formatting_idempotent() {
+    for path in corpus() {
+        let source = fs::read_to_string(&path).unwrap();
+        let formatted = format_source(&source).unwrap();
+        assert_eq!(
+            format_source(&formatted).unwrap(),
+            formatted,
+            "{}",
+            path.display()
+        );
+        let (program, reformatted) = (parse(&source).unwrap(), parse(&formatted).unwrap());
+        assert_eq!(ast_diff(&program, &reformatted), [], "{}", path.display());
+        // comments are kept
+        let count = |s: &str| s.matches("//").count();
+        assert_eq!(count(&formatted), count(&source), "{}", path.display());
+    }
+}